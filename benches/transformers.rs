@@ -0,0 +1,65 @@
+//! Benchmarks for text transformations on large pastes
+//!
+//! Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ninepaste::recipe::{Recipe, Transformation};
+use ninepaste::transformers;
+
+/// A few hundred KB of mixed prose, links, contacts and markdown
+fn large_paste() -> String {
+    let block = "## Meeting notes\n\
+        - **Action**: email alice@example.com about the _launch_ plan\n\
+        - See https://example.com/docs/launch?ref=notes for details\n\
+        - Call   +1 555-123-4567   before   Friday\n\
+        \n\
+        12: The [roadmap](https://example.com/roadmap) is `final`.\n";
+    block.repeat(2000)
+}
+
+fn bench_transformers(c: &mut Criterion) {
+    let text = large_paste();
+    
+    c.bench_function("normalize_whitespace", |b| {
+        b.iter(|| transformers::normalize_whitespace(black_box(&text)))
+    });
+    c.bench_function("remove_urls", |b| {
+        b.iter(|| transformers::remove_urls(black_box(&text)))
+    });
+    c.bench_function("remove_emails", |b| {
+        b.iter(|| transformers::remove_emails(black_box(&text)))
+    });
+    c.bench_function("remove_markdown", |b| {
+        b.iter(|| transformers::remove_markdown(black_box(&text)))
+    });
+    c.bench_function("remove_line_numbers", |b| {
+        b.iter(|| transformers::remove_line_numbers(black_box(&text)))
+    });
+}
+
+fn bench_recipes(c: &mut Criterion) {
+    // Clipboard-sized text, where compiling patterns dominates
+    let text = "Order 12-34 shipped to alice@example.com";
+    
+    let mut recipe = Recipe::new("Bench");
+    recipe.add_transformation(Transformation::RegexReplace {
+        pattern: r"(?i)\b(\d+)-(\d+)\b".to_string(),
+        replacement: "$2-$1".to_string(),
+    });
+    recipe.add_transformation(Transformation::RegexReplace {
+        pattern: r"\b[\w.]+@[\w.]+\.\w{2,}\b".to_string(),
+        replacement: "[email]".to_string(),
+    });
+    recipe.add_transformation(Transformation::NormalizeWhitespace);
+    let compiled = recipe.compile();
+    
+    c.bench_function("recipe_apply", |b| {
+        b.iter(|| recipe.apply(black_box(text)))
+    });
+    c.bench_function("compiled_recipe_apply", |b| {
+        b.iter(|| compiled.apply(black_box(text)))
+    });
+}
+
+criterion_group!(benches, bench_transformers, bench_recipes);
+criterion_main!(benches);
@@ -0,0 +1,364 @@
+//! Application-aware recipe selection
+//!
+//! Finds out which application has focus when the clipboard changes, so
+//! rules in the config can apply a different recipe to what each application
+//! copies, or leave it alone (e.g. in a terminal or password manager).
+//! On X11 the focused window comes from `_NET_ACTIVE_WINDOW`; elsewhere no
+//! rule applies and the active recipe is used as before.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+#[cfg(target_os = "linux")]
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use tracing::debug;
+use tracing::info;
+
+use crate::recipe::{CompiledRecipe, Recipe};
+
+/// The focused window
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActiveWindow {
+    /// Window class, e.g. "firefox" (the second value `xprop WM_CLASS` shows)
+    pub class: String,
+    /// Window title
+    pub title: String,
+}
+
+impl ActiveWindow {
+    /// Create a new ActiveWindow
+    pub fn new(class: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            class: class.into(),
+            title: title.into(),
+        }
+    }
+}
+
+/// A rule choosing the recipe for content copied from matching applications
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppRule {
+    /// Text the window class must contain (case-insensitive), empty for any class
+    #[serde(default)]
+    pub class: String,
+    /// Text the window title must contain (case-insensitive), empty for any title
+    #[serde(default)]
+    pub title: String,
+    /// Name or ID of the recipe to apply, or None to leave the clipboard alone
+    pub recipe: Option<String>,
+}
+
+impl AppRule {
+    /// Create a rule for a window class
+    pub fn new(class: impl Into<String>, recipe: Option<&str>) -> Self {
+        Self {
+            class: class.into(),
+            title: String::new(),
+            recipe: recipe.map(String::from),
+        }
+    }
+    
+    /// Whether the rule applies to a window. A rule without any pattern never applies,
+    /// since the active recipe already covers every other application.
+    pub fn matches(&self, window: &ActiveWindow) -> bool {
+        if self.class.trim().is_empty() && self.title.trim().is_empty() {
+            return false;
+        }
+        contains_ignore_case(&window.class, &self.class) && contains_ignore_case(&window.title, &self.title)
+    }
+}
+
+impl fmt::Display for AppRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.class.trim(), self.title.trim()) {
+            (class, "") => write!(f, "{}", class),
+            ("", title) => write!(f, "\"{}\"", title),
+            (class, title) => write!(f, "{} \"{}\"", class, title),
+        }
+    }
+}
+
+/// Whether a haystack contains a non-blank needle, ignoring case
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    let needle = needle.trim();
+    needle.is_empty() || haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// Rules for a new config: never transform what password managers copy
+pub fn default_rules() -> Vec<AppRule> {
+    ["KeePassXC", "Bitwarden", "1Password"]
+        .into_iter()
+        .map(|class| AppRule::new(class, None))
+        .collect()
+}
+
+/// App rules with their recipes looked up and compiled
+#[derive(Debug, Clone, Default)]
+pub struct AppRules {
+    /// Rules in order of priority, with the recipe each applies
+    rules: Vec<(AppRule, Option<CompiledRecipe>)>,
+    /// Rules that were skipped because their recipe doesn't exist
+    problems: Vec<String>,
+}
+
+impl AppRules {
+    /// Look up the recipe of each rule by name or ID
+    pub fn new(rules: &[AppRule], recipes: &[Recipe]) -> Self {
+        let mut resolved = Vec::new();
+        let mut problems = Vec::new();
+        
+        for rule in rules {
+            let recipe = match rule.recipe {
+                Some(ref name) => match recipes.iter().find(|r| r.matches_name(name)) {
+                    Some(recipe) => Some(recipe.compile()),
+                    None => {
+                        problems.push(format!("App rule for {}: recipe not found: {}", rule, name));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            resolved.push((rule.clone(), recipe));
+        }
+        
+        Self {
+            rules: resolved,
+            problems,
+        }
+    }
+    
+    /// Check if there are no usable rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+    
+    /// Problems found while looking up recipes
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+    
+    /// Recipes the rules apply
+    pub fn recipes(&self) -> impl Iterator<Item = &CompiledRecipe> {
+        self.rules.iter().filter_map(|(_, recipe)| recipe.as_ref())
+    }
+    
+    /// Find the first rule that applies to a window, along with its recipe
+    /// (None if the rule leaves the clipboard alone)
+    pub fn select(&self, window: &ActiveWindow) -> Option<(&AppRule, Option<&CompiledRecipe>)> {
+        self.rules.iter()
+            .find(|(rule, _)| rule.matches(window))
+            .map(|(rule, recipe)| (rule, recipe.as_ref()))
+    }
+}
+
+/// A backend that tells which window has focus
+pub trait WindowSource: Send {
+    /// Name of the backend, for logging
+    fn name(&self) -> &'static str;
+    
+    /// Get the focused window, or None if it can't be determined
+    fn active_window(&self) -> Option<ActiveWindow>;
+}
+
+/// Pick the best window source available on this system
+pub fn default_window_source() -> Box<dyn WindowSource> {
+    #[cfg(target_os = "linux")]
+    match X11WindowSource::new() {
+        Ok(source) => {
+            info!("Using the X11 active window for app rules");
+            return Box::new(source);
+        }
+        Err(e) => debug!("X11 active window unavailable: {:#}", e),
+    }
+    
+    info!("Focused window unavailable, app rules are disabled");
+    Box::new(NoWindowSource)
+}
+
+/// Never knows the focused window, so no app rule applies
+pub struct NoWindowSource;
+
+impl WindowSource for NoWindowSource {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+    
+    fn active_window(&self) -> Option<ActiveWindow> {
+        None
+    }
+}
+
+/// Reports whatever window it was last given, for tests.
+/// Clones share the same window.
+#[derive(Debug, Clone, Default)]
+pub struct StaticWindowSource {
+    window: Arc<Mutex<Option<ActiveWindow>>>,
+}
+
+impl StaticWindowSource {
+    /// Create a new StaticWindowSource reporting a window
+    pub fn new(window: Option<ActiveWindow>) -> Self {
+        Self {
+            window: Arc::new(Mutex::new(window)),
+        }
+    }
+    
+    /// Change the reported window
+    pub fn set(&self, window: Option<ActiveWindow>) {
+        *self.window.lock().unwrap() = window;
+    }
+}
+
+impl WindowSource for StaticWindowSource {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+    
+    fn active_window(&self) -> Option<ActiveWindow> {
+        self.window.lock().unwrap().clone()
+    }
+}
+
+/// Reads the focused window from the EWMH `_NET_ACTIVE_WINDOW` root property
+#[cfg(target_os = "linux")]
+pub struct X11WindowSource {
+    conn: x11rb::rust_connection::RustConnection,
+    root: u32,
+    net_active_window: u32,
+    net_wm_name: u32,
+    utf8_string: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl X11WindowSource {
+    /// Connect to the X server
+    pub fn new() -> Result<Self> {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::ConnectionExt as _;
+        
+        let (conn, screen_num) = x11rb::connect(None)
+            .context("Failed to connect to X server")?;
+        let root = conn.setup().roots[screen_num].root;
+        
+        let net_active_window = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW")?.reply()?.atom;
+        let net_wm_name = conn.intern_atom(false, b"_NET_WM_NAME")?.reply()?.atom;
+        let utf8_string = conn.intern_atom(false, b"UTF8_STRING")?.reply()?.atom;
+        
+        Ok(Self {
+            conn,
+            root,
+            net_active_window,
+            net_wm_name,
+            utf8_string,
+        })
+    }
+    
+    /// Read the class and title of the focused window
+    fn query(&self) -> Result<Option<ActiveWindow>> {
+        use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+        
+        let active = self.conn
+            .get_property(false, self.root, self.net_active_window, AtomEnum::WINDOW, 0, 1)?
+            .reply()?;
+        let Some(window) = active.value32().and_then(|mut values| values.next()) else {
+            return Ok(None);
+        };
+        if window == x11rb::NONE {
+            return Ok(None);
+        }
+        
+        // WM_CLASS holds the instance and class names, each NUL-terminated
+        let wm_class = self.conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+            .reply()?;
+        let class = wm_class.value
+            .split(|&b| b == 0)
+            .rfind(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .unwrap_or_default();
+        
+        // Prefer the UTF-8 EWMH title over the legacy WM_NAME
+        let mut title = self.conn
+            .get_property(false, window, self.net_wm_name, self.utf8_string, 0, 1024)?
+            .reply()?
+            .value;
+        if title.is_empty() {
+            title = self.conn
+                .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 1024)?
+                .reply()?
+                .value;
+        }
+        
+        Ok(Some(ActiveWindow {
+            class,
+            title: String::from_utf8_lossy(&title).into_owned(),
+        }))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl WindowSource for X11WindowSource {
+    fn name(&self) -> &'static str {
+        "X11"
+    }
+    
+    fn active_window(&self) -> Option<ActiveWindow> {
+        match self.query() {
+            Ok(window) => window,
+            Err(e) => {
+                debug!("Failed to get the active window: {:#}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recipe::Transformation;
+    
+    #[test]
+    fn test_rule_matching() {
+        let firefox = ActiveWindow::new("firefox", "Pull requests · GitHub — Mozilla Firefox");
+        
+        assert!(AppRule::new("Firefox", None).matches(&firefox));
+        assert!(!AppRule::new("kitty", None).matches(&firefox));
+        
+        let mut rule = AppRule::new("firefox", None);
+        rule.title = "github".into();
+        assert!(rule.matches(&firefox));
+        rule.title = "GitLab".into();
+        assert!(!rule.matches(&firefox));
+        
+        // A rule without patterns would shadow the active recipe everywhere
+        assert!(!AppRule::new(" ", None).matches(&firefox));
+    }
+    
+    #[test]
+    fn test_select_rule() {
+        let mut upper = Recipe::new("Upper");
+        upper.add_transformation(Transformation::ToUppercase);
+        
+        let rules = AppRules::new(
+            &[
+                AppRule::new("kitty", None),
+                AppRule::new("firefox", Some("upper")),
+                AppRule::new("chromium", Some("Missing")),
+            ],
+            &[upper],
+        );
+        assert_eq!(rules.problems().len(), 1);
+        assert!(rules.problems()[0].contains("Missing"));
+        
+        let (_, recipe) = rules.select(&ActiveWindow::new("firefox", "")).unwrap();
+        assert_eq!(recipe.map(|r| r.name.as_str()), Some("Upper"));
+        
+        let (_, recipe) = rules.select(&ActiveWindow::new("kitty", "~")).unwrap();
+        assert!(recipe.is_none());
+        
+        // Rules with unknown recipes are skipped, leaving the active recipe in charge
+        assert!(rules.select(&ActiveWindow::new("chromium", "")).is_none());
+    }
+}
@@ -0,0 +1,707 @@
+//! Clipboard backends
+//!
+//! Low-level access to a clipboard's contents. `ClipboardManager` is generic
+//! over these so the monitor and recipe application can run against the
+//! system clipboard or an in-memory one in tests.
+
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+use std::fmt;
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use arboard::{Clipboard, Get, ImageData, Set};
+#[cfg(target_os = "linux")]
+use arboard::{GetExtLinux, LinuxClipboardKind, SetExtLinux};
+use serde::{Deserialize, Serialize};
+
+use crate::images::ClipboardImage;
+#[cfg(target_os = "linux")]
+use crate::images::ImageFormat;
+#[cfg(target_os = "linux")]
+use crate::wayland::{self, WaylandBackend};
+
+/// Clipboard format KDE and password managers offer on Linux to mark secrets
+#[cfg(target_os = "linux")]
+pub(crate) const PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+
+/// Clipboard format password managers offer on Windows to mark secrets
+#[cfg(target_os = "windows")]
+const EXCLUDE_FROM_MONITOR: &str = "ExcludeClipboardContentFromMonitorProcessing";
+
+/// Keeps the clipboard connection that serves HTML and images set in the background alive
+#[cfg(target_os = "linux")]
+static BACKGROUND_OWNER: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+/// Whether this process is the background service
+static BACKGROUND_SERVICE: AtomicBool = AtomicBool::new(false);
+
+/// How long to wait for the clipboard owner to list its formats
+#[cfg(target_os = "linux")]
+pub(crate) const TARGETS_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// A selection to read and write. Besides the clipboard, X11 and Wayland
+/// have the PRIMARY selection: the last selected text, pasted with a middle click.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selection {
+    /// The clipboard, pasted with Ctrl+V
+    #[default]
+    Clipboard,
+    /// The PRIMARY selection (Linux only)
+    Primary,
+}
+
+impl Selection {
+    /// Get the name of the selection, as xclip takes it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Clipboard => "clipboard",
+            Self::Primary => "primary",
+        }
+    }
+    
+    /// Fail for selections this platform doesn't have
+    pub fn check_available(&self) -> Result<()> {
+        if *self == Self::Primary && !cfg!(target_os = "linux") {
+            anyhow::bail!("The PRIMARY selection only exists on Linux");
+        }
+        Ok(())
+    }
+    
+    #[cfg(target_os = "linux")]
+    fn kind(&self) -> LinuxClipboardKind {
+        match self {
+            Self::Clipboard => LinuxClipboardKind::Clipboard,
+            Self::Primary => LinuxClipboardKind::Primary,
+        }
+    }
+    
+    /// Name of the selection's X11 atom
+    #[cfg(target_os = "linux")]
+    pub fn atom_name(&self) -> &'static str {
+        match self {
+            Self::Clipboard => "CLIPBOARD",
+            Self::Primary => "PRIMARY",
+        }
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clipboard" => Ok(Self::Clipboard),
+            "primary" => Ok(Self::Primary),
+            _ => Err(format!("Unknown selection: {} (expected clipboard or primary)", s)),
+        }
+    }
+}
+
+/// Clipboard text, along with its HTML flavour if there is one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClipboardContent {
+    /// Plain text
+    pub text: String,
+    /// HTML, e.g. from a browser copy
+    pub html: Option<String>,
+}
+
+impl ClipboardContent {
+    /// Content with only plain text
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            html: None,
+        }
+    }
+}
+
+/// Access to a clipboard's contents
+pub trait ClipboardBackend: Send + Sync + 'static {
+    /// Get the current clipboard text
+    fn get_text(&self) -> Result<String>;
+    
+    /// Set the clipboard text
+    fn set_text(&self, text: &str) -> Result<()>;
+    
+    /// Set the clipboard text without blocking the caller
+    /// Use this when the calling process needs to exit immediately (e.g., Quick Menu)
+    fn set_text_background(&self, text: &str) -> Result<()> {
+        self.set_text(text)
+    }
+    
+    /// Get the HTML flavour of the clipboard, if it has one
+    fn get_html(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+    
+    /// Set HTML along with a plain-text version for applications that only take text.
+    /// Backends without HTML support set just the text.
+    fn set_html(&self, _html: &str, alt_text: &str) -> Result<()> {
+        self.set_text(alt_text)
+    }
+    
+    /// Set HTML and its plain-text version without blocking the caller
+    fn set_html_background(&self, html: &str, alt_text: &str) -> Result<()> {
+        self.set_html(html, alt_text)
+    }
+    
+    /// Get the clipboard text, and its HTML flavour too if `with_html` is set
+    fn get_content(&self, with_html: bool) -> Result<ClipboardContent> {
+        let text = self.get_text()?;
+        let html = if with_html { self.get_html()? } else { None };
+        Ok(ClipboardContent { text, html })
+    }
+    
+    /// Set text, or HTML with its plain-text version, without blocking the caller
+    fn set_content_background(&self, content: &ClipboardContent) -> Result<()> {
+        match content.html {
+            Some(ref html) => self.set_html_background(html, &content.text),
+            None => self.set_text_background(&content.text),
+        }
+    }
+    
+    /// Get the clipboard image, if it holds one
+    fn get_image(&self) -> Result<Option<ClipboardImage>> {
+        Ok(None)
+    }
+    
+    /// Set the clipboard image
+    fn set_image(&self, _image: &ClipboardImage) -> Result<()> {
+        anyhow::bail!("This clipboard doesn't support images")
+    }
+    
+    /// Set the clipboard image without blocking the caller
+    fn set_image_background(&self, image: &ClipboardImage) -> Result<()> {
+        self.set_image(image)
+    }
+    
+    /// The selection this backend reads and writes
+    fn selection(&self) -> Selection {
+        Selection::Clipboard
+    }
+    
+    /// Whether the clipboard owner marked the content as secret, as password managers do
+    fn has_sensitive_hint(&self) -> bool {
+        false
+    }
+}
+
+/// Mark this process as the background service. It runs long enough to serve
+/// content set in the background itself, while other processes exit right
+/// after setting the clipboard and hand text to xclip/xsel.
+pub fn set_background_service() {
+    BACKGROUND_SERVICE.store(true, Ordering::SeqCst);
+}
+
+/// The system clipboard, accessed through arboard, or natively on Wayland
+/// compositors with the data-control protocols
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArboardBackend {
+    selection: Selection,
+}
+
+impl ArboardBackend {
+    /// The CLIPBOARD selection
+    pub const CLIPBOARD: Self = Self { selection: Selection::Clipboard };
+    
+    /// Create a backend for a selection
+    pub fn new(selection: Selection) -> Self {
+        Self { selection }
+    }
+    
+    /// Check if clipboard access is available
+    pub fn is_available() -> bool {
+        Clipboard::new().is_ok()
+    }
+    
+    /// Start reading the selection
+    fn get<'a>(&self, clipboard: &'a mut Clipboard) -> Result<Get<'a>> {
+        self.selection.check_available()?;
+        #[cfg(target_os = "linux")]
+        return Ok(clipboard.get().clipboard(self.selection.kind()));
+        #[cfg(not(target_os = "linux"))]
+        Ok(clipboard.get())
+    }
+    
+    /// The native Wayland clipboard, if the compositor supports data-control
+    #[cfg(target_os = "linux")]
+    fn wayland(&self) -> Option<WaylandBackend> {
+        wayland::is_available().then(|| WaylandBackend::new(self.selection))
+    }
+    
+    /// The native Wayland clipboard, if this process serves content set in the background
+    #[cfg(target_os = "linux")]
+    fn wayland_server(&self) -> Option<WaylandBackend> {
+        self.wayland().filter(|_| BACKGROUND_SERVICE.load(Ordering::SeqCst))
+    }
+    
+    /// Start setting the selection. On Linux, setting waits for the clipboard
+    /// manager to take ownership.
+    fn set<'a>(&self, clipboard: &'a mut Clipboard) -> Result<Set<'a>> {
+        self.selection.check_available()?;
+        // Use the Set builder with wait() for proper handover to clipboard manager on Linux
+        #[cfg(target_os = "linux")]
+        return Ok(clipboard.set().clipboard(self.selection.kind()).wait());
+        #[cfg(not(target_os = "linux"))]
+        Ok(clipboard.set())
+    }
+}
+
+impl ClipboardBackend for ArboardBackend {
+    fn get_text(&self) -> Result<String> {
+        #[cfg(target_os = "linux")]
+        if let Some(wayland) = self.wayland() {
+            return wayland.get_text();
+        }
+        let mut clipboard = Clipboard::new()
+            .context("Failed to access clipboard")?;
+        self.get(&mut clipboard)?
+            .text()
+            .context("Failed to get clipboard text")
+    }
+    
+    /// On Linux, this waits for the clipboard manager to take ownership
+    fn set_text(&self, text: &str) -> Result<()> {
+        let mut clipboard = Clipboard::new()
+            .context("Failed to access clipboard")?;
+        self.set(&mut clipboard)?
+            .text(text.to_string())
+            .context("Failed to set clipboard text")
+    }
+    
+    /// The background service serves the text itself, like HTML and images.
+    /// Other processes hand it to xclip/xsel, which keep serving it after they exit.
+    #[cfg(target_os = "linux")]
+    fn set_text_background(&self, text: &str) -> Result<()> {
+        if let Some(wayland) = self.wayland_server() {
+            return wayland.set_text_background(text);
+        }
+        if BACKGROUND_SERVICE.load(Ordering::SeqCst) {
+            let kind = self.selection.kind();
+            return with_background_owner(|clipboard| clipboard.set().clipboard(kind).text(text.to_string()))
+                .context("Failed to set clipboard text");
+        }
+        if XclipBackend::new(self.selection).set_text(text).is_ok() {
+            return Ok(());
+        }
+        
+        // Last resort: blocking set (will freeze until clipboard is read)
+        match self.wayland() {
+            Some(wayland) => wayland.set_text(text),
+            None => self.set_text(text),
+        }
+    }
+    
+    fn get_html(&self) -> Result<Option<String>> {
+        #[cfg(target_os = "linux")]
+        if let Some(wayland) = self.wayland() {
+            return wayland.get_html();
+        }
+        let mut clipboard = Clipboard::new()
+            .context("Failed to access clipboard")?;
+        match self.get(&mut clipboard)?.html() {
+            Ok(html) => Ok(Some(html)),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(e) => Err(e).context("Failed to get clipboard HTML"),
+        }
+    }
+    
+    /// On Linux, this waits for the clipboard manager to take ownership
+    fn set_html(&self, html: &str, alt_text: &str) -> Result<()> {
+        let mut clipboard = Clipboard::new()
+            .context("Failed to access clipboard")?;
+        self.set(&mut clipboard)?
+            .html(html, Some(alt_text))
+            .context("Failed to set clipboard HTML")
+    }
+    
+    /// xclip can only serve one format, so this process keeps serving both
+    /// for as long as it runs (or until a clipboard manager takes over).
+    /// The background service does so natively on Wayland.
+    #[cfg(target_os = "linux")]
+    fn set_html_background(&self, html: &str, alt_text: &str) -> Result<()> {
+        if let Some(wayland) = self.wayland_server() {
+            return wayland.set_html_background(html, alt_text);
+        }
+        let kind = self.selection.kind();
+        with_background_owner(|clipboard| clipboard.set().clipboard(kind).html(html, Some(alt_text)))
+            .context("Failed to set clipboard HTML")
+    }
+    
+    fn get_image(&self) -> Result<Option<ClipboardImage>> {
+        #[cfg(target_os = "linux")]
+        if let Some(wayland) = self.wayland() {
+            return wayland.get_image();
+        }
+        let mut clipboard = Clipboard::new()
+            .context("Failed to access clipboard")?;
+        match self.get(&mut clipboard)?.image() {
+            Ok(image) => ClipboardImage::from_rgba(image.width as u32, image.height as u32, image.bytes.into_owned())
+                .map(Some),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(e) => Err(e).context("Failed to get clipboard image"),
+        }
+    }
+    
+    /// Clipboards only take pixels through arboard, so the image is offered in
+    /// the platform's usual format (PNG on Linux) whatever its own format is.
+    /// On Linux, this waits for the clipboard manager to take ownership.
+    fn set_image(&self, image: &ClipboardImage) -> Result<()> {
+        let image = image_data(image)?;
+        let mut clipboard = Clipboard::new()
+            .context("Failed to access clipboard")?;
+        self.set(&mut clipboard)?
+            .image(image)
+            .context("Failed to set clipboard image")
+    }
+    
+    /// Like HTML, this process keeps serving the image for as long as it runs
+    #[cfg(target_os = "linux")]
+    fn set_image_background(&self, image: &ClipboardImage) -> Result<()> {
+        if let Some(wayland) = self.wayland_server() {
+            return wayland.set_image_background(image);
+        }
+        let image = image_data(image)?;
+        let kind = self.selection.kind();
+        with_background_owner(|clipboard| clipboard.set().clipboard(kind).image(image))
+            .context("Failed to set clipboard image")
+    }
+    
+    fn selection(&self) -> Selection {
+        self.selection
+    }
+    
+    /// The clipboard monitor gets the hint from its change detector instead
+    /// where it can, rather than asking the owner again
+    #[cfg(target_os = "linux")]
+    fn has_sensitive_hint(&self) -> bool {
+        if let Some(wayland) = self.wayland() {
+            return wayland.has_sensitive_hint();
+        }
+        x11_selection_offers(self.selection, PASSWORD_MANAGER_HINT).unwrap_or(false)
+    }
+    
+    #[cfg(target_os = "windows")]
+    fn has_sensitive_hint(&self) -> bool {
+        clipboard_win::register_format(EXCLUDE_FROM_MONITOR)
+            .is_some_and(|format| clipboard_win::is_format_avail(format.get()))
+    }
+}
+
+/// Set the clipboard through the connection this process keeps for serving it
+#[cfg(target_os = "linux")]
+fn with_background_owner(set: impl FnOnce(&mut Clipboard) -> Result<(), arboard::Error>) -> Result<()> {
+    let mut owner = BACKGROUND_OWNER.lock().unwrap();
+    let clipboard = match *owner {
+        Some(ref mut clipboard) => clipboard,
+        None => owner.insert(Clipboard::new().context("Failed to access clipboard")?),
+    };
+    Ok(set(clipboard)?)
+}
+
+/// Decode an image into the pixels arboard takes
+fn image_data(image: &ClipboardImage) -> Result<ImageData<'static>> {
+    let (width, height, rgba) = image.to_rgba()?;
+    Ok(ImageData {
+        width: width as usize,
+        height: height as usize,
+        bytes: rgba.into(),
+    })
+}
+
+/// Check whether the owner of an X11 selection offers the given format
+#[cfg(target_os = "linux")]
+fn x11_selection_offers(selection: Selection, target: &str) -> Result<bool> {
+    use std::time::Instant;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::Event;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, CreateWindowAux, WindowClass};
+    
+    let (conn, screen_num) = x11rb::connect(None)
+        .context("Failed to connect to X server")?;
+    
+    // An atom nobody has interned can't be on offer
+    let target = conn.intern_atom(true, target.as_bytes())?.reply()?.atom;
+    if target == x11rb::NONE {
+        return Ok(false);
+    }
+    
+    // An invisible window to receive the list of formats
+    let root = conn.setup().roots[screen_num].root;
+    let window = conn.generate_id()?;
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0, 0, 1, 1, 0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new(),
+    )?;
+    
+    let selection = conn.intern_atom(false, selection.atom_name().as_bytes())?.reply()?.atom;
+    let targets = conn.intern_atom(false, b"TARGETS")?.reply()?.atom;
+    let property = conn.intern_atom(false, b"9PASTE_TARGETS")?.reply()?.atom;
+    conn.convert_selection(window, selection, targets, property, x11rb::CURRENT_TIME)?;
+    conn.flush()?;
+    
+    let deadline = Instant::now() + TARGETS_TIMEOUT;
+    loop {
+        match conn.poll_for_event()? {
+            Some(Event::SelectionNotify(event)) if event.requestor == window => {
+                // No owner, or it refused to list its formats
+                if event.property == x11rb::NONE {
+                    return Ok(false);
+                }
+                break;
+            }
+            Some(_) => {}
+            None if Instant::now() < deadline => std::thread::sleep(std::time::Duration::from_millis(5)),
+            None => anyhow::bail!("Clipboard owner did not list its formats"),
+        }
+    }
+    
+    let reply = conn.get_property(true, window, property, AtomEnum::ATOM, 0, 1024)?.reply()?;
+    Ok(reply.value32().is_some_and(|mut atoms| atoms.any(|atom| atom == target)))
+}
+
+/// The X11 clipboard through the external `xclip` or `xsel` tools
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XclipBackend {
+    selection: Selection,
+}
+
+#[cfg(target_os = "linux")]
+impl XclipBackend {
+    /// Create a backend for a selection
+    pub fn new(selection: Selection) -> Self {
+        Self { selection }
+    }
+    
+    /// The flag xsel takes for the selection
+    fn xsel_flag(&self) -> &'static str {
+        match self.selection {
+            Selection::Clipboard => "--clipboard",
+            Selection::Primary => "--primary",
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ClipboardBackend for XclipBackend {
+    fn get_text(&self) -> Result<String> {
+        use std::process::Command;
+        
+        let output = Command::new("xclip")
+            .args(["-selection", self.selection.name(), "-o"])
+            .output()
+            .or_else(|_| Command::new("xsel").args([self.xsel_flag(), "--output"]).output())
+            .context("Neither xclip nor xsel is available")?;
+        
+        if !output.status.success() {
+            anyhow::bail!("Failed to get clipboard text");
+        }
+        String::from_utf8(output.stdout)
+            .context("Clipboard text is not valid UTF-8")
+    }
+    
+    /// Doesn't wait: the spawned tool forks and keeps serving the text
+    fn set_text(&self, text: &str) -> Result<()> {
+        use std::process::{Command, Stdio};
+        use std::io::Write;
+        
+        // Use xclip if available (most reliable for persistence), then xsel
+        let mut child = Command::new("xclip")
+            .args(["-selection", self.selection.name()])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .or_else(|_| {
+                Command::new("xsel")
+                    .args([self.xsel_flag(), "--input"])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+            })
+            .context("Neither xclip nor xsel is available")?;
+        
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        Ok(())
+    }
+    
+    /// Only supported with xclip, which can pick the format
+    fn get_html(&self) -> Result<Option<String>> {
+        use std::process::Command;
+        
+        let output = Command::new("xclip")
+            .args(["-selection", self.selection.name(), "-t", "text/html", "-o"])
+            .output()
+            .context("xclip is not available")?;
+        
+        // xclip fails if the owner doesn't offer HTML
+        if !output.status.success() || output.stdout.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(decode_html_bytes(&output.stdout)))
+    }
+    
+    /// Only supported with xclip. Keeps the format the image was copied in.
+    fn get_image(&self) -> Result<Option<ClipboardImage>> {
+        use std::process::Command;
+        
+        let targets = xclip_targets(self.selection)?;
+        let Some(format) = ImageFormat::ALL.into_iter().find(|format| targets.iter().any(|t| t == format.mime_type())) else {
+            return Ok(None);
+        };
+        
+        let output = Command::new("xclip")
+            .args(["-selection", self.selection.name(), "-t", format.mime_type(), "-o"])
+            .output()
+            .context("xclip is not available")?;
+        if !output.status.success() {
+            anyhow::bail!("Failed to get clipboard image");
+        }
+        Ok(Some(ClipboardImage::new(format, output.stdout)))
+    }
+    
+    /// Only supported with xclip, which offers the image in its own format only.
+    /// Doesn't wait: xclip forks and keeps serving the image.
+    fn set_image(&self, image: &ClipboardImage) -> Result<()> {
+        use std::process::{Command, Stdio};
+        use std::io::Write;
+        
+        let mut child = Command::new("xclip")
+            .args(["-selection", self.selection.name(), "-t", image.format.mime_type(), "-i"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("xclip is not available")?;
+        
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&image.bytes)?;
+        }
+        Ok(())
+    }
+    
+    fn selection(&self) -> Selection {
+        self.selection
+    }
+    
+    /// Only supported with xclip, which can list the offered formats
+    fn has_sensitive_hint(&self) -> bool {
+        xclip_targets(self.selection).is_ok_and(|targets| targets.iter().any(|target| target == PASSWORD_MANAGER_HINT))
+    }
+}
+
+/// List the formats the selection owner offers, through xclip
+#[cfg(target_os = "linux")]
+fn xclip_targets(selection: Selection) -> Result<Vec<String>> {
+    use std::process::Command;
+    
+    let output = Command::new("xclip")
+        .args(["-selection", selection.name(), "-t", "TARGETS", "-o"])
+        .output()
+        .context("xclip is not available")?;
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect())
+}
+
+/// Decode clipboard HTML, which some browsers still offer as UTF-16
+#[cfg(target_os = "linux")]
+pub(crate) fn decode_html_bytes(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => {
+            let units: Vec<u16> = rest.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// An in-memory clipboard, for tests and headless use
+///
+/// Clones share the same contents, so a test can keep a handle while the
+/// `ClipboardManager` owns another.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryClipboard {
+    text: Arc<Mutex<String>>,
+    html: Arc<Mutex<Option<String>>>,
+    image: Arc<Mutex<Option<ClipboardImage>>>,
+    sensitive_hint: Arc<AtomicBool>,
+}
+
+impl MemoryClipboard {
+    /// Create an in-memory clipboard holding `text`
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: Arc::new(Mutex::new(text.into())),
+            html: Arc::new(Mutex::new(None)),
+            image: Arc::new(Mutex::new(None)),
+            sensitive_hint: Arc::new(AtomicBool::new(false)),
+        }
+    }
+    
+    /// Mark the content as secret, like a password manager would
+    pub fn set_sensitive_hint(&self, hint: bool) {
+        self.sensitive_hint.store(hint, Ordering::SeqCst);
+    }
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    /// Fails while the clipboard holds an image, like system clipboards do
+    fn get_text(&self) -> Result<String> {
+        if self.image.lock().unwrap().is_some() {
+            anyhow::bail!("Clipboard holds an image");
+        }
+        Ok(self.text.lock().unwrap().clone())
+    }
+    
+    /// Replaces any HTML or image, like copying plain text does
+    fn set_text(&self, text: &str) -> Result<()> {
+        *self.text.lock().unwrap() = text.to_string();
+        *self.html.lock().unwrap() = None;
+        *self.image.lock().unwrap() = None;
+        Ok(())
+    }
+    
+    fn get_html(&self) -> Result<Option<String>> {
+        Ok(self.html.lock().unwrap().clone())
+    }
+    
+    fn set_html(&self, html: &str, alt_text: &str) -> Result<()> {
+        *self.text.lock().unwrap() = alt_text.to_string();
+        *self.html.lock().unwrap() = Some(html.to_string());
+        *self.image.lock().unwrap() = None;
+        Ok(())
+    }
+    
+    fn get_image(&self) -> Result<Option<ClipboardImage>> {
+        Ok(self.image.lock().unwrap().clone())
+    }
+    
+    fn set_image(&self, image: &ClipboardImage) -> Result<()> {
+        self.text.lock().unwrap().clear();
+        *self.html.lock().unwrap() = None;
+        *self.image.lock().unwrap() = Some(image.clone());
+        Ok(())
+    }
+    
+    fn has_sensitive_hint(&self) -> bool {
+        self.sensitive_hint.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,186 @@
+//! Content type detection
+//!
+//! Guesses what kind of text is on the clipboard, so recipe steps can be
+//! limited to certain content (e.g. only normalize whitespace in prose).
+
+use serde::{Deserialize, Serialize};
+
+use crate::transformers::regex;
+
+/// Share of non-empty lines that must look like code for the text to be code
+const CODE_LINE_RATIO: f64 = 0.5;
+
+/// Kind of clipboard content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ContentType {
+    Url,
+    Json,
+    Code,
+    Email,
+    Csv,
+    Tsv,
+    Path,
+    Number,
+    Prose,
+}
+
+impl ContentType {
+    /// All content types, in the order they are offered in the dashboard
+    pub const ALL: [Self; 9] = [
+        Self::Prose, Self::Code, Self::Json, Self::Url, Self::Email,
+        Self::Csv, Self::Tsv, Self::Path, Self::Number,
+    ];
+    
+    /// Get a human-readable name for the content type
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Url => "URL",
+            Self::Json => "JSON",
+            Self::Code => "Code",
+            Self::Email => "Email Address",
+            Self::Csv => "CSV",
+            Self::Tsv => "TSV",
+            Self::Path => "File Path",
+            Self::Number => "Number",
+            Self::Prose => "Prose",
+        }
+    }
+}
+
+/// Guess the kind of content in text
+pub fn classify(text: &str) -> ContentType {
+    let trimmed = text.trim();
+    let single_line = !trimmed.contains('\n');
+    
+    if single_line {
+        if regex!(r"^[-+]?[$€£¥]?(?:\d{1,3}(?:[,_ ]\d{3})+|\d+)(?:\.\d+)?(?:[eE][-+]?\d+)?%?$").is_match(trimmed) {
+            return ContentType::Number;
+        }
+        if regex!(r"^(?:(?:https?|ftp|file)://|www\.)\S+$").is_match(trimmed) {
+            return ContentType::Url;
+        }
+        if regex!(r"^[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}$").is_match(trimmed) {
+            return ContentType::Email;
+        }
+        // Not `// comment` or `/* comment */`, which are code
+        if regex!(r"^(?:~|\.{1,2})?/(?:[^/*\t][^\t]*|/[^\s\w/*][^\t]*)?$|^[A-Za-z]:\\|^\\\\[^\\\s]+\\").is_match(trimmed) {
+            return ContentType::Path;
+        }
+    }
+    
+    if trimmed.starts_with(['{', '[']) && serde_json::from_str::<serde_json::Value>(trimmed).is_ok() {
+        return ContentType::Json;
+    }
+    if looks_like_code(trimmed) {
+        return ContentType::Code;
+    }
+    if is_delimited(trimmed, '\t') {
+        return ContentType::Tsv;
+    }
+    // Lines of prose often have a single comma too, but end like sentences
+    let sentences = trimmed.lines().any(|line| line.trim_end().ends_with(['.', '!', '?']));
+    if !sentences && is_delimited(trimmed, ',') {
+        return ContentType::Csv;
+    }
+    ContentType::Prose
+}
+
+/// Whether enough lines look like source code
+fn looks_like_code(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        return false;
+    }
+    
+    let code_lines = lines.iter().filter(|line| looks_like_code_line(line)).count();
+    code_lines as f64 / lines.len() as f64 >= CODE_LINE_RATIO
+}
+
+/// Whether a trimmed line looks like a line of source code
+fn looks_like_code_line(line: &str) -> bool {
+    if line.ends_with([';', '{', '}']) || line.starts_with("//") || line.starts_with("/*") {
+        return true;
+    }
+    
+    // Lowercase keywords, so sentences starting with "If" or "For" don't count
+    let keyword = regex!(
+        r"^(?:fn|pub|let|const|var|function|def|class|import|from|return|if|else|elif|for|while|match|struct|enum|impl|use|package|public|private|protected|static|async|export|interface|#include|#define)\b"
+    );
+    let has_syntax = line.contains(['(', ')', '{', '}', '[', ']', '=', ':', ';', '<', '>']);
+    if keyword.is_match(line) && has_syntax {
+        return true;
+    }
+    
+    ["=>", "->", "::", "==", "!=", "&&", "||", "+=", "();"].iter().any(|op| line.contains(op))
+}
+
+/// Whether there are at least two lines, all with the same number (at least two) of fields
+fn is_delimited(text: &str, delimiter: char) -> bool {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let Some(first) = lines.next() else {
+        return false;
+    };
+    
+    let fields = field_count(first, delimiter);
+    let mut rows = 1;
+    for line in lines {
+        if field_count(line, delimiter) != fields {
+            return false;
+        }
+        rows += 1;
+    }
+    rows >= 2 && fields >= 2
+}
+
+/// Number of fields in a delimited line, ignoring delimiters inside double quotes
+fn field_count(line: &str, delimiter: char) -> usize {
+    let mut in_quotes = false;
+    let mut fields = 1;
+    for c in line.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == delimiter && !in_quotes {
+            fields += 1;
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_classify() {
+        let cases = [
+            ("https://example.com/path?q=1", ContentType::Url),
+            ("www.example.com", ContentType::Url),
+            ("  {\"name\": \"9paste\", \"tags\": [1, 2]}\n", ContentType::Json),
+            ("[1, 2, 3]", ContentType::Json),
+            ("fn main() {\n    println!(\"hi\");\n}", ContentType::Code),
+            ("def greet(name):\n    return f\"Hello {name}\"", ContentType::Code),
+            ("const x = 1;", ContentType::Code),
+            ("// TODO: handle errors", ContentType::Code),
+            ("//nolint", ContentType::Code),
+            ("/* unused */", ContentType::Code),
+            ("bob.smith@example.com", ContentType::Email),
+            ("name,age,city\nBob,42,\"Paris, France\"\nAlice,37,Berlin", ContentType::Csv),
+            ("name\tage\nBob\t42", ContentType::Tsv),
+            ("/usr/local/bin/9paste", ContentType::Path),
+            ("~/Documents/My Notes.txt", ContentType::Path),
+            ("C:\\Users\\Bob\\file.txt", ContentType::Path),
+            ("1,234,567.89", ContentType::Number),
+            ("-42", ContentType::Number),
+            ("$19.99", ContentType::Number),
+            ("If you want to, we can meet at 5: the usual place.", ContentType::Prose),
+            ("Hello, World!\n\nThis is a test, with commas.", ContentType::Prose),
+            ("", ContentType::Prose),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(classify(text), expected, "{:?}", text);
+        }
+    }
+}
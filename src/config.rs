@@ -7,6 +7,9 @@ use std::fs;
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
 
+use crate::apps::{self, AppRule};
+use crate::backend::Selection;
+
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -37,6 +40,23 @@ pub struct Config {
     /// Global hotkey to open dashboard
     pub dashboard_hotkey: Option<String>,
     
+    /// Global hotkey to undo the last transformation. Off by default, since
+    /// the obvious choices are taken by Redo in most applications.
+    #[serde(default)]
+    pub undo_hotkey: Option<String>,
+    
+    /// Global hotkey to apply the active recipe and paste the result
+    #[serde(default)]
+    pub paste_hotkey: Option<String>,
+    
+    /// How the paste hotkey puts the result into the focused window
+    #[serde(default)]
+    pub paste_mode: PasteMode,
+    
+    /// Put the original clipboard text back after the paste hotkey pastes
+    #[serde(default = "default_true")]
+    pub restore_clipboard: bool,
+    
     /// Theme: "dark", "light", or "system"
     pub theme: String,
     
@@ -46,10 +66,110 @@ pub struct Config {
     /// Maximum history size
     pub max_history_size: usize,
     
+    /// Where the key that encrypts history comes from
+    #[serde(default)]
+    pub history_encryption: HistoryEncryption,
+    
+    /// Detect secrets (passwords, API keys, card numbers) and keep them out of history
+    #[serde(default = "default_true")]
+    pub detect_sensitive: bool,
+    
+    /// Transform clipboard content even if it looks secret
+    #[serde(default)]
+    pub transform_sensitive: bool,
+    
+    /// Rules picking the recipe by the application content is copied from,
+    /// checked in order before the active recipe
+    #[serde(default = "apps::default_rules")]
+    pub app_rules: Vec<AppRule>,
+    
+    /// Which selections to monitor and transform
+    #[serde(default)]
+    pub selections: Selections,
+    
+    /// Recipe (name or ID) transforming the PRIMARY selection, if it is monitored
+    #[serde(default)]
+    pub primary_recipe: Option<String>,
+    
     /// ID of the currently active recipe (UUID as string)
     pub active_recipe_id: Option<String>,
 }
 
+/// Where the key that encrypts clipboard history comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryEncryption {
+    /// A random key stored in the OS keyring
+    #[default]
+    Keyring,
+    /// A passphrase entered to unlock history
+    Passphrase,
+}
+
+/// How the paste hotkey puts the result into the focused window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PasteMode {
+    /// Put the result on the clipboard and press Ctrl+V
+    #[default]
+    Paste,
+    /// Type the result key by key, for fields that refuse pastes
+    Type,
+}
+
+impl PasteMode {
+    /// All modes
+    pub const ALL: [PasteMode; 2] = [Self::Paste, Self::Type];
+    
+    /// Get a human-readable name for the mode
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Paste => "Paste (Ctrl+V)",
+            Self::Type => "Type it out",
+        }
+    }
+}
+
+/// Which selections the background service monitors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selections {
+    /// The clipboard, with the active recipe
+    #[default]
+    Clipboard,
+    /// The PRIMARY selection, with the primary recipe
+    Primary,
+    /// Both, each with its own recipe
+    Both,
+}
+
+impl Selections {
+    /// All choices
+    pub const ALL: [Selections; 3] = [Self::Clipboard, Self::Primary, Self::Both];
+    
+    /// Get a human-readable name for the choice
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Clipboard => "Clipboard",
+            Self::Primary => "Primary selection",
+            Self::Both => "Clipboard and primary",
+        }
+    }
+    
+    /// Whether a selection is monitored
+    pub fn includes(&self, selection: Selection) -> bool {
+        match self {
+            Self::Clipboard => selection == Selection::Clipboard,
+            Self::Primary => selection == Selection::Primary,
+            Self::Both => true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -62,9 +182,19 @@ impl Default for Config {
             toggle_hotkey: Some("Ctrl+Shift+T".into()),
             quick_menu_hotkey: Some("Ctrl+Shift+V".into()),
             dashboard_hotkey: Some("Ctrl+Shift+D".into()),
+            undo_hotkey: None,
+            paste_hotkey: None,
+            paste_mode: PasteMode::default(),
+            restore_clipboard: true,
             theme: "system".into(),
             keep_history: true,
             max_history_size: 100,
+            history_encryption: HistoryEncryption::default(),
+            detect_sensitive: true,
+            transform_sensitive: false,
+            app_rules: apps::default_rules(),
+            selections: Selections::default(),
+            primary_recipe: None,
             active_recipe_id: None,
         }
     }
@@ -133,105 +263,3 @@ impl Config {
         Ok(config_dir)
     }
 }
-
-/// Clipboard history entry
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HistoryEntry {
-    /// Original text before transformation
-    pub original: String,
-    /// Transformed text (if any)
-    pub transformed: Option<String>,
-    /// Recipe ID used for transformation
-    pub recipe_id: Option<String>,
-    /// Recipe name used for transformation
-    pub recipe_name: Option<String>,
-    /// Timestamp
-    pub timestamp: chrono::DateTime<chrono::Utc>,
-}
-
-/// Clipboard history manager
-pub struct HistoryManager {
-    entries: Vec<HistoryEntry>,
-    max_size: usize,
-    history_path: PathBuf,
-}
-
-impl HistoryManager {
-    /// Create a new history manager
-    pub fn new(max_size: usize) -> Result<Self> {
-        let history_path = Config::data_dir()?.join("history.json");
-        
-        let entries = if history_path.exists() {
-            let data = fs::read_to_string(&history_path)
-                .context("Failed to read history file")?;
-            serde_json::from_str(&data).unwrap_or_default()
-        } else {
-            Vec::new()
-        };
-        
-        Ok(Self {
-            entries,
-            max_size,
-            history_path,
-        })
-    }
-    
-    /// Add an entry to history
-    pub fn add(&mut self, entry: HistoryEntry) -> Result<()> {
-        // Reload from disk first to pick up any changes made by other processes (e.g., dashboard)
-        // If reload fails, just use current in-memory state
-        let _ = self.reload();
-        
-        self.entries.insert(0, entry);
-        
-        // Trim to max size
-        if self.entries.len() > self.max_size {
-            self.entries.truncate(self.max_size);
-        }
-        
-        self.save()
-    }
-    
-    /// Get all history entries (reloads from disk first)
-    pub fn get_all(&mut self) -> &[HistoryEntry] {
-        // Reload from disk to get latest data
-        let _ = self.reload();
-        &self.entries
-    }
-    
-    /// Reload history from disk
-    pub fn reload(&mut self) -> Result<()> {
-        if self.history_path.exists() {
-            let data = fs::read_to_string(&self.history_path)
-                .context("Failed to read history file")?;
-            self.entries = serde_json::from_str(&data)
-                .context("Failed to parse history file")?;
-        }
-        Ok(())
-    }
-    
-    /// Remove a specific entry by index
-    pub fn remove(&mut self, index: usize) -> Result<()> {
-        if index < self.entries.len() {
-            self.entries.remove(index);
-            self.save()
-        } else {
-            Ok(())
-        }
-    }
-    
-    /// Clear all history
-    pub fn clear(&mut self) -> Result<()> {
-        self.entries.clear();
-        self.save()
-    }
-    
-    /// Save history to disk
-    fn save(&self) -> Result<()> {
-        let data = serde_json::to_string_pretty(&self.entries)
-            .context("Failed to serialize history")?;
-        fs::write(&self.history_path, data)
-            .context("Failed to write history file")?;
-        Ok(())
-    }
-}
@@ -5,10 +5,17 @@
 use eframe::egui;
 use std::sync::{Arc, Mutex};
 
-use crate::recipe::{Recipe, RecipeManager, Transformation};
-use crate::config::{Config, HistoryManager};
+use crate::apps::AppRule;
+use crate::backend::{ClipboardContent, Selection};
+use crate::formats::DataFormat;
+use crate::images::ImageFormat;
+use crate::recipe::{Condition, Flavor, Recipe, RecipeManager, Transformation};
+use crate::tables::TableFormat;
+use crate::classify::{classify, ContentType};
+use crate::config::{Config, HistoryEncryption, PasteMode, Selections};
+use crate::history::{HistoryEntry, HistoryManager, HistoryKey, HistoryQuery};
 use crate::clipboard::ClipboardManager;
-use crate::ipc::{IpcClient, IpcCommand};
+use crate::ipc::{IpcClient, IpcCommand, Secret};
 use egui_phosphor::regular::*;
 
 /// Dashboard application state
@@ -25,6 +32,8 @@ pub struct Dashboard {
     test_input: String,
     /// Test output (preview)
     test_output: String,
+    /// Why the preview could not be produced
+    preview_error: Option<String>,
     /// Whether transformation is enabled
     transform_enabled: bool,
     /// Status message
@@ -35,6 +44,35 @@ pub struct Dashboard {
     editing_recipe: bool,
     /// History manager
     history_manager: Option<HistoryManager>,
+    /// Key the history was unlocked with
+    history_key: Option<HistoryKey>,
+    /// Passphrase being entered to unlock history
+    history_passphrase: String,
+    /// Passphrase being entered to protect history with
+    new_history_passphrase: String,
+    /// History tab search text
+    history_search: String,
+    /// History tab recipe filter (recipe ID)
+    history_recipe: Option<String>,
+    /// History tab time range filter
+    history_range: HistoryRange,
+    /// History tab page
+    history_page: usize,
+    /// History tab page as last queried, or None to query it again
+    history_view: Option<HistoryView>,
+}
+
+/// Number of history entries shown per page
+const HISTORY_PAGE_SIZE: usize = 50;
+
+/// A page of history, kept between frames since egui redraws on every mouse move
+#[derive(Default)]
+struct HistoryView {
+    entries: Vec<HistoryEntry>,
+    /// Number of entries matching the filters
+    total: usize,
+    /// IDs and names of the recipes in history
+    recipes: Vec<(String, String)>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,6 +83,38 @@ pub enum DashboardTab {
     About,
 }
 
+/// Time range filter for the history tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryRange {
+    AllTime,
+    Day,
+    Week,
+    Month,
+}
+
+impl HistoryRange {
+    const ALL: [Self; 4] = [Self::AllTime, Self::Day, Self::Week, Self::Month];
+    
+    fn label(self) -> &'static str {
+        match self {
+            Self::AllTime => "All time",
+            Self::Day => "Last 24 hours",
+            Self::Week => "Last 7 days",
+            Self::Month => "Last 30 days",
+        }
+    }
+    
+    fn since(self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let days = match self {
+            Self::AllTime => return None,
+            Self::Day => 1,
+            Self::Week => 7,
+            Self::Month => 30,
+        };
+        Some(chrono::Utc::now() - chrono::Duration::days(days))
+    }
+}
+
 impl Dashboard {
     /// Create a new dashboard
     pub fn new(
@@ -53,7 +123,10 @@ impl Dashboard {
     ) -> Self {
         let transform_enabled = config.lock().unwrap().auto_transform;
         let max_history_size = config.lock().unwrap().max_history_size;
-        let history_manager = HistoryManager::new(max_history_size).ok();
+        // A passphrase-protected history stays locked until it is entered
+        let history_key = HistoryKey::from_config(&config.lock().unwrap()).ok().flatten();
+        let history_manager = history_key.as_ref()
+            .and_then(|key| HistoryManager::new(max_history_size, key).ok());
         
         Self {
             recipe_manager,
@@ -62,11 +135,20 @@ impl Dashboard {
             current_tab: DashboardTab::Recipes,
             test_input: "Hello, World!\n\nThis is a   test with  extra   spaces.\n\n\"Smart quotes\" and 'apostrophes'.\n\nLine one\nLine one\nLine two".to_string(),
             test_output: String::new(),
+            preview_error: None,
             transform_enabled,
             status_message: None,
             new_recipe_name: String::new(),
             editing_recipe: false,
             history_manager,
+            history_key,
+            history_passphrase: String::new(),
+            new_history_passphrase: String::new(),
+            history_search: String::new(),
+            history_recipe: None,
+            history_range: HistoryRange::AllTime,
+            history_page: 0,
+            history_view: None,
         }
     }
     
@@ -105,7 +187,16 @@ impl Dashboard {
         if let Some(recipe_id) = self.selected_recipe {
             let recipe_manager = self.recipe_manager.lock().unwrap();
             if let Some(recipe) = recipe_manager.get_recipe(recipe_id) {
-                self.test_output = recipe.apply(&self.test_input);
+                match recipe.try_apply_content(&ClipboardContent::text(self.test_input.as_str())) {
+                    Ok(output) => {
+                        self.test_output = output.html.unwrap_or(output.text);
+                        self.preview_error = None;
+                    }
+                    Err(e) => {
+                        self.test_output.clear();
+                        self.preview_error = Some(e.to_string());
+                    }
+                }
             }
         }
     }
@@ -145,6 +236,8 @@ impl eframe::App for Dashboard {
                 }
                 if ui.selectable_label(self.current_tab == DashboardTab::History, format!("{} History", CLIPBOARD)).clicked() {
                     self.current_tab = DashboardTab::History;
+                    // Pick up entries the background service added meanwhile
+                    self.history_view = None;
                 }
                 if ui.selectable_label(self.current_tab == DashboardTab::About, format!("{} About", INFO)).clicked() {
                     self.current_tab = DashboardTab::About;
@@ -160,7 +253,7 @@ impl eframe::App for Dashboard {
                             config.save().ok();
                         } // config guard dropped here
 
-                        IpcClient::send(IpcCommand::ToggleTransformation);
+                        IpcClient::send(IpcCommand::ToggleTransformation).ok();
                         self.show_status(if self.transform_enabled { 
                             "Transformation enabled" 
                         } else { 
@@ -292,14 +385,14 @@ impl Dashboard {
                         if ui.button(format!("{} Deactivate", STOP)).clicked() {
                             self.recipe_manager.lock().unwrap().deactivate_all().ok();
                             // Notify background service to reload recipe
-                            IpcClient::send(IpcCommand::ReloadRecipe);
+                            IpcClient::send(IpcCommand::ReloadRecipe).ok();
                             self.show_status("Recipe deactivated");
                         }
                     } else {
                         if ui.button(format!("{} Activate", PLAY)).clicked() {
                             self.recipe_manager.lock().unwrap().set_active(recipe_id).ok();
                             // Notify background service to reload recipe
-                            IpcClient::send(IpcCommand::ReloadRecipe);
+                            IpcClient::send(IpcCommand::ReloadRecipe).ok();
                             self.show_status("Recipe activated - transformations will be applied automatically");
                         }
                     }
@@ -339,6 +432,32 @@ impl Dashboard {
                 }
             });
             
+            let mut hotkey = recipe.hotkey.clone().unwrap_or_default();
+            ui.horizontal(|ui| {
+                ui.label("Hotkey:");
+                let response = ui.add(egui::TextEdit::singleline(&mut hotkey).hint_text("e.g. Ctrl+Alt+P"));
+                if response.changed() {
+                    recipe.hotkey = if hotkey.is_empty() { None } else { Some(hotkey) };
+                    self.recipe_manager.lock().unwrap().update_recipe(recipe.clone()).ok();
+                }
+                if response.lost_focus() {
+                    // Notify background service to re-register recipe hotkeys
+                    IpcClient::send(IpcCommand::ReloadRecipe).ok();
+                }
+            });
+            
+            ui.horizontal(|ui| {
+                let mut changed = false;
+                ui.label("Reads:");
+                changed |= flavor_combo(ui, "recipe_input_flavor", &mut recipe.input);
+                ui.label("Writes:");
+                changed |= flavor_combo(ui, "recipe_output_flavor", &mut recipe.output);
+                if changed {
+                    self.recipe_manager.lock().unwrap().update_recipe(recipe.clone()).ok();
+                    IpcClient::send(IpcCommand::ReloadRecipe).ok();
+                }
+            });
+            
             ui.add_space(10.0);
             ui.separator();
             
@@ -353,17 +472,55 @@ impl Dashboard {
                     .max_height(200.0)
                     .show(&mut columns[0], |ui| {
                         let mut to_remove = None;
+                        let mut condition_change = None;
+                        let mut params_change = None;
+                        let problems = recipe.validate();
                         
                         for (i, transformation) in recipe.transformations.iter().enumerate() {
+                            let problem = problems.iter().find(|p| p.index == i);
+                            // Conditional steps are shown as their steps, with the condition beside them
+                            let (name, current) = match transformation {
+                                Transformation::When { condition, steps } => {
+                                    let names: Vec<_> = steps.iter().map(Transformation::display_name).collect();
+                                    (names.join(", "), Some(condition))
+                                }
+                                _ => (transformation.display_name().to_string(), None),
+                            };
                             ui.horizontal(|ui| {
                                 ui.label(format!("{}.", i + 1));
-                                ui.label(transformation.display_name());
+                                ui.label(name);
+                                if let Some(problem) = problem {
+                                    ui.colored_label(ui.visuals().error_fg_color, WARNING)
+                                        .on_hover_text(&problem.message);
+                                }
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                     if ui.button(TRASH).clicked() {
                                         to_remove = Some(i);
                                     }
+                                    
+                                    egui::ComboBox::from_id_salt(("step_condition", i))
+                                        .selected_text(current.map_or("Always".to_string(), Condition::label))
+                                        .show_ui(ui, |ui| {
+                                            if ui.selectable_label(current.is_none(), "Always").clicked() {
+                                                condition_change = Some((i, None));
+                                            }
+                                            for content_type in ContentType::ALL {
+                                                for condition in [Condition::Is { content_type }, Condition::IsNot { content_type }] {
+                                                    if ui.selectable_label(current == Some(&condition), condition.label()).clicked() {
+                                                        condition_change = Some((i, Some(condition)));
+                                                    }
+                                                }
+                                            }
+                                        });
                                 });
                             });
+                            let mut edited = transformation.clone();
+                            if step_params(ui, egui::Id::new(("step_params", i)), &mut edited) {
+                                params_change = Some((i, edited));
+                            }
+                            if let Some(problem) = problem {
+                                ui.colored_label(ui.visuals().error_fg_color, &problem.message);
+                            }
                         }
                         
                         if let Some(i) = to_remove {
@@ -372,6 +529,19 @@ impl Dashboard {
                             self.update_preview();
                             self.show_status("Transformation removed");
                         }
+                        
+                        if let Some((i, condition)) = condition_change {
+                            let step = recipe.transformations[i].clone();
+                            recipe.transformations.splice(i..=i, with_condition(step, condition));
+                            self.recipe_manager.lock().unwrap().update_recipe(recipe.clone()).ok();
+                            self.update_preview();
+                        }
+                        
+                        if let Some((i, step)) = params_change {
+                            recipe.transformations[i] = step;
+                            self.recipe_manager.lock().unwrap().update_recipe(recipe.clone()).ok();
+                            self.update_preview();
+                        }
                     });
                 
                 columns[0].add_space(10.0);
@@ -387,8 +557,9 @@ impl Dashboard {
                         
                         // Group by category
                         for category in &[
-                            "Whitespace", "Case Conversion", "Line Operations",
-                            "Character Cleanup", "Content Removal", "HTML", "URL"
+                            "Whitespace", "Case Conversion", "Line Operations", "Tables", "Columns",
+                            "Character Cleanup", "Content Removal", "Extraction", "JSON", "Data Formats",
+                            "HTML", "URL", "Images"
                         ] {
                             ui.collapsing(*category, |ui| {
                                 ui.vertical_centered_justified(|ui| {
@@ -409,7 +580,7 @@ impl Dashboard {
                 columns[1].heading("Preview");
                 columns[1].add_space(5.0);
                 
-                columns[1].label("Input:");
+                columns[1].label(format!("Input (detected: {}):", classify(&self.test_input).display_name()));
                 egui::ScrollArea::vertical()
                     .id_salt("input_scroll")
                     .max_height(200.0)
@@ -424,6 +595,10 @@ impl Dashboard {
                 
                 columns[1].add_space(10.0);
                 columns[1].label("Output:");
+                if let Some(ref error) = self.preview_error {
+                    let color = columns[1].visuals().error_fg_color;
+                    columns[1].colored_label(color, format!("{} {}", WARNING, error));
+                }
                 egui::ScrollArea::vertical()
                     .id_salt("output_scroll")
                     .max_height(200.0)
@@ -466,6 +641,18 @@ impl Dashboard {
                 Transformation::ToUnixLineEndings,
                 Transformation::ToWindowsLineEndings,
             ],
+            "Tables" => TableFormat::ALL.into_iter()
+                .map(|to| Transformation::ConvertTable { to })
+                .chain([Transformation::TableToSql { table: "my_table".to_string() }])
+                .collect(),
+            "Columns" => vec![
+                Transformation::SelectColumns { delimiter: '\t', columns: vec![1] },
+                Transformation::DropColumns { delimiter: '\t', columns: vec![1] },
+                Transformation::SortByColumn { delimiter: '\t', column: 1, numeric: false },
+                Transformation::DedupeByColumn { delimiter: '\t', column: 1 },
+                Transformation::TransposeColumns { delimiter: '\t' },
+                Transformation::AlignColumns { delimiter: '\t' },
+            ],
             "Character Cleanup" => vec![
                 Transformation::FixSmartQuotes,
                 Transformation::RemoveNonAscii,
@@ -479,18 +666,49 @@ impl Dashboard {
                 Transformation::RemovePhoneNumbers,
                 Transformation::RemoveMarkdown,
             ],
+            "Extraction" => vec![
+                Transformation::ExtractNumbers,
+                Transformation::JsonQuery { expr: ".".to_string(), raw: true },
+            ],
+            "JSON" => vec![
+                Transformation::JsonPrettyPrint { indent: 2 },
+                Transformation::JsonMinify,
+                Transformation::JsonSortKeys,
+                Transformation::JsonValidate,
+            ],
+            "Data Formats" => DataFormat::ALL.into_iter()
+                .map(|to| Transformation::ConvertFormat { from: None, to })
+                .collect(),
             "HTML" => vec![
                 Transformation::EncodeHtmlEntities,
                 Transformation::DecodeHtmlEntities,
+                Transformation::sanitize_html(),
+                Transformation::HtmlToMarkdown,
+                Transformation::HtmlToPlainText { keep_links: true },
             ],
             "URL" => vec![
                 Transformation::Slugify,
             ],
+            "Images" => vec![
+                Transformation::ResizeImage { max_dimension: 1920 },
+                Transformation::ConvertImage { format: ImageFormat::Png },
+                Transformation::ConvertImage { format: ImageFormat::Jpeg },
+                Transformation::StripImageMetadata,
+                Transformation::GrayscaleImage,
+            ],
             _ => vec![],
         }
     }
     
     fn show_settings_tab(&mut self, ctx: &egui::Context) {
+        let mut action_encryption: Option<HistoryEncryption> = None;
+        let mut action_reload_rules = false;
+        let history_unlocked = self.history_manager.is_some();
+        let recipe_names: Vec<String> = self.recipe_manager.lock().unwrap()
+            .recipes.iter()
+            .map(|r| r.name.clone())
+            .collect();
+        
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading(format!("{} Settings", GEAR_SIX));
             ui.add_space(20.0);
@@ -534,12 +752,84 @@ impl Dashboard {
                     
                     ui.label("Max history size:");
                     let mut size = config.max_history_size as i32;
-                    if ui.add(egui::Slider::new(&mut size, 10..=500)).changed() {
+                    if ui.add(egui::Slider::new(&mut size, 10..=100_000).logarithmic(true)).changed() {
                         config.max_history_size = size as usize;
                         config.save().ok();
                     }
                     ui.end_row();
                     
+                    ui.label("Keep secrets out of history:");
+                    if ui.checkbox(&mut config.detect_sensitive, "")
+                        .on_hover_text("Passwords, API keys, private keys and card numbers")
+                        .changed()
+                    {
+                        config.save().ok();
+                    }
+                    ui.end_row();
+                    
+                    ui.label("Transform secrets:");
+                    if ui.add_enabled(config.detect_sensitive, egui::Checkbox::without_text(&mut config.transform_sensitive)).changed() {
+                        config.save().ok();
+                    }
+                    ui.end_row();
+                    
+                    ui.label("Monitor:");
+                    egui::ComboBox::from_id_salt("selections_combo")
+                        .selected_text(config.selections.display_name())
+                        .show_ui(ui, |ui| {
+                            for selections in Selections::ALL {
+                                if ui.selectable_value(&mut config.selections, selections, selections.display_name()).changed() {
+                                    config.save().ok();
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("The primary selection is pasted with a middle click (Linux only). Takes effect when the background service restarts.");
+                    ui.end_row();
+                    
+                    ui.label("Primary selection recipe:");
+                    ui.add_enabled_ui(config.selections.includes(Selection::Primary), |ui| {
+                        egui::ComboBox::from_id_salt("primary_recipe_combo")
+                            .selected_text(config.primary_recipe.as_deref().unwrap_or("Don't transform"))
+                            .show_ui(ui, |ui| {
+                                if ui.selectable_value(&mut config.primary_recipe, None, "Don't transform").changed() {
+                                    action_reload_rules = true;
+                                }
+                                for name in &recipe_names {
+                                    if ui.selectable_value(&mut config.primary_recipe, Some(name.clone()), name).changed() {
+                                        action_reload_rules = true;
+                                    }
+                                }
+                            });
+                    });
+                    ui.end_row();
+                    
+                    // Changing the key re-encrypts the history, so it must be unlocked
+                    ui.label("History encryption:");
+                    ui.horizontal(|ui| {
+                        match config.history_encryption {
+                            HistoryEncryption::Keyring => {
+                                ui.label("OS keyring");
+                                let passphrase_edit = egui::TextEdit::singleline(&mut self.new_history_passphrase)
+                                    .password(true)
+                                    .hint_text("New passphrase")
+                                    .desired_width(150.0);
+                                ui.add(passphrase_edit);
+                                let can_change = history_unlocked && !self.new_history_passphrase.is_empty();
+                                if ui.add_enabled(can_change, egui::Button::new(format!("{} Use Passphrase", LOCK))).clicked() {
+                                    action_encryption = Some(HistoryEncryption::Passphrase);
+                                }
+                            }
+                            HistoryEncryption::Passphrase => {
+                                ui.label("Passphrase");
+                                if ui.add_enabled(history_unlocked, egui::Button::new(format!("{} Use OS Keyring", KEY))).clicked() {
+                                    action_encryption = Some(HistoryEncryption::Keyring);
+                                }
+                            }
+                        }
+                    });
+                    ui.end_row();
+                    
                     ui.label("Theme:");
                     egui::ComboBox::from_id_salt("theme_combo")
                         .selected_text(&config.theme)
@@ -587,23 +877,184 @@ impl Dashboard {
                         config.save().ok();
                     }
                     ui.end_row();
+                    
+                    ui.label("Undo transformation:");
+                    let mut undo = config.undo_hotkey.clone().unwrap_or_default();
+                    if ui.add(egui::TextEdit::singleline(&mut undo).hint_text("Ctrl+Alt+Z")).changed() {
+                        config.undo_hotkey = if undo.is_empty() { None } else { Some(undo) };
+                        config.save().ok();
+                    }
+                    ui.end_row();
+                    
+                    ui.label("Transform and paste:");
+                    let mut paste = config.paste_hotkey.clone().unwrap_or_default();
+                    if ui.text_edit_singleline(&mut paste)
+                        .on_hover_text("Applies the active recipe to the clipboard and pastes the result into the focused window (X11)")
+                        .changed()
+                    {
+                        config.paste_hotkey = if paste.is_empty() { None } else { Some(paste) };
+                        config.save().ok();
+                    }
+                    ui.end_row();
+                    
+                    ui.label("Paste by:");
+                    egui::ComboBox::from_id_salt("paste_mode_combo")
+                        .selected_text(config.paste_mode.display_name())
+                        .show_ui(ui, |ui| {
+                            for mode in PasteMode::ALL {
+                                if ui.selectable_value(&mut config.paste_mode, mode, mode.display_name()).changed() {
+                                    config.save().ok();
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("Typing works in fields that refuse pastes, like remote desktops");
+                    ui.end_row();
+                    
+                    ui.label("Restore clipboard after pasting:");
+                    let paste_mode = config.paste_mode == PasteMode::Paste;
+                    if ui.add_enabled(paste_mode, egui::Checkbox::without_text(&mut config.restore_clipboard)).changed() {
+                        config.save().ok();
+                    }
+                    ui.end_row();
+                });
+            
+            ui.add_space(20.0);
+            ui.separator();
+            ui.add_space(10.0);
+            
+            ui.heading(format!("{} App Rules", APP_WINDOW));
+            ui.label("Pick the recipe by the application you copy from. The first matching rule wins; other applications use the active recipe.");
+            ui.add_space(10.0);
+            
+            let mut remove_rule = None;
+            egui::ScrollArea::vertical()
+                .id_salt("app_rules_scroll")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("app_rules_grid")
+                        .num_columns(4)
+                        .spacing([10.0, 6.0])
+                        .show(ui, |ui| {
+                            ui.label("Window class");
+                            ui.label("Title contains");
+                            ui.label("Recipe");
+                            ui.end_row();
+                            
+                            for (i, rule) in config.app_rules.iter_mut().enumerate() {
+                                let class_edit = ui.add(egui::TextEdit::singleline(&mut rule.class)
+                                    .hint_text("e.g. firefox")
+                                    .desired_width(140.0));
+                                let title_edit = ui.add(egui::TextEdit::singleline(&mut rule.title)
+                                    .hint_text("any")
+                                    .desired_width(140.0));
+                                if class_edit.lost_focus() || title_edit.lost_focus() {
+                                    action_reload_rules = true;
+                                }
+                                
+                                egui::ComboBox::from_id_salt(("app_rule_recipe", i))
+                                    .selected_text(rule.recipe.as_deref().unwrap_or("Don't transform"))
+                                    .show_ui(ui, |ui| {
+                                        if ui.selectable_value(&mut rule.recipe, None, "Don't transform").changed() {
+                                            action_reload_rules = true;
+                                        }
+                                        for name in &recipe_names {
+                                            if ui.selectable_value(&mut rule.recipe, Some(name.clone()), name).changed() {
+                                                action_reload_rules = true;
+                                            }
+                                        }
+                                    });
+                                
+                                if ui.button(TRASH).on_hover_text("Remove rule").clicked() {
+                                    remove_rule = Some(i);
+                                }
+                                ui.end_row();
+                            }
+                        });
                 });
+            
+            if let Some(i) = remove_rule {
+                config.app_rules.remove(i);
+                action_reload_rules = true;
+            }
+            if ui.button(format!("{} Add Rule", PLUS)).clicked() {
+                config.app_rules.push(AppRule::new("", None));
+            }
+            
+            if action_reload_rules {
+                config.save().ok();
+            }
         });
+        
+        if let Some(encryption) = action_encryption {
+            self.set_history_encryption(encryption);
+        }
+        if action_reload_rules {
+            // The background service looks up the rules' recipes when it reloads
+            IpcClient::send(IpcCommand::ReloadRecipe).ok();
+        }
+    }
+    
+    /// Re-encrypt the history with a new key and remember how it is protected
+    fn set_history_encryption(&mut self, encryption: HistoryEncryption) {
+        let Some(ref mut hm) = self.history_manager else {
+            return;
+        };
+        
+        let key = match encryption {
+            HistoryEncryption::Keyring => HistoryKey::from_keyring(),
+            HistoryEncryption::Passphrase => Ok(HistoryKey::Passphrase(std::mem::take(&mut self.new_history_passphrase))),
+        };
+        match key.and_then(|key| hm.rekey(&key).map(|_| key)) {
+            Ok(key) => {
+                {
+                    let mut config = self.config.lock().unwrap();
+                    config.history_encryption = encryption;
+                    config.save().ok();
+                }
+                // The background service has to reopen history with the new key
+                notify_history_unlocked(&key);
+                self.history_key = Some(key);
+                self.show_status("History encryption changed");
+            }
+            Err(e) => self.show_status(format!("Failed to change history encryption: {:#}", e)),
+        }
     }
     
     fn show_history_tab(&mut self, ctx: &egui::Context) {
         // Track actions to perform after UI rendering
         let mut action_clear = false;
         let mut action_refresh = false;
+        let mut action_unlock = false;
         let mut copy_text: Option<String> = None;
-        let mut remove_index: Option<usize> = None;
+        let mut remove_id: Option<i64> = None;
+        let mut filters_changed = false;
+        
+        let search = self.history_search.trim();
+        let query = HistoryQuery {
+            search: (!search.is_empty()).then(|| search.to_string()),
+            recipe_id: self.history_recipe.clone(),
+            since: self.history_range.since(),
+            until: None,
+            offset: self.history_page * HISTORY_PAGE_SIZE,
+            limit: Some(HISTORY_PAGE_SIZE),
+        };
+        let filtered = query.search.is_some() || query.recipe_id.is_some() || query.since.is_some();
         
-        // Clone entries to avoid borrow issues
-        let entries: Vec<_> = self.history_manager
-            .as_mut()
-            .map(|hm| hm.get_all().to_vec())
-            .unwrap_or_default();
+        let page = self.history_page;
+        let view = self.history_view.take().unwrap_or_else(|| match self.history_manager {
+            Some(ref hm) => HistoryView {
+                entries: hm.query(&query).unwrap_or_default(),
+                total: hm.count(&query).unwrap_or(0),
+                recipes: hm.recipes().unwrap_or_default(),
+            },
+            None => HistoryView::default(),
+        });
+        let HistoryView { entries, total, recipes } = view;
+        let pages = total.div_ceil(HISTORY_PAGE_SIZE).max(1);
         let has_history = self.history_manager.is_some();
+        let locked = !has_history && self.history_key.is_none()
+            && self.config.lock().unwrap().history_encryption == HistoryEncryption::Passphrase;
         
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -618,19 +1069,80 @@ impl Dashboard {
                 });
             });
             
-            ui.add_space(10.0);
+            ui.add_space(5.0);
+            
+            // Filters
+            ui.horizontal(|ui| {
+                let search_edit = egui::TextEdit::singleline(&mut self.history_search)
+                    .hint_text(format!("{} Search history...", MAGNIFYING_GLASS))
+                    .desired_width(200.0);
+                filters_changed |= ui.add(search_edit).changed();
+                
+                let recipe_label = self.history_recipe.as_ref()
+                    .and_then(|id| recipes.iter().find(|(recipe_id, _)| recipe_id == id))
+                    .map_or("All recipes", |(_, name)| name.as_str());
+                egui::ComboBox::from_id_salt("history_recipe_combo")
+                    .selected_text(recipe_label)
+                    .show_ui(ui, |ui| {
+                        filters_changed |= ui.selectable_value(&mut self.history_recipe, None, "All recipes").changed();
+                        for (id, name) in &recipes {
+                            filters_changed |= ui.selectable_value(&mut self.history_recipe, Some(id.clone()), name).changed();
+                        }
+                    });
+                
+                egui::ComboBox::from_id_salt("history_range_combo")
+                    .selected_text(self.history_range.label())
+                    .show_ui(ui, |ui| {
+                        for range in HistoryRange::ALL {
+                            filters_changed |= ui.selectable_value(&mut self.history_range, range, range.label()).changed();
+                        }
+                    });
+            });
+            
+            ui.add_space(5.0);
             ui.separator();
             ui.add_space(5.0);
             
-            if !has_history {
+            if locked {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(40.0);
+                    ui.label(format!("{} History is protected by a passphrase.", LOCK));
+                    ui.add_space(10.0);
+                    let passphrase_edit = egui::TextEdit::singleline(&mut self.history_passphrase)
+                        .password(true)
+                        .hint_text("Passphrase")
+                        .desired_width(200.0);
+                    let response = ui.add(passphrase_edit);
+                    let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button(format!("{} Unlock", LOCK_OPEN)).clicked() || submitted {
+                        action_unlock = !self.history_passphrase.is_empty();
+                    }
+                });
+            } else if !has_history {
                 ui.centered_and_justified(|ui| {
                     ui.label("Failed to load history.");
                 });
+            } else if entries.is_empty() && filtered {
+                ui.centered_and_justified(|ui| {
+                    ui.label("No matching history entries.");
+                });
             } else if entries.is_empty() {
                 ui.centered_and_justified(|ui| {
                     ui.label("No history entries yet.\nTransformations will appear here.");
                 });
             } else {
+                // Paging
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(self.history_page > 0, egui::Button::new(CARET_LEFT)).clicked() {
+                        self.history_page -= 1;
+                    }
+                    ui.label(format!("Page {} of {} ({} entries)", self.history_page + 1, pages, total));
+                    if ui.add_enabled(self.history_page + 1 < pages, egui::Button::new(CARET_RIGHT)).clicked() {
+                        self.history_page += 1;
+                    }
+                });
+                ui.add_space(5.0);
+                
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     for (i, entry) in entries.iter().enumerate() {
                         ui.group(|ui| {
@@ -646,7 +1158,7 @@ impl Dashboard {
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                     // Delete button
                                     if ui.small_button(TRASH).clicked() {
-                                        remove_index = Some(i);
+                                        remove_id = entry.id;
                                     }
                                     
                                     // Copy transformed button
@@ -713,9 +1225,25 @@ impl Dashboard {
             }
         }
         
+        if action_unlock {
+            let key = HistoryKey::Passphrase(std::mem::take(&mut self.history_passphrase));
+            let max_size = self.config.lock().unwrap().max_history_size;
+            match HistoryManager::new(max_size, &key) {
+                Ok(hm) => {
+                    // Let the background service record history too
+                    notify_history_unlocked(&key);
+                    self.history_manager = Some(hm);
+                    self.history_key = Some(key);
+                    self.show_status("History unlocked");
+                }
+                Err(e) => self.show_status(format!("{:#}", e)),
+            }
+        }
+        
         if action_refresh {
             let max_size = self.config.lock().unwrap().max_history_size;
-            self.history_manager = HistoryManager::new(max_size).ok();
+            self.history_manager = self.history_key.as_ref()
+                .and_then(|key| HistoryManager::new(max_size, key).ok());
             self.show_status("History refreshed");
         }
         
@@ -725,13 +1253,27 @@ impl Dashboard {
             }
         }
         
-        if let Some(idx) = remove_index {
+        if let Some(id) = remove_id {
             if let Some(ref mut hm) = self.history_manager {
-                if hm.remove(idx).is_ok() {
+                if hm.remove(id).is_ok() {
                     self.show_status("Entry removed");
                 }
             }
         }
+        
+        // Start over when the filters change, and don't stay past the last page
+        if filters_changed {
+            self.history_page = 0;
+        } else if self.history_page >= pages {
+            self.history_page = pages - 1;
+        }
+        
+        // Keep the page unless something changed what it shows
+        let changed = filters_changed || action_clear || action_unlock || action_refresh
+            || remove_id.is_some() || self.history_page != page;
+        if !changed {
+            self.history_view = Some(HistoryView { entries, total, recipes });
+        }
     }
     
     fn show_about_tab(&mut self, ctx: &egui::Context) {
@@ -778,3 +1320,183 @@ impl Dashboard {
         });
     }
 }
+
+/// Tell the background service to reopen history with `key`
+fn notify_history_unlocked(key: &HistoryKey) {
+    let passphrase = match key {
+        HistoryKey::Passphrase(passphrase) => Some(Secret(passphrase.clone())),
+        HistoryKey::Raw(_) => None,
+    };
+    IpcClient::send(IpcCommand::UnlockHistory { passphrase }).ok();
+}
+
+/// Put a step under a condition, or take its steps out of one with `None`
+fn with_condition(step: Transformation, condition: Option<Condition>) -> Vec<Transformation> {
+    let steps = match step {
+        Transformation::When { steps, .. } => steps,
+        step => vec![step],
+    };
+    match condition {
+        Some(condition) => vec![Transformation::When { condition, steps }],
+        None => steps,
+    }
+}
+
+/// Edit the parameters of a step, and of the steps under its condition,
+/// returning whether they changed
+fn step_params(ui: &mut egui::Ui, id: egui::Id, step: &mut Transformation) -> bool {
+    match step {
+        Transformation::JsonQuery { expr, raw } => {
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                ui.label("Query:");
+                let query = ui.add(egui::TextEdit::singleline(expr).hint_text(".items[] | .name"));
+                let raw = ui.checkbox(raw, "Raw strings")
+                    .on_hover_text("Write strings without quotes");
+                query.changed() || raw.changed()
+            })
+            .inner
+        }
+        Transformation::TableToSql { table } => {
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                ui.label("Table:");
+                ui.text_edit_singleline(table).changed()
+            })
+            .inner
+        }
+        Transformation::ConvertFormat { from, to } => {
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                let mut changed = false;
+                ui.label("From:");
+                egui::ComboBox::from_id_salt(id.with("from"))
+                    .selected_text(from.map_or("Auto-detect", |format| format.display_name()))
+                    .show_ui(ui, |ui| {
+                        changed |= ui.selectable_value(from, None, "Auto-detect").changed();
+                        for format in DataFormat::ALL {
+                            changed |= ui.selectable_value(from, Some(format), format.display_name()).changed();
+                        }
+                    });
+                ui.label("To:");
+                egui::ComboBox::from_id_salt(id.with("to"))
+                    .selected_text(to.display_name())
+                    .show_ui(ui, |ui| {
+                        for format in DataFormat::ALL {
+                            changed |= ui.selectable_value(to, format, format.display_name()).changed();
+                        }
+                    });
+                changed
+            })
+            .inner
+        }
+        Transformation::SelectColumns { delimiter, columns } |
+        Transformation::DropColumns { delimiter, columns } => {
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                let delimiter = delimiter_combo(ui, id, delimiter);
+                ui.label("Columns:");
+                columns_edit(ui, id.with("columns"), columns) || delimiter
+            })
+            .inner
+        }
+        Transformation::SortByColumn { delimiter, column, numeric } => {
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                let delimiter = delimiter_combo(ui, id, delimiter);
+                ui.label("Column:");
+                let column = ui.add(egui::DragValue::new(column).range(1..=999));
+                let numeric = ui.checkbox(numeric, "Numeric")
+                    .on_hover_text("Compare as numbers, putting cells that aren't numbers last");
+                delimiter || column.changed() || numeric.changed()
+            })
+            .inner
+        }
+        Transformation::DedupeByColumn { delimiter, column } => {
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                let delimiter = delimiter_combo(ui, id, delimiter);
+                ui.label("Column:");
+                ui.add(egui::DragValue::new(column).range(1..=999)).changed() || delimiter
+            })
+            .inner
+        }
+        Transformation::TransposeColumns { delimiter } | Transformation::AlignColumns { delimiter } => {
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                delimiter_combo(ui, id, delimiter)
+            })
+            .inner
+        }
+        Transformation::When { steps, .. } => {
+            let mut changed = false;
+            for (i, step) in steps.iter_mut().enumerate() {
+                changed |= step_params(ui, id.with(i), step);
+            }
+            changed
+        }
+        _ => false,
+    }
+}
+
+/// Delimiters offered for column steps
+const DELIMITERS: [(char, &str); 5] = [
+    ('\t', "Tab"),
+    (',', "Comma"),
+    (';', "Semicolon"),
+    ('|', "Pipe"),
+    (' ', "Space"),
+];
+
+/// Pick the delimiter of a column step, returning whether it changed
+fn delimiter_combo(ui: &mut egui::Ui, id: egui::Id, delimiter: &mut char) -> bool {
+    let mut changed = false;
+    ui.label("Delimiter:");
+    let selected = DELIMITERS.iter()
+        .find(|(c, _)| c == delimiter)
+        .map_or_else(|| delimiter.to_string(), |(_, name)| name.to_string());
+    egui::ComboBox::from_id_salt(id.with("delimiter"))
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            for (c, name) in DELIMITERS {
+                changed |= ui.selectable_value(delimiter, c, name).changed();
+            }
+        });
+    changed
+}
+
+/// Edit a comma-separated list of column numbers, returning whether it changed.
+/// The text is kept as typed while the field has focus.
+fn columns_edit(ui: &mut egui::Ui, id: egui::Id, columns: &mut Vec<usize>) -> bool {
+    let mut text = ui.data_mut(|data| data.get_temp::<String>(id)).unwrap_or_else(|| {
+        columns.iter().map(|column| column.to_string()).collect::<Vec<_>>().join(", ")
+    });
+    let response = ui.add(egui::TextEdit::singleline(&mut text).hint_text("1, 3").desired_width(80.0));
+    if response.has_focus() {
+        ui.data_mut(|data| data.insert_temp(id, text.clone()));
+    } else {
+        ui.data_mut(|data| data.remove::<String>(id));
+    }
+    
+    let parsed: Vec<usize> = text.split([',', ' '])
+        .filter_map(|column| column.parse().ok())
+        .collect();
+    if !response.changed() || parsed == *columns {
+        return false;
+    }
+    *columns = parsed;
+    true
+}
+
+/// Pick the clipboard flavour a recipe reads or writes, returning whether it changed
+fn flavor_combo(ui: &mut egui::Ui, id: &str, flavor: &mut Flavor) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(flavor.display_name())
+        .show_ui(ui, |ui| {
+            for option in [Flavor::Text, Flavor::Html] {
+                changed |= ui.selectable_value(flavor, option, option.display_name()).changed();
+            }
+        });
+    changed
+}
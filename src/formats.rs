@@ -0,0 +1,392 @@
+//! Structured data formats
+//!
+//! Converts between JSON, YAML, TOML and URL query strings by way of JSON
+//! values. Conversions fail with the path of the offending value rather than
+//! dropping what the target format can't hold, like nulls in TOML.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+use crate::query::type_name;
+use crate::transformers::regex;
+
+/// Formats structured data can be converted between
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+    /// URL query strings, like `a=1&b=2`
+    QueryString,
+}
+
+impl DataFormat {
+    /// All supported formats
+    pub const ALL: [DataFormat; 4] = [Self::Json, Self::Yaml, Self::Toml, Self::QueryString];
+    
+    /// Get a human-readable name for the format
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+            Self::QueryString => "Query String",
+        }
+    }
+    
+    /// Recognize the format of text, trying the strictest formats first.
+    /// A single `key=value` is also TOML, but more likely a query string.
+    /// Only mappings and lists count as YAML, since almost any text is a YAML scalar.
+    pub fn detect(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        if serde_json::from_str::<Value>(text).is_ok() {
+            return Some(Self::Json);
+        }
+        if regex!(r"^[^\s=&?#]+=[^\s&#]*(&[^\s=&#]+(=[^\s&#]*)?)*$").is_match(query_part(text)) {
+            return Some(Self::QueryString);
+        }
+        if text.parse::<toml::Table>().is_ok() {
+            return Some(Self::Toml);
+        }
+        match serde_yaml::from_str::<serde_yaml::Value>(text) {
+            Ok(serde_yaml::Value::Mapping(_) | serde_yaml::Value::Sequence(_)) => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+    
+    /// Parse text in this format
+    fn parse(&self, text: &str) -> Result<Value, String> {
+        match self {
+            Self::Json => serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e)),
+            Self::Yaml => {
+                let mut value: serde_yaml::Value = serde_yaml::from_str(text)
+                    .map_err(|e| format!("Invalid YAML: {}", e))?;
+                value.apply_merge().map_err(|e| format!("Invalid YAML: {}", e))?;
+                from_yaml(value, "")
+            }
+            Self::Toml => {
+                let table: toml::Table = text.parse().map_err(|e| format!("Invalid TOML: {}", e))?;
+                from_toml(toml::Value::Table(table), "")
+            }
+            Self::QueryString => Ok(parse_query_string(text)),
+        }
+    }
+    
+    /// Write a value in this format
+    fn write(&self, value: &Value) -> Result<String, String> {
+        match self {
+            Self::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::to_string(&to_yaml(value, "")?)
+                .map(|yaml| yaml.trim_end().to_string())
+                .map_err(|e| e.to_string()),
+            Self::Toml => {
+                if !value.is_object() {
+                    return Err(format!("TOML needs keys at the top level, not {}", type_name(value)));
+                }
+                toml::to_string(&to_toml(value, "")?)
+                    .map(|toml| toml.trim_end().to_string())
+                    .map_err(|e| e.to_string())
+            }
+            Self::QueryString => write_query_string(value),
+        }
+    }
+}
+
+impl fmt::Display for DataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.display_name())
+    }
+}
+
+/// Convert structured data to another format, detecting the format it is in
+/// when `from` is None
+pub fn convert(text: &str, from: Option<DataFormat>, to: DataFormat) -> Result<String, String> {
+    let from = match from {
+        Some(from) => from,
+        None => DataFormat::detect(text)
+            .ok_or_else(|| "Not recognized as JSON, YAML, TOML or a query string".to_string())?,
+    };
+    to.write(&from.parse(text)?)
+}
+
+/// Describe where a value is, for error messages
+fn at(path: &str) -> String {
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!(" at `{}`", path)
+    }
+}
+
+fn key_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Convert YAML to JSON. Keys must be text, and tags have no equivalent.
+fn from_yaml(value: serde_yaml::Value, path: &str) -> Result<Value, String> {
+    use serde_yaml::Value as Yaml;
+    
+    Ok(match value {
+        Yaml::Null => Value::Null,
+        Yaml::Bool(b) => Value::Bool(b),
+        Yaml::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else {
+                let f = n.as_f64().unwrap_or(f64::NAN);
+                Value::Number(Number::from_f64(f).ok_or_else(|| format!("{} has no JSON equivalent{}", n, at(path)))?)
+            }
+        }
+        Yaml::String(s) => Value::String(s),
+        Yaml::Sequence(items) => Value::Array(
+            items.into_iter()
+                .enumerate()
+                .map(|(i, item)| from_yaml(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Mapping(mapping) => {
+            let mut map = Map::new();
+            for (key, value) in mapping {
+                let Yaml::String(key) = key else {
+                    let key = serde_yaml::to_string(&key).unwrap_or_default();
+                    return Err(format!("Key {} isn't text{}", key.trim_end(), at(path)));
+                };
+                let value = from_yaml(value, &key_path(path, &key))?;
+                map.insert(key, value);
+            }
+            Value::Object(map)
+        }
+        Yaml::Tagged(tagged) => return Err(format!("Tag {} has no equivalent{}", tagged.tag, at(path))),
+    })
+}
+
+/// Convert TOML to JSON. Dates and times become text.
+fn from_toml(value: toml::Value, path: &str) -> Result<Value, String> {
+    use toml::Value as Toml;
+    
+    Ok(match value {
+        Toml::String(s) => Value::String(s),
+        Toml::Integer(i) => Value::from(i),
+        Toml::Float(f) => Value::Number(Number::from_f64(f).ok_or_else(|| format!("{} has no JSON equivalent{}", f, at(path)))?),
+        Toml::Boolean(b) => Value::Bool(b),
+        Toml::Datetime(datetime) => Value::String(datetime.to_string()),
+        Toml::Array(items) => Value::Array(
+            items.into_iter()
+                .enumerate()
+                .map(|(i, item)| from_toml(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Toml::Table(table) => {
+            let mut map = Map::new();
+            for (key, value) in table {
+                let value = from_toml(value, &key_path(path, &key))?;
+                map.insert(key, value);
+            }
+            Value::Object(map)
+        }
+    })
+}
+
+/// A number as YAML and TOML hold it
+enum Number64 {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+}
+
+/// JSON numbers keep the digits they were written with, while other formats
+/// hold 64-bit integers and floats. Integers beyond those fail rather than
+/// being rounded.
+fn number64(n: &Number, path: &str, format: &str) -> Result<Number64, String> {
+    if let Some(i) = n.as_i64() {
+        return Ok(Number64::Int(i));
+    }
+    if let Some(u) = n.as_u64() {
+        return Ok(Number64::UInt(u));
+    }
+    match n.as_f64() {
+        Some(f) if f.is_finite() && n.to_string().contains(['.', 'e', 'E']) => Ok(Number64::Float(f)),
+        _ => Err(format!("{} is too large for {}{}", n, format, at(path))),
+    }
+}
+
+/// Convert JSON to YAML
+fn to_yaml(value: &Value, path: &str) -> Result<serde_yaml::Value, String> {
+    use serde_yaml::Value as Yaml;
+    
+    Ok(match value {
+        Value::Null => Yaml::Null,
+        Value::Bool(b) => Yaml::Bool(*b),
+        Value::Number(n) => Yaml::Number(match number64(n, path, "YAML")? {
+            Number64::Int(i) => i.into(),
+            Number64::UInt(u) => u.into(),
+            Number64::Float(f) => f.into(),
+        }),
+        Value::String(s) => Yaml::String(s.clone()),
+        Value::Array(items) => Yaml::Sequence(
+            items.iter()
+                .enumerate()
+                .map(|(i, item)| to_yaml(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => {
+            let mut mapping = serde_yaml::Mapping::new();
+            for (key, value) in map {
+                mapping.insert(Yaml::String(key.clone()), to_yaml(value, &key_path(path, key))?);
+            }
+            Yaml::Mapping(mapping)
+        }
+    })
+}
+
+/// Convert JSON to TOML, which has no null
+fn to_toml(value: &Value, path: &str) -> Result<toml::Value, String> {
+    use toml::Value as Toml;
+    
+    Ok(match value {
+        Value::Null => return Err(format!("TOML has no null{}", at(path))),
+        Value::Bool(b) => Toml::Boolean(*b),
+        Value::Number(n) => match number64(n, path, "TOML")? {
+            Number64::Int(i) => Toml::Integer(i),
+            Number64::UInt(_) => return Err(format!("{} is too large for TOML{}", n, at(path))),
+            Number64::Float(f) => Toml::Float(f),
+        },
+        Value::String(s) => Toml::String(s.clone()),
+        Value::Array(items) => Toml::Array(
+            items.iter()
+                .enumerate()
+                .map(|(i, item)| to_toml(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => {
+            let mut table = toml::Table::new();
+            for (key, value) in map {
+                table.insert(key.clone(), to_toml(value, &key_path(path, key))?);
+            }
+            Toml::Table(table)
+        }
+    })
+}
+
+/// The query string of a URL, or the text itself if it isn't one
+fn query_part(text: &str) -> &str {
+    let query = text.split_once('?').map_or(text, |(_, query)| query);
+    query.split_once('#').map_or(query, |(query, _)| query)
+}
+
+/// Parse a query string into an object. Repeated keys collect their values in a list.
+fn parse_query_string(text: &str) -> Value {
+    let mut map = Map::new();
+    for (key, value) in form_urlencoded::parse(query_part(text.trim()).as_bytes()) {
+        let value = Value::String(value.into_owned());
+        match map.get_mut(key.as_ref()) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+            None => {
+                map.insert(key.into_owned(), value);
+            }
+        }
+    }
+    Value::Object(map)
+}
+
+/// Write an object as a query string. Lists repeat their key, and numbers
+/// and booleans are written as text, since that is all query strings hold.
+fn write_query_string(value: &Value) -> Result<String, String> {
+    let Value::Object(map) = value else {
+        return Err(format!("A query string needs keys at the top level, not {}", type_name(value)));
+    };
+    
+    let mut serializer = form_urlencoded::Serializer::new(String::new());
+    for (key, value) in map {
+        let values = match value {
+            Value::Array(values) => values.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+        for value in values {
+            let text = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                Value::Null => return Err(format!("Query strings have no null{}", at(key))),
+                _ => return Err(format!("Query strings can't hold {}{}", type_name(value), at(key))),
+            };
+            serializer.append_pair(key, &text);
+        }
+    }
+    Ok(serializer.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_detect() {
+        assert_eq!(DataFormat::detect(r#"{"a": [1, 2]}"#), Some(DataFormat::Json));
+        assert_eq!(DataFormat::detect("[package]\nname = \"9paste\""), Some(DataFormat::Toml));
+        assert_eq!(DataFormat::detect("name: web\nports:\n  - 80"), Some(DataFormat::Yaml));
+        assert_eq!(DataFormat::detect("q=rust+lang&page=2"), Some(DataFormat::QueryString));
+        assert_eq!(DataFormat::detect("page=2"), Some(DataFormat::QueryString));
+        assert_eq!(DataFormat::detect("q=rust"), Some(DataFormat::QueryString));
+        assert_eq!(DataFormat::detect("page = 2"), Some(DataFormat::Toml));
+        assert_eq!(DataFormat::detect("https://example.com/search?q=rust&page=2#top"), Some(DataFormat::QueryString));
+        assert_eq!(DataFormat::detect("just some words"), None);
+    }
+    
+    #[test]
+    fn test_convert() {
+        let yaml = "name: web\nspec:\n  replicas: 2\n  ports:\n  - 80\n  - 443";
+        let json = convert(yaml, None, DataFormat::Json).unwrap();
+        assert_eq!(json, "{\n  \"name\": \"web\",\n  \"spec\": {\n    \"replicas\": 2,\n    \"ports\": [\n      80,\n      443\n    ]\n  }\n}");
+        assert_eq!(convert(&json, None, DataFormat::Yaml).unwrap(), yaml);
+        assert_eq!(
+            convert(&json, None, DataFormat::Toml).unwrap(),
+            "name = \"web\"\n\n[spec]\nreplicas = 2\nports = [80, 443]"
+        );
+        
+        let toml = "[package]\nname = \"9paste\"\nreleased = 2024-05-01";
+        assert_eq!(
+            convert(toml, Some(DataFormat::Toml), DataFormat::Yaml).unwrap(),
+            "package:\n  name: 9paste\n  released: 2024-05-01"
+        );
+        
+        let query = convert("?tag=a&tag=b&q=hello%20world", None, DataFormat::Json).unwrap();
+        assert_eq!(query, "{\n  \"tag\": [\n    \"a\",\n    \"b\"\n  ],\n  \"q\": \"hello world\"\n}");
+        assert_eq!(convert(&query, None, DataFormat::QueryString).unwrap(), "tag=a&tag=b&q=hello+world");
+    }
+    
+    #[test]
+    fn test_lossy_conversions_fail() {
+        assert_eq!(
+            convert(r#"{"a": {"b": [1, null]}}"#, None, DataFormat::Toml).unwrap_err(),
+            "TOML has no null at `a.b[1]`"
+        );
+        assert_eq!(convert("[1, 2]", None, DataFormat::Toml).unwrap_err(), "TOML needs keys at the top level, not an array");
+        assert_eq!(
+            convert(r#"{"a": {"b": 1}}"#, None, DataFormat::QueryString).unwrap_err(),
+            "Query strings can't hold an object at `a`"
+        );
+        assert_eq!(convert("ports:\n  1: a", None, DataFormat::Json).unwrap_err(), "Key 1 isn't text at `ports`");
+        assert_eq!(convert("a: !Ref b", None, DataFormat::Json).unwrap_err(), "Tag !Ref has no equivalent at `a`");
+        assert!(convert("{\"a\": ", Some(DataFormat::Json), DataFormat::Yaml).unwrap_err().starts_with("Invalid JSON"));
+        assert_eq!(
+            convert(r#"{"id": 12345678901234567890123}"#, None, DataFormat::Yaml).unwrap_err(),
+            "12345678901234567890123 is too large for YAML at `id`"
+        );
+        assert_eq!(
+            convert(r#"{"id": 18446744073709551615}"#, None, DataFormat::Toml).unwrap_err(),
+            "18446744073709551615 is too large for TOML at `id`"
+        );
+    }
+}
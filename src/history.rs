@@ -0,0 +1,650 @@
+//! Clipboard history
+//!
+//! Stores transformed clipboard entries in an embedded SQLite database with
+//! FTS5 full-text search. The daemon, dashboard and quick menu all open the
+//! same database; SQLite's locking keeps their writes from clobbering each other.
+//!
+//! The database is encrypted at rest with SQLCipher (AES-256 with an HMAC on
+//! every page), using a random key kept in the OS keyring or a user passphrase.
+
+use std::path::Path;
+use std::fs;
+use std::fmt;
+use std::io::Read;
+use std::time::Duration;
+use anyhow::{Result, Context, anyhow, bail};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, params_from_iter, Connection, Row, Transaction, TransactionBehavior};
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config::{Config, HistoryEncryption};
+use crate::recipe::Recipe;
+
+/// How long to wait for another process that holds the database lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// OS keyring entry that holds the history key
+const KEYRING_SERVICE: &str = "9paste";
+const KEYRING_USER: &str = "history-key";
+
+/// Header of an unencrypted SQLite database file
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Columns selected for a `HistoryEntry`, in `HistoryEntry::from_row` order
+const ENTRY_COLUMNS: &str = "id, original, transformed, recipe_id, recipe_name, timestamp";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        original TEXT NOT NULL,
+        transformed TEXT,
+        recipe_id TEXT,
+        recipe_name TEXT,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS history_timestamp ON history (timestamp);
+    CREATE INDEX IF NOT EXISTS history_recipe ON history (recipe_id);
+    
+    -- Full-text index over the text columns, kept in sync by triggers
+    CREATE VIRTUAL TABLE IF NOT EXISTS history_fts USING fts5(
+        original, transformed, content = 'history', content_rowid = 'id'
+    );
+    CREATE TRIGGER IF NOT EXISTS history_insert AFTER INSERT ON history BEGIN
+        INSERT INTO history_fts (rowid, original, transformed)
+        VALUES (new.id, new.original, new.transformed);
+    END;
+    CREATE TRIGGER IF NOT EXISTS history_delete AFTER DELETE ON history BEGIN
+        INSERT INTO history_fts (history_fts, rowid, original, transformed)
+        VALUES ('delete', old.id, old.original, old.transformed);
+    END;
+";
+
+/// Clipboard history entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Database ID, set once the entry has been stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    /// Original text before transformation
+    pub original: String,
+    /// Transformed text (if any)
+    pub transformed: Option<String>,
+    /// Recipe ID used for transformation
+    pub recipe_id: Option<String>,
+    /// Recipe name used for transformation
+    pub recipe_name: Option<String>,
+    /// Timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
+impl HistoryEntry {
+    /// Create a new entry timestamped now
+    pub fn new(original: String, transformed: Option<String>, recipe: Option<&Recipe>) -> Self {
+        Self {
+            id: None,
+            original,
+            transformed,
+            recipe_id: recipe.map(|r| r.id.to_string()),
+            recipe_name: recipe.map(|r| r.name.clone()),
+            timestamp: Utc::now(),
+        }
+    }
+    
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: Some(row.get(0)?),
+            original: row.get(1)?,
+            transformed: row.get(2)?,
+            recipe_id: row.get(3)?,
+            recipe_name: row.get(4)?,
+            timestamp: DateTime::from_timestamp_millis(row.get(5)?).unwrap_or_default(),
+        })
+    }
+}
+
+/// Filters and paging for history queries
+#[derive(Debug, Clone, Default)]
+pub struct HistoryQuery {
+    /// Full-text search; every word must match the start of a word in the entry
+    pub search: Option<String>,
+    /// Only entries transformed by this recipe (ID)
+    pub recipe_id: Option<String>,
+    /// Only entries at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Number of matching entries to skip
+    pub offset: usize,
+    /// Maximum number of entries to return
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    /// Query the most recent `limit` entries
+    pub fn recent(limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Default::default()
+        }
+    }
+    
+    /// Build the WHERE clause and its parameters
+    fn filter(&self) -> (String, Vec<Value>) {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        
+        if let Some(terms) = self.search.as_deref().and_then(fts_query) {
+            conditions.push("id IN (SELECT rowid FROM history_fts WHERE history_fts MATCH ?)");
+            values.push(Value::Text(terms));
+        }
+        if let Some(ref recipe_id) = self.recipe_id {
+            conditions.push("recipe_id = ?");
+            values.push(Value::Text(recipe_id.clone()));
+        }
+        if let Some(since) = self.since {
+            conditions.push("timestamp >= ?");
+            values.push(Value::Integer(since.timestamp_millis()));
+        }
+        if let Some(until) = self.until {
+            conditions.push("timestamp < ?");
+            values.push(Value::Integer(until.timestamp_millis()));
+        }
+        
+        if conditions.is_empty() {
+            (String::new(), values)
+        } else {
+            (format!(" WHERE {}", conditions.join(" AND ")), values)
+        }
+    }
+}
+
+/// Turn user search text into an FTS5 query of quoted prefix terms,
+/// so punctuation in the search can't produce a syntax error
+fn fts_query(search: &str) -> Option<String> {
+    let terms: Vec<_> = search.split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Key for the encrypted history database
+#[derive(Clone, PartialEq, Eq)]
+pub enum HistoryKey {
+    /// A random 256-bit key, used as is
+    Raw([u8; 32]),
+    /// A passphrase, stretched by SQLCipher's key derivation
+    Passphrase(String),
+}
+
+impl HistoryKey {
+    /// Get the history key from the OS keyring, creating one on first use
+    pub fn from_keyring() -> Result<Self> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+            .context("Failed to access OS keyring")?;
+        
+        match entry.get_password() {
+            Ok(hex) => Self::from_hex(&hex),
+            Err(keyring::Error::NoEntry) => {
+                let mut key = [0u8; 32];
+                getrandom::getrandom(&mut key)
+                    .map_err(|e| anyhow!("Failed to generate history key: {}", e))?;
+                let key = Self::Raw(key);
+                entry.set_password(&key.to_hex().unwrap_or_default())
+                    .context("Failed to store history key in OS keyring")?;
+                info!("Created history key in OS keyring");
+                Ok(key)
+            }
+            Err(e) => Err(e).context("Failed to read history key from OS keyring"),
+        }
+    }
+    
+    /// Get the configured history key, or None if history is protected by
+    /// a passphrase that has to be entered first
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        match config.history_encryption {
+            HistoryEncryption::Keyring => Self::from_keyring().map(Some),
+            HistoryEncryption::Passphrase => Ok(None),
+        }
+    }
+    
+    /// Parse a raw key from 64 hex digits
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.is_ascii() {
+            bail!("History key must be 64 hex digits");
+        }
+        
+        let mut key = [0u8; 32];
+        for (byte, digits) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits)?;
+            *byte = u8::from_str_radix(digits, 16)
+                .context("History key must be 64 hex digits")?;
+        }
+        Ok(Self::Raw(key))
+    }
+    
+    /// Format a raw key as hex digits
+    pub fn to_hex(&self) -> Option<String> {
+        match self {
+            Self::Raw(key) => Some(key.iter().map(|b| format!("{:02x}", b)).collect()),
+            Self::Passphrase(_) => None,
+        }
+    }
+    
+    /// The key in the form SQLCipher's `key` pragma expects
+    fn sql_value(&self) -> String {
+        match self {
+            Self::Raw(_) => format!("x'{}'", self.to_hex().unwrap_or_default()),
+            Self::Passphrase(passphrase) => passphrase.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for HistoryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the secret
+        match self {
+            Self::Raw(_) => f.write_str("HistoryKey::Raw(..)"),
+            Self::Passphrase(_) => f.write_str("HistoryKey::Passphrase(..)"),
+        }
+    }
+}
+
+/// Clipboard history manager
+pub struct HistoryManager {
+    conn: Connection,
+    max_size: usize,
+}
+
+impl HistoryManager {
+    /// Open the encrypted history database in the data directory,
+    /// encrypting an unencrypted one and importing an old `history.json`
+    pub fn new(max_size: usize, key: &HistoryKey) -> Result<Self> {
+        let data_dir = Config::data_dir()?;
+        let path = data_dir.join("history.db");
+        
+        if is_plaintext(&path) {
+            encrypt_database(&path, key)?;
+        }
+        
+        let mut manager = Self::open(&path, max_size, Some(key))?;
+        if let Err(e) = manager.import_json(&data_dir.join("history.json")) {
+            warn!("Failed to import old history: {:#}", e);
+        }
+        Ok(manager)
+    }
+    
+    /// Open (or create) a history database at `path`,
+    /// encrypted with `key` or unencrypted if there is none
+    pub fn open(path: &Path, max_size: usize, key: Option<&HistoryKey>) -> Result<Self> {
+        let conn = Connection::open(path)
+            .context("Failed to open history database")?;
+        
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key.sql_value())?;
+            // The key is only checked once the database is read
+            conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+                .map_err(|_| anyhow!("Wrong history key or passphrase"))?;
+        }
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        
+        // WAL lets the dashboard read while the daemon writes
+        conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create history tables")?;
+        
+        Ok(Self { conn, max_size })
+    }
+    
+    /// Import entries from a `history.json` file written by older versions,
+    /// then delete it so no unencrypted copy is left behind.
+    /// Returns the number of imported entries.
+    pub fn import_json(&mut self, path: &Path) -> Result<usize> {
+        if !path.exists() {
+            return Ok(0);
+        }
+        
+        // Check again under the write lock, in case another process just imported it
+        let max_size = self.max_size;
+        let tx = self.write()?;
+        if !path.exists() {
+            return Ok(0);
+        }
+        
+        let data = fs::read_to_string(path)
+            .context("Failed to read history file")?;
+        let entries: Vec<HistoryEntry> = serde_json::from_str(&data)
+            .context("Failed to parse history file")?;
+        
+        // The JSON file is newest first
+        for entry in entries.iter().rev() {
+            insert(&tx, entry)?;
+        }
+        trim(&tx, max_size)?;
+        
+        // Move the file aside while still holding the lock, and only delete it once committed
+        let importing = path.with_extension("json.importing");
+        fs::rename(path, &importing)
+            .context("Failed to move imported history file")?;
+        if let Err(e) = tx.commit() {
+            let _ = fs::rename(&importing, path);
+            return Err(e).context("Failed to import history");
+        }
+        fs::remove_file(&importing)
+            .context("Failed to delete imported history file")?;
+        
+        info!("Imported {} history entries from {}", entries.len(), path.display());
+        Ok(entries.len())
+    }
+    
+    /// Change the key the database is encrypted with
+    pub fn rekey(&mut self, key: &HistoryKey) -> Result<()> {
+        self.conn.pragma_update(None, "rekey", key.sql_value())
+            .context("Failed to change history key")
+    }
+    
+    /// Add an entry to history
+    pub fn add(&mut self, entry: HistoryEntry) -> Result<()> {
+        let max_size = self.max_size;
+        let tx = self.write()?;
+        insert(&tx, &entry)?;
+        trim(&tx, max_size)?;
+        tx.commit().context("Failed to save history entry")
+    }
+    
+    /// Get the entries matching a query, newest first
+    pub fn query(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>> {
+        let (filter, mut values) = query.filter();
+        let sql = format!(
+            "SELECT {} FROM history{} ORDER BY timestamp DESC, id DESC LIMIT ? OFFSET ?",
+            ENTRY_COLUMNS, filter,
+        );
+        // A negative limit means no limit
+        values.push(Value::Integer(query.limit.map_or(-1, |l| l as i64)));
+        values.push(Value::Integer(query.offset as i64));
+        
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let entries = stmt.query_map(params_from_iter(values), HistoryEntry::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read history")?;
+        Ok(entries)
+    }
+    
+    /// Count the entries matching a query, ignoring its paging
+    pub fn count(&self, query: &HistoryQuery) -> Result<usize> {
+        let (filter, values) = query.filter();
+        let sql = format!("SELECT COUNT(*) FROM history{}", filter);
+        let count: i64 = self.conn.query_row(&sql, params_from_iter(values), |row| row.get(0))?;
+        Ok(count as usize)
+    }
+    
+    /// Get the most recent entries
+    pub fn recent(&self, limit: usize) -> Result<Vec<HistoryEntry>> {
+        self.query(&HistoryQuery::recent(limit))
+    }
+    
+    /// Get the ID and latest name of every recipe that appears in history
+    pub fn recipes(&self) -> Result<Vec<(String, String)>> {
+        // SQLite takes the bare columns from the row with the MAX() timestamp
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT recipe_id, recipe_name, MAX(timestamp) FROM history
+             WHERE recipe_id IS NOT NULL GROUP BY recipe_id ORDER BY recipe_name",
+        )?;
+        let recipes = stmt.query_map([], |row| {
+            Ok((row.get(0)?, row.get::<_, Option<String>>(1)?.unwrap_or_default()))
+        })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(recipes)
+    }
+    
+    /// Remove a specific entry by ID
+    pub fn remove(&mut self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM history WHERE id = ?", [id])
+            .context("Failed to remove history entry")?;
+        Ok(())
+    }
+    
+    /// Clear all history
+    pub fn clear(&mut self) -> Result<()> {
+        self.conn.execute("DELETE FROM history", [])
+            .context("Failed to clear history")?;
+        Ok(())
+    }
+    
+    /// Start a transaction that holds the write lock from the beginning,
+    /// so it waits for other writers instead of failing part way
+    fn write(&mut self) -> Result<Transaction<'_>> {
+        self.conn.transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Failed to lock history database")
+    }
+}
+
+/// Whether `path` is an existing, unencrypted SQLite database
+fn is_plaintext(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| &header == PLAINTEXT_HEADER)
+}
+
+/// Replace an unencrypted database with an encrypted copy
+fn encrypt_database(path: &Path, key: &HistoryKey) -> Result<()> {
+    let encrypted = path.with_extension("db.encrypting");
+    let _ = fs::remove_file(&encrypted);
+    
+    {
+        let conn = Connection::open(path)
+            .context("Failed to open history database")?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute(
+            "ATTACH DATABASE ? AS encrypted KEY ?",
+            params![encrypted.to_string_lossy(), key.sql_value()],
+        )?;
+        conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+            .context("Failed to encrypt history database")?;
+        conn.execute("DETACH DATABASE encrypted", [])?;
+    }
+    
+    // The export includes anything still in the write-ahead log
+    fs::rename(&encrypted, path)
+        .context("Failed to replace history database")?;
+    for suffix in ["db-wal", "db-shm"] {
+        let _ = fs::remove_file(path.with_extension(suffix));
+    }
+    
+    info!("Encrypted history database");
+    Ok(())
+}
+
+fn insert(conn: &Connection, entry: &HistoryEntry) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO history (original, transformed, recipe_id, recipe_name, timestamp)
+         VALUES (?, ?, ?, ?, ?)",
+    )?
+        .execute(params![
+            entry.original,
+            entry.transformed,
+            entry.recipe_id,
+            entry.recipe_name,
+            entry.timestamp.timestamp_millis(),
+        ])
+        .context("Failed to save history entry")?;
+    Ok(())
+}
+
+/// Delete all but the newest `max_size` entries
+fn trim(conn: &Connection, max_size: usize) -> Result<()> {
+    conn.execute(
+        "DELETE FROM history WHERE id NOT IN
+         (SELECT id FROM history ORDER BY timestamp DESC, id DESC LIMIT ?)",
+        [max_size as i64],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    
+    /// A fresh database path in the temp directory, deleted along with
+    /// its write-ahead log when dropped
+    struct TempDb(PathBuf);
+    
+    impl std::ops::Deref for TempDb {
+        type Target = Path;
+        
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+    
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            for suffix in ["db", "db-wal", "db-shm", "db.encrypting"] {
+                let _ = fs::remove_file(self.0.with_extension(suffix));
+            }
+        }
+    }
+    
+    fn temp_db() -> TempDb {
+        TempDb(std::env::temp_dir().join(format!("9paste-history-{}.db", uuid::Uuid::new_v4())))
+    }
+    
+    fn entry(original: &str, recipe: Option<&Recipe>, minutes_ago: i64) -> HistoryEntry {
+        let mut entry = HistoryEntry::new(original.to_string(), Some(original.to_uppercase()), recipe);
+        entry.timestamp = Utc::now() - chrono::Duration::minutes(minutes_ago);
+        entry
+    }
+    
+    #[test]
+    fn test_search_filter_and_page() {
+        let db = temp_db();
+        let mut hm = HistoryManager::open(&db, 100, None).unwrap();
+        let recipe = Recipe::new("Upper");
+        
+        hm.add(entry("hello world", Some(&recipe), 30)).unwrap();
+        hm.add(entry("goodbye \"world\"", None, 20)).unwrap();
+        hm.add(entry("hello again", Some(&recipe), 10)).unwrap();
+        
+        let all = hm.recent(10).unwrap();
+        let originals: Vec<_> = all.iter().map(|e| e.original.as_str()).collect();
+        assert_eq!(originals, ["hello again", "goodbye \"world\"", "hello world"]);
+        
+        let search = HistoryQuery { search: Some("wor \"".into()), ..Default::default() };
+        assert_eq!(hm.count(&search).unwrap(), 2);
+        
+        let by_recipe = HistoryQuery {
+            recipe_id: Some(recipe.id.to_string()),
+            since: Some(Utc::now() - chrono::Duration::minutes(15)),
+            ..Default::default()
+        };
+        assert_eq!(hm.query(&by_recipe).unwrap()[0].original, "hello again");
+        assert_eq!(hm.count(&by_recipe).unwrap(), 1);
+        
+        let page = HistoryQuery { offset: 1, limit: Some(1), ..Default::default() };
+        assert_eq!(hm.query(&page).unwrap()[0].original, "goodbye \"world\"");
+        assert_eq!(hm.recipes().unwrap(), vec![(recipe.id.to_string(), "Upper".to_string())]);
+        
+        hm.remove(all[0].id.unwrap()).unwrap();
+        let search = HistoryQuery { search: Some("again".into()), ..Default::default() };
+        assert_eq!(hm.count(&search).unwrap(), 0);
+    }
+    
+    #[test]
+    fn test_trims_to_max_size() {
+        let db = temp_db();
+        let mut hm = HistoryManager::open(&db, 2, None).unwrap();
+        for (i, text) in ["one", "two", "three"].iter().enumerate() {
+            hm.add(entry(text, None, 10 - i as i64)).unwrap();
+        }
+        
+        let originals: Vec<_> = hm.recent(10).unwrap().into_iter().map(|e| e.original).collect();
+        assert_eq!(originals, ["three", "two"]);
+    }
+    
+    #[test]
+    fn test_imports_json_once() {
+        let json = std::env::temp_dir().join(format!("9paste-history-{}.json", uuid::Uuid::new_v4()));
+        let old = vec![entry("newest", None, 1), entry("oldest", None, 2)];
+        fs::write(&json, serde_json::to_string(&old).unwrap()).unwrap();
+        
+        let db = temp_db();
+        let mut hm = HistoryManager::open(&db, 100, None).unwrap();
+        assert_eq!(hm.import_json(&json).unwrap(), 2);
+        assert_eq!(hm.import_json(&json).unwrap(), 0);
+        assert!(!json.exists());
+        assert_eq!(hm.recent(1).unwrap()[0].original, "newest");
+    }
+    
+    #[test]
+    fn test_concurrent_writers() {
+        let path = temp_db();
+        let key = HistoryKey::Passphrase("secret".into());
+        HistoryManager::open(&path, 1000, Some(&key)).unwrap();
+        
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let path = path.to_path_buf();
+                let key = key.clone();
+                std::thread::spawn(move || {
+                    let mut hm = HistoryManager::open(&path, 1000, Some(&key)).unwrap();
+                    for i in 0..25 {
+                        hm.add(entry(&format!("writer {} entry {}", w, i), None, 0)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        
+        let hm = HistoryManager::open(&path, 1000, Some(&key)).unwrap();
+        assert_eq!(hm.count(&HistoryQuery::default()).unwrap(), 100);
+    }
+    
+    #[test]
+    fn test_encryption() {
+        let path = temp_db();
+        {
+            let mut hm = HistoryManager::open(&path, 100, None).unwrap();
+            hm.add(entry("my password is hunter2", None, 0)).unwrap();
+        }
+        assert!(is_plaintext(&path));
+        
+        let key = HistoryKey::from_hex(&"ab".repeat(32)).unwrap();
+        encrypt_database(&path, &key).unwrap();
+        assert!(!is_plaintext(&path));
+        assert!(!fs::read(&*path).unwrap().windows(7).any(|w| w == b"hunter2"));
+        
+        assert!(HistoryManager::open(&path, 100, None).is_err());
+        assert!(HistoryManager::open(&path, 100, Some(&HistoryKey::Passphrase("guess".into()))).is_err());
+        
+        let mut hm = HistoryManager::open(&path, 100, Some(&key)).unwrap();
+        let search = HistoryQuery { search: Some("hunter".into()), ..Default::default() };
+        assert_eq!(hm.count(&search).unwrap(), 1);
+        
+        let passphrase = HistoryKey::Passphrase("correct horse".into());
+        hm.rekey(&passphrase).unwrap();
+        drop(hm);
+        assert!(HistoryManager::open(&path, 100, Some(&key)).is_err());
+        assert_eq!(HistoryManager::open(&path, 100, Some(&passphrase)).unwrap().count(&search).unwrap(), 1);
+    }
+    
+    #[test]
+    fn test_key_hex_roundtrip() {
+        let hex = "00ff".repeat(16);
+        let key = HistoryKey::from_hex(&hex).unwrap();
+        assert_eq!(key.to_hex().unwrap(), hex);
+        assert!(HistoryKey::from_hex("abc").is_err());
+        assert!(HistoryKey::from_hex(&"zz".repeat(32)).is_err());
+        assert_eq!(format!("{:?}", key), "HistoryKey::Raw(..)");
+    }
+}
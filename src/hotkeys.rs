@@ -2,7 +2,7 @@
 //!
 //! Provides cross-platform global hotkey registration and handling.
 
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
 use anyhow::{Result, Context};
 use global_hotkey::{
     GlobalHotKeyManager, GlobalHotKeyEvent,
@@ -10,6 +10,7 @@ use global_hotkey::{
 };
 use tokio::sync::mpsc;
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
 /// Hotkey actions
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,13 +21,20 @@ pub enum HotkeyAction {
     OpenQuickMenu,
     /// Open dashboard
     OpenDashboard,
+    /// Restore the clipboard from before the last transformation
+    Undo,
+    /// Apply the active recipe to the clipboard and paste the result
+    TransformAndPaste,
+    /// Apply a recipe to the current clipboard once
+    ApplyRecipe(Uuid),
 }
 
 /// Hotkey manager for registering and handling global hotkeys
 pub struct HotkeyManager {
     manager: Option<GlobalHotKeyManager>,
     running: Arc<AtomicBool>,
-    registered_hotkeys: Vec<(HotKey, HotkeyAction)>,
+    /// Shared with the listener thread so hotkeys can change while it runs
+    registered_hotkeys: Arc<Mutex<Vec<(HotKey, HotkeyAction)>>>,
 }
 
 impl HotkeyManager {
@@ -38,7 +46,7 @@ impl HotkeyManager {
         Ok(Self {
             manager: Some(manager),
             running: Arc::new(AtomicBool::new(false)),
-            registered_hotkeys: Vec::new(),
+            registered_hotkeys: Arc::new(Mutex::new(Vec::new())),
         })
     }
     
@@ -142,21 +150,36 @@ impl HotkeyManager {
             manager.register(hotkey)
                 .context("Failed to register hotkey")?;
             
-            self.registered_hotkeys.push((hotkey, action));
+            self.registered_hotkeys.lock().unwrap().push((hotkey, action));
             info!("Registered hotkey: {} -> {:?}", hotkey_str, action);
         }
         
         Ok(())
     }
     
+    /// Unregister all hotkeys bound to recipes, keeping the global ones
+    pub fn unregister_recipe_hotkeys(&mut self) -> Result<()> {
+        let mut registered = self.registered_hotkeys.lock().unwrap();
+        if let Some(ref manager) = self.manager {
+            for (hotkey, action) in registered.iter() {
+                if matches!(action, HotkeyAction::ApplyRecipe(_)) {
+                    manager.unregister(*hotkey).ok();
+                }
+            }
+        }
+        registered.retain(|(_, action)| !matches!(action, HotkeyAction::ApplyRecipe(_)));
+        Ok(())
+    }
+    
     /// Unregister all hotkeys
     pub fn unregister_all(&mut self) -> Result<()> {
+        let mut registered = self.registered_hotkeys.lock().unwrap();
         if let Some(ref manager) = self.manager {
-            for (hotkey, _) in &self.registered_hotkeys {
+            for (hotkey, _) in registered.iter() {
                 manager.unregister(*hotkey).ok();
             }
         }
-        self.registered_hotkeys.clear();
+        registered.clear();
         Ok(())
     }
     
@@ -166,7 +189,7 @@ impl HotkeyManager {
         self.running.store(true, Ordering::SeqCst);
         
         let running = Arc::clone(&self.running);
-        let hotkeys = self.registered_hotkeys.clone();
+        let hotkeys = Arc::clone(&self.registered_hotkeys);
         
         std::thread::spawn(move || {
             let receiver = GlobalHotKeyEvent::receiver();
@@ -174,14 +197,15 @@ impl HotkeyManager {
             while running.load(Ordering::SeqCst) {
                 if let Ok(event) = receiver.recv_timeout(std::time::Duration::from_millis(100)) {
                     // Find the action for this hotkey
-                    for (hotkey, action) in &hotkeys {
-                        if hotkey.id() == event.id {
-                            info!("Hotkey triggered: {:?}", action);
-                            if tx.blocking_send(*action).is_err() {
-                                error!("Failed to send hotkey action");
-                                return;
-                            }
-                            break;
+                    let action = hotkeys.lock().unwrap().iter()
+                        .find(|(hotkey, _)| hotkey.id() == event.id)
+                        .map(|(_, action)| *action);
+                    
+                    if let Some(action) = action {
+                        info!("Hotkey triggered: {:?}", action);
+                        if tx.blocking_send(action).is_err() {
+                            error!("Failed to send hotkey action");
+                            return;
                         }
                     }
                 }
@@ -0,0 +1,764 @@
+//! HTML clipboard content
+//!
+//! A small, forgiving HTML tokenizer and the conversions recipes apply to the
+//! HTML flavour of the clipboard: sanitizing to a set of allowed tags, and
+//! converting to Markdown or plain text.
+
+use crate::transformers::regex;
+
+/// Tags `SanitizeHtml` keeps by default
+pub const DEFAULT_ALLOWED_TAGS: &[&str] = &[
+    "a", "b", "strong", "i", "em", "u", "s", "code", "pre", "p", "br", "hr",
+    "h1", "h2", "h3", "h4", "h5", "h6", "ul", "ol", "li", "blockquote",
+    "table", "thead", "tbody", "tr", "th", "td",
+];
+
+/// Elements dropped together with everything inside them
+const DROPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "head", "title", "template", "noscript", "iframe", "object", "svg",
+];
+
+/// Elements that never have content or an end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// Elements that start on a new line in plain text and Markdown
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "div", "dl", "dt", "dd", "figure", "figcaption", "footer",
+    "form", "header", "main", "nav", "section", "table", "thead", "tbody", "tfoot",
+];
+
+/// A piece of HTML
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    /// Text, with entities still encoded
+    Text(&'a str),
+    /// A start tag, with a lowercase name and decoded attribute values
+    Start { name: String, attrs: Vec<(String, String)>, self_closing: bool },
+    /// An end tag, with a lowercase name
+    End { name: String },
+}
+
+/// Split HTML into text and tags. Comments, doctypes and processing
+/// instructions are skipped; a `<` that doesn't start a tag is text.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let bytes = html.as_bytes();
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    
+    while i < bytes.len() {
+        if bytes[i] != b'<' {
+            i += 1;
+            continue;
+        }
+        
+        let rest = &html[i..];
+        let (token, len) = if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            (None, end)
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            (None, end)
+        } else if rest.starts_with("</") && rest[2..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            let name = tag_name(&rest[2..]);
+            (Some(Token::End { name }), end)
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (token, len) = start_tag(rest);
+            (Some(token), len)
+        } else {
+            // A lone `<` is text
+            i += 1;
+            continue;
+        };
+        
+        if text_start < i {
+            tokens.push(Token::Text(&html[text_start..i]));
+        }
+        i += len;
+        text_start = i;
+        
+        // Script and style content is raw text, which may contain `<`
+        let raw_text_end = match token {
+            Some(Token::Start { ref name, self_closing: false, .. }) if name == "script" || name == "style" => {
+                let close = format!("</{}", name);
+                Some(html[i..].to_ascii_lowercase().find(&close).map_or(html.len(), |end| i + end))
+            }
+            _ => None,
+        };
+        tokens.extend(token);
+        if let Some(end) = raw_text_end {
+            if i < end {
+                tokens.push(Token::Text(&html[i..end]));
+            }
+            i = end;
+            text_start = end;
+        }
+    }
+    
+    if text_start < html.len() {
+        tokens.push(Token::Text(&html[text_start..]));
+    }
+    tokens
+}
+
+/// Read a lowercase tag name from the start of text
+fn tag_name(text: &str) -> String {
+    text.chars()
+        .take_while(|&c| c.is_ascii_alphanumeric() || c == '-' || c == ':')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Parse a start tag at the start of text, returning it and its length
+fn start_tag(text: &str) -> (Token<'static>, usize) {
+    let name = tag_name(&text[1..]);
+    let mut attrs = Vec::new();
+    let mut self_closing = false;
+    let mut i = 1 + name.len();
+    
+    loop {
+        let rest = &text[i..];
+        let trimmed = rest.trim_start();
+        i += rest.len() - trimmed.len();
+        
+        if trimmed.is_empty() {
+            break;
+        }
+        if trimmed.starts_with('>') {
+            i += 1;
+            break;
+        }
+        if trimmed.starts_with("/>") {
+            self_closing = true;
+            i += 2;
+            break;
+        }
+        if trimmed.starts_with('/') {
+            i += 1;
+            continue;
+        }
+        
+        let attr_len = trimmed
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(trimmed.len())
+            .max(1);
+        let attr = trimmed[..attr_len].to_ascii_lowercase();
+        i += attr_len;
+        
+        let rest = &text[i..];
+        let after_name = rest.trim_start();
+        if !after_name.starts_with('=') {
+            attrs.push((attr, String::new()));
+            continue;
+        }
+        i += rest.len() - after_name.len() + 1;
+        
+        let rest = &text[i..];
+        let value_start = rest.trim_start();
+        i += rest.len() - value_start.len();
+        let (value, len) = match value_start.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = value_start[1..].find(quote).map_or(value_start.len(), |end| end + 1);
+                (&value_start[1..end], (end + 1).min(value_start.len()))
+            }
+            _ => {
+                let end = value_start
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(value_start.len());
+                (&value_start[..end], end)
+            }
+        };
+        attrs.push((attr, decode_entities(value)));
+        i += len;
+    }
+    
+    (Token::Start { name, attrs, self_closing }, i.min(text.len()))
+}
+
+/// Decode character references like `&amp;`, `&#8217;` and `&#x2014;`.
+/// Unknown named references are left as they are.
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    
+    let re = regex!(r"&(?:#([0-9]{1,7})|#[xX]([0-9a-fA-F]{1,6})|([A-Za-z][A-Za-z0-9]{1,9}));");
+    re.replace_all(text, |caps: &regex::Captures| {
+        let decoded = if let Some(dec) = caps.get(1) {
+            dec.as_str().parse().ok().and_then(char::from_u32)
+        } else if let Some(hex) = caps.get(2) {
+            u32::from_str_radix(hex.as_str(), 16).ok().and_then(char::from_u32)
+        } else {
+            named_entity(&caps[3])
+        };
+        decoded.map_or_else(|| caps[0].to_string(), String::from)
+    })
+    .into_owned()
+}
+
+/// Look up the common named character references
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{A0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201C}',
+        "rdquo" => '\u{201D}',
+        "bull" => '•',
+        "middot" => '·',
+        "deg" => '°',
+        "times" => '×',
+        "divide" => '÷',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "sect" => '§',
+        "para" => '¶',
+        _ => return None,
+    })
+}
+
+/// Escape text for use in HTML content or a quoted attribute
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Turn plain text into HTML that shows the same, keeping line breaks and runs of spaces
+pub fn from_text(text: &str) -> String {
+    let spaces = regex!(r"^ +| {2,}");
+    text.lines()
+        .map(|line| {
+            let line = escape(line);
+            // Browsers collapse spaces, but not non-breaking ones
+            spaces.replace_all(&line, |caps: &regex::Captures| {
+                let run = caps[0].len();
+                if run == 1 { "&nbsp;".to_string() } else { format!("{} ", "&nbsp;".repeat(run - 1)) }
+            })
+            .into_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("<br>\n")
+}
+
+/// Keep only allowed tags, and only their safe attributes (links, image
+/// sources, table spans). Scripts, styles and event handlers are always removed.
+pub fn sanitize(html: &str, allowed_tags: &[String]) -> String {
+    let allowed = |name: &str| allowed_tags.iter().any(|tag| tag.eq_ignore_ascii_case(name));
+    let mut out = String::with_capacity(html.len());
+    let mut dropped_depth = 0usize;
+    
+    for token in tokenize(html) {
+        match token {
+            Token::Text(text) => {
+                if dropped_depth == 0 {
+                    out.push_str(&escape(&decode_entities(text)));
+                }
+            }
+            Token::Start { name, attrs, self_closing } => {
+                if DROPPED_ELEMENTS.contains(&name.as_str()) {
+                    if !self_closing {
+                        dropped_depth += 1;
+                    }
+                    continue;
+                }
+                if dropped_depth > 0 || !allowed(&name) {
+                    continue;
+                }
+                
+                out.push('<');
+                out.push_str(&name);
+                for (attr, value) in attrs.iter().filter(|(attr, value)| is_safe_attribute(&name, attr, value)) {
+                    out.push_str(&format!(" {}=\"{}\"", attr, escape(value)));
+                }
+                out.push('>');
+            }
+            Token::End { name } => {
+                if DROPPED_ELEMENTS.contains(&name.as_str()) {
+                    dropped_depth = dropped_depth.saturating_sub(1);
+                } else if dropped_depth == 0 && allowed(&name) && !VOID_ELEMENTS.contains(&name.as_str()) {
+                    out.push_str(&format!("</{}>", name));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Whether an attribute may be kept on a sanitized element
+fn is_safe_attribute(element: &str, attr: &str, value: &str) -> bool {
+    match (element, attr) {
+        ("a", "href") | ("img", "src") => is_safe_url(value),
+        ("a", "title") | ("img", "alt") | ("img", "title") => true,
+        ("td" | "th", "colspan" | "rowspan") => value.chars().all(|c| c.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+/// Whether a URL can't run script when followed
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    match url.split_once(':') {
+        // A colon after a slash, `?` or `#` is part of a relative URL
+        Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => {
+            matches!(scheme, "http" | "https" | "mailto" | "tel" | "ftp")
+        }
+        _ => true,
+    }
+}
+
+/// Convert HTML to Markdown
+pub fn to_markdown(html: &str) -> String {
+    Renderer::new(true, true).render(html)
+}
+
+/// Convert HTML to plain text, keeping line structure. With `keep_links`,
+/// link targets follow the link text in parentheses.
+pub fn to_plain_text(html: &str, keep_links: bool) -> String {
+    Renderer::new(false, keep_links).render(html)
+}
+
+/// Turns HTML into Markdown or plain text
+struct Renderer {
+    /// Whether to write Markdown rather than plain text
+    markdown: bool,
+    /// Whether to write link targets
+    links: bool,
+    /// Output, with one buffer per open list item or quote, innermost last
+    buffers: Vec<String>,
+    /// Open lists, with the next number for ordered ones
+    lists: Vec<Option<usize>>,
+    /// Targets of open links, if written
+    open_links: Vec<Option<String>>,
+    /// Depth of open `pre` elements
+    pre_depth: usize,
+    /// Depth of open dropped elements
+    dropped_depth: usize,
+    /// Cells in the current table row
+    row_cells: usize,
+    /// Whether the current table row has header cells
+    header_row: bool,
+    /// Whether the current table already has its Markdown header separator
+    table_has_header: bool,
+}
+
+impl Renderer {
+    fn new(markdown: bool, links: bool) -> Self {
+        Self {
+            markdown,
+            links,
+            buffers: vec![String::new()],
+            lists: Vec::new(),
+            open_links: Vec::new(),
+            pre_depth: 0,
+            dropped_depth: 0,
+            row_cells: 0,
+            header_row: false,
+            table_has_header: false,
+        }
+    }
+    
+    fn render(mut self, html: &str) -> String {
+        for token in tokenize(html) {
+            match token {
+                Token::Text(text) if self.dropped_depth == 0 => self.text(&decode_entities(text)),
+                Token::Text(_) => {}
+                Token::Start { name, attrs, self_closing } => {
+                    if DROPPED_ELEMENTS.contains(&name.as_str()) {
+                        if !self_closing {
+                            self.dropped_depth += 1;
+                        }
+                    } else if self.dropped_depth == 0 {
+                        self.start(&name, &attrs);
+                        if self_closing && !VOID_ELEMENTS.contains(&name.as_str()) {
+                            self.end(&name);
+                        }
+                    }
+                }
+                Token::End { name } => {
+                    if DROPPED_ELEMENTS.contains(&name.as_str()) {
+                        self.dropped_depth = self.dropped_depth.saturating_sub(1);
+                    } else if self.dropped_depth == 0 {
+                        self.end(&name);
+                    }
+                }
+            }
+        }
+        
+        // Close list items and quotes left open
+        while self.buffers.len() > 1 {
+            let content = self.buffers.pop().unwrap();
+            self.push(&content);
+        }
+        
+        let out = self.buffers.pop().unwrap_or_default();
+        let out = regex!(r"\n{3,}").replace_all(&out, "\n\n");
+        out.trim_matches('\n').trim_end().to_string()
+    }
+    
+    /// The innermost output buffer
+    fn out(&mut self) -> &mut String {
+        self.buffers.last_mut().unwrap()
+    }
+    
+    fn push(&mut self, text: &str) {
+        self.out().push_str(text);
+    }
+    
+    /// Write text content, collapsing whitespace outside `pre`
+    fn text(&mut self, text: &str) {
+        if self.pre_depth > 0 {
+            self.push(&text.replace('\u{A0}', " "));
+            return;
+        }
+        
+        // Non-breaking spaces are kept, as spaces
+        let collapsed = regex!(r"[ \t\r\n\x0C]+").replace_all(text, " ");
+        let at_line_start = self.out().is_empty() || self.out().ends_with(['\n', ' ']);
+        let collapsed = if at_line_start { collapsed.trim_start_matches(' ') } else { &collapsed };
+        let collapsed = collapsed.replace('\u{A0}', " ");
+        self.push(&collapsed);
+    }
+    
+    /// End the current line, if anything is on it
+    fn line_break(&mut self) {
+        let out = self.out();
+        let trimmed_len = out.trim_end_matches(' ').len();
+        out.truncate(trimmed_len);
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    
+    /// Leave a blank line before what follows
+    fn block_break(&mut self) {
+        self.line_break();
+        let out = self.out();
+        if !out.is_empty() && !out.ends_with("\n\n") {
+            out.push('\n');
+        }
+    }
+    
+    /// Write an inline Markdown marker
+    fn marker(&mut self, marker: &str) {
+        if self.markdown && self.pre_depth == 0 {
+            self.push(marker);
+        }
+    }
+    
+    /// Write a closing inline Markdown marker, keeping trailing space outside it
+    fn closing_marker(&mut self, marker: &str) {
+        if !self.markdown || self.pre_depth > 0 {
+            return;
+        }
+        let out = self.out();
+        let space = out.ends_with(' ');
+        if space {
+            out.pop();
+        }
+        out.push_str(marker);
+        if space {
+            out.push(' ');
+        }
+    }
+    
+    fn start(&mut self, name: &str, attrs: &[(String, String)]) {
+        let attr = |wanted: &str| attrs.iter().find(|(attr, _)| attr == wanted).map(|(_, value)| value.as_str());
+        
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.block_break();
+                if self.markdown {
+                    let level = name[1..].parse().unwrap_or(1);
+                    self.push(&format!("{} ", "#".repeat(level)));
+                }
+            }
+            "p" => self.block_break(),
+            "br" => {
+                if self.markdown && self.pre_depth == 0 {
+                    self.push("  \n");
+                } else {
+                    self.push("\n");
+                }
+            }
+            "hr" => {
+                self.block_break();
+                if self.markdown {
+                    self.push("---");
+                }
+                self.block_break();
+            }
+            "b" | "strong" => self.marker("**"),
+            "i" | "em" => self.marker("_"),
+            "s" | "del" | "strike" => self.marker("~~"),
+            "code" => self.marker("`"),
+            "pre" => {
+                self.block_break();
+                if self.markdown && self.pre_depth == 0 {
+                    self.push("```\n");
+                }
+                self.pre_depth += 1;
+            }
+            "a" => {
+                let href = attr("href")
+                    .filter(|href| self.links && !href.is_empty() && !href.starts_with('#'))
+                    .map(String::from);
+                if href.is_some() && self.markdown {
+                    self.push("[");
+                }
+                self.open_links.push(href);
+            }
+            "img" => {
+                let alt = attr("alt").unwrap_or("").to_string();
+                match attr("src") {
+                    Some(src) if self.markdown => self.push(&format!("![{}]({})", alt, src)),
+                    _ => self.push(&alt),
+                }
+            }
+            "ul" | "ol" => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else {
+                    self.line_break();
+                }
+                self.lists.push((name == "ol").then_some(1));
+            }
+            "li" | "blockquote" => self.buffers.push(String::new()),
+            "tr" => {
+                self.line_break();
+                if self.markdown {
+                    self.push("|");
+                }
+                self.row_cells = 0;
+                self.header_row = false;
+            }
+            "th" | "td" => {
+                if name == "th" {
+                    self.header_row = true;
+                }
+                if self.markdown {
+                    self.push(" ");
+                } else if self.row_cells > 0 {
+                    self.push("\t");
+                }
+            }
+            "table" => {
+                self.block_break();
+                self.table_has_header = false;
+            }
+            _ if BLOCK_ELEMENTS.contains(&name) => self.line_break(),
+            _ => {}
+        }
+    }
+    
+    fn end(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "table" => self.block_break(),
+            "b" | "strong" => self.closing_marker("**"),
+            "i" | "em" => self.closing_marker("_"),
+            "s" | "del" | "strike" => self.closing_marker("~~"),
+            "code" => self.closing_marker("`"),
+            "pre" => {
+                self.pre_depth = self.pre_depth.saturating_sub(1);
+                self.line_break();
+                if self.markdown && self.pre_depth == 0 {
+                    self.push("```");
+                }
+                self.block_break();
+            }
+            "a" => {
+                if let Some(Some(href)) = self.open_links.pop() {
+                    if self.markdown {
+                        self.push(&format!("]({})", href));
+                    } else {
+                        let out = self.out();
+                        // Don't repeat a link target that is already the text
+                        let text_is_href = out.trim_end().ends_with(href.trim_start_matches("mailto:"));
+                        if !text_is_href {
+                            out.push_str(&format!(" ({})", href));
+                        }
+                    }
+                }
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else {
+                    self.line_break();
+                }
+            }
+            "li" => self.end_list_item(),
+            "blockquote" => self.end_quote(),
+            "th" | "td" => {
+                self.row_cells += 1;
+                if self.markdown {
+                    let out = self.out();
+                    let trimmed_len = out.trim_end_matches(' ').len();
+                    out.truncate(trimmed_len);
+                    out.push_str(" |");
+                }
+            }
+            "tr" => {
+                if self.markdown && self.header_row && !self.table_has_header {
+                    self.line_break();
+                    let separator = format!("|{}", " --- |".repeat(self.row_cells.max(1)));
+                    self.push(&separator);
+                    self.table_has_header = true;
+                }
+                self.line_break();
+            }
+            _ if BLOCK_ELEMENTS.contains(&name) => self.line_break(),
+            _ => {}
+        }
+    }
+    
+    /// Write a finished list item, with its marker and continuation lines indented
+    fn end_list_item(&mut self) {
+        if self.buffers.len() < 2 {
+            return;
+        }
+        let content = self.buffers.pop().unwrap();
+        let marker = match self.lists.last_mut() {
+            Some(Some(number)) => {
+                *number += 1;
+                format!("{}. ", *number - 1)
+            }
+            _ => "- ".to_string(),
+        };
+        let indent = " ".repeat(marker.len());
+        
+        let content = content.trim();
+        let mut item = String::new();
+        for (i, line) in content.lines().enumerate() {
+            if i == 0 {
+                item.push_str(&marker);
+            } else {
+                item.push('\n');
+                if !line.is_empty() {
+                    item.push_str(&indent);
+                }
+            }
+            item.push_str(line);
+        }
+        if content.is_empty() {
+            item.push_str(marker.trim_end());
+        }
+        
+        self.line_break();
+        self.push(&item);
+        self.line_break();
+    }
+    
+    /// Write a finished quote, prefixed with `> ` in Markdown
+    fn end_quote(&mut self) {
+        if self.buffers.len() < 2 {
+            return;
+        }
+        let content = self.buffers.pop().unwrap();
+        let content = content.trim();
+        let quote = if self.markdown {
+            content.lines()
+                .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            content.to_string()
+        };
+        
+        self.block_break();
+        self.push(&quote);
+        self.block_break();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_sanitize() {
+        let allowed: Vec<String> = DEFAULT_ALLOWED_TAGS.iter().map(|tag| tag.to_string()).collect();
+        let html = concat!(
+            r#"<div class="post" style="color:red"><p onclick="steal()">Hi <b>there</b> &amp; "you"</p>"#,
+            r#"<script>alert("<p>x</p>")</script><a href="javascript:alert(1)">bad</a> "#,
+            r#"<a href="https://example.com/?a=1&amp;b=2" target="_blank">good</a><br/><img src=x onerror=alert(1)></div>"#,
+        );
+        assert_eq!(
+            sanitize(html, &allowed),
+            r#"<p>Hi <b>there</b> &amp; &quot;you&quot;</p><a>bad</a> <a href="https://example.com/?a=1&amp;b=2">good</a><br>"#
+        );
+        
+        // Allowing fewer tags keeps only their text
+        assert_eq!(sanitize("<p>Some <em>text</em></p>", &["em".to_string()]), "Some <em>text</em>");
+    }
+    
+    #[test]
+    fn test_to_markdown() {
+        let html = concat!(
+            "<h2>Title</h2><p>Some <strong>bold </strong>and <em>italic</em> text with <code>code</code>.</p>",
+            "<ul><li>One</li><li>Two<ol><li>Nested</li></ol></li></ul>",
+            r#"<p>See <a href="https://example.com">the site</a>.</p>"#,
+            "<blockquote><p>Quoted</p><p>twice</p></blockquote>",
+            "<pre><code>fn main() {\n    run();\n}</code></pre>",
+            "<table><tr><th>Name</th><th>Age</th></tr><tr><td>Bob</td><td>42</td></tr></table>",
+        );
+        assert_eq!(to_markdown(html), concat!(
+            "## Title\n\n",
+            "Some **bold** and _italic_ text with `code`.\n\n",
+            "- One\n- Two\n  1. Nested\n\n",
+            "See [the site](https://example.com).\n\n",
+            "> Quoted\n>\n> twice\n\n",
+            "```\nfn main() {\n    run();\n}\n```\n\n",
+            "| Name | Age |\n| --- | --- |\n| Bob | 42 |",
+        ));
+    }
+    
+    #[test]
+    fn test_to_plain_text() {
+        let html = concat!(
+            "<html><head><style>p { color: red }</style></head><body>",
+            "<p>Read   the\n<a href=\"https://example.com/docs\">docs</a> or mail ",
+            "<a href=\"mailto:bob@example.com\">bob@example.com</a>.</p>",
+            "<ul><li>First</li><li>Second</li></ul><p>Caf&#233; &mdash; 5&nbsp;&lt;&nbsp;6</p>",
+            "</body></html>",
+        );
+        assert_eq!(
+            to_plain_text(html, true),
+            "Read the docs (https://example.com/docs) or mail bob@example.com.\n\n- First\n- Second\n\nCafé — 5 < 6"
+        );
+        assert!(to_plain_text(html, false).starts_with("Read the docs or mail"));
+    }
+    
+    #[test]
+    fn test_from_text() {
+        let text = "a < b\n  indented   twice";
+        let html = from_text(text);
+        assert_eq!(html, "a &lt; b<br>\n&nbsp; indented&nbsp;&nbsp; twice");
+        assert_eq!(to_plain_text(&html, true), text);
+    }
+}
@@ -0,0 +1,305 @@
+//! Clipboard images
+//!
+//! Images are kept encoded, in the format they were copied in, so steps can
+//! strip metadata without re-encoding and the result can be saved as is.
+//! Steps that change pixels decode the image and encode it again in the same
+//! format, which drops its metadata too.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+use anyhow::{Result, Context};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+/// Quality of JPEG images steps encode, from 1 to 100
+const JPEG_QUALITY: u8 = 90;
+
+/// PNG chunks holding text, EXIF data or timestamps
+const PNG_METADATA_CHUNKS: [&[u8; 4]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"tIME"];
+
+/// Formats images are read and written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Bmp,
+}
+
+impl ImageFormat {
+    /// All supported formats
+    pub const ALL: [ImageFormat; 3] = [Self::Png, Self::Jpeg, Self::Bmp];
+    
+    /// Get a human-readable name for the format
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Png => "PNG",
+            Self::Jpeg => "JPEG",
+            Self::Bmp => "BMP",
+        }
+    }
+    
+    /// MIME type the format is offered as on the clipboard
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Bmp => "image/bmp",
+        }
+    }
+    
+    /// Recognize an encoded image by its first bytes
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(Self::Png),
+            [0xFF, 0xD8, 0xFF, ..] => Some(Self::Jpeg),
+            [b'B', b'M', ..] => Some(Self::Bmp),
+            _ => None,
+        }
+    }
+    
+    fn codec(&self) -> image::ImageFormat {
+        match self {
+            Self::Png => image::ImageFormat::Png,
+            Self::Jpeg => image::ImageFormat::Jpeg,
+            Self::Bmp => image::ImageFormat::Bmp,
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.display_name())
+    }
+}
+
+/// An encoded image from the clipboard
+#[derive(Clone, PartialEq, Eq)]
+pub struct ClipboardImage {
+    /// Format the image is encoded in
+    pub format: ImageFormat,
+    /// The encoded image
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Debug for ClipboardImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClipboardImage")
+            .field("format", &self.format)
+            .field("bytes", &self.bytes.len())
+            .finish()
+    }
+}
+
+impl ClipboardImage {
+    /// Create a new ClipboardImage
+    pub fn new(format: ImageFormat, bytes: Vec<u8>) -> Self {
+        Self { format, bytes }
+    }
+    
+    /// Encode RGBA pixels, as clipboards hand them over, as a PNG
+    pub fn from_rgba(width: u32, height: u32, rgba: Vec<u8>) -> Result<Self> {
+        let pixels = image::RgbaImage::from_raw(width, height, rgba)
+            .context("Image data doesn't match its size")?;
+        Self::encode(&DynamicImage::ImageRgba8(pixels), ImageFormat::Png)
+    }
+    
+    /// Decode the image into its width, height and RGBA pixels
+    pub fn to_rgba(&self) -> Result<(u32, u32, Vec<u8>)> {
+        let pixels = self.decode()?.into_rgba8();
+        Ok((pixels.width(), pixels.height(), pixels.into_raw()))
+    }
+    
+    /// Width and height of the image
+    pub fn dimensions(&self) -> Result<(u32, u32)> {
+        let reader = image::ImageReader::with_format(Cursor::new(&self.bytes), self.format.codec());
+        reader.into_dimensions()
+            .context(format!("Failed to read {} image", self.format))
+    }
+    
+    /// A hash of the encoded image, to tell images apart cheaply
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+    
+    fn decode(&self) -> Result<DynamicImage> {
+        image::load_from_memory_with_format(&self.bytes, self.format.codec())
+            .context(format!("Failed to decode {} image", self.format))
+    }
+    
+    fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Self> {
+        let mut bytes = Vec::new();
+        let result = match format {
+            ImageFormat::Jpeg => {
+                // JPEG has no alpha channel
+                let image = match image {
+                    DynamicImage::ImageLuma8(_) | DynamicImage::ImageRgb8(_) => image.clone(),
+                    DynamicImage::ImageLumaA8(_) => DynamicImage::ImageLuma8(image.to_luma8()),
+                    _ => DynamicImage::ImageRgb8(image.to_rgb8()),
+                };
+                let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY);
+                image.write_with_encoder(encoder)
+            }
+            _ => image.write_to(&mut Cursor::new(&mut bytes), format.codec()),
+        };
+        result.context(format!("Failed to encode {} image", format))?;
+        Ok(Self::new(format, bytes))
+    }
+}
+
+/// Scale an image down so neither side is longer than `max_dimension`,
+/// keeping its aspect ratio. Smaller images are left alone.
+pub fn resize(image: &ClipboardImage, max_dimension: u32) -> Result<ClipboardImage> {
+    let (width, height) = image.dimensions()?;
+    if width <= max_dimension && height <= max_dimension {
+        return Ok(image.clone());
+    }
+    
+    let resized = image.decode()?.resize(max_dimension, max_dimension, image::imageops::FilterType::Lanczos3);
+    ClipboardImage::encode(&resized, image.format)
+}
+
+/// Re-encode an image in another format
+pub fn convert(image: &ClipboardImage, format: ImageFormat) -> Result<ClipboardImage> {
+    if image.format == format {
+        return Ok(image.clone());
+    }
+    ClipboardImage::encode(&image.decode()?, format)
+}
+
+/// Convert an image to shades of gray, keeping transparency
+pub fn grayscale(image: &ClipboardImage) -> Result<ClipboardImage> {
+    ClipboardImage::encode(&image.decode()?.grayscale(), image.format)
+}
+
+/// Remove EXIF data, text comments and similar metadata without re-encoding
+/// the pixels. Colour profiles are kept, since they change how the image looks.
+pub fn strip_metadata(image: &ClipboardImage) -> Result<ClipboardImage> {
+    let bytes = match image.format {
+        ImageFormat::Png => strip_png(&image.bytes),
+        ImageFormat::Jpeg => strip_jpeg(&image.bytes),
+        // BMP has nowhere to keep metadata
+        ImageFormat::Bmp => Some(image.bytes.clone()),
+    };
+    let bytes = bytes.context(format!("Malformed {} image", image.format))?;
+    Ok(ClipboardImage::new(image.format, bytes))
+}
+
+/// Copy a PNG without its metadata chunks, or None if it is malformed
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = bytes.get(..8)?.to_vec();
+    let mut rest = &bytes[8..];
+    
+    // Each chunk is a length, a type, the data and a CRC
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let chunk = rest.get(..length.checked_add(12)?)?;
+        if !PNG_METADATA_CHUNKS.iter().any(|name| &chunk[4..8] == *name) {
+            out.extend_from_slice(chunk);
+        }
+        rest = &rest[chunk.len()..];
+    }
+    Some(out)
+}
+
+/// Copy a JPEG without its EXIF, XMP, IPTC and comment segments, or None if
+/// it is malformed. The JFIF header, ICC profile and Adobe colour transform are kept.
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = bytes.get(..2)?.to_vec();
+    let mut pos = 2;
+    
+    loop {
+        if *bytes.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(pos + 1)?;
+        match marker {
+            // Fill bytes before a marker
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                out.extend_from_slice(&bytes[pos..pos + 2]);
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+        
+        let length = u16::from_be_bytes([*bytes.get(pos + 2)?, *bytes.get(pos + 3)?]) as usize;
+        let segment = bytes.get(pos..pos + 2 + length)?;
+        
+        // Start of scan: the compressed image follows up to the end
+        if marker == 0xDA {
+            out.extend_from_slice(&bytes[pos..]);
+            return Some(out);
+        }
+        
+        let metadata = matches!(marker, 0xE1 | 0xE3..=0xED | 0xEF | 0xFE);
+        if !metadata {
+            out.extend_from_slice(segment);
+        }
+        pos += segment.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn checkerboard() -> ClipboardImage {
+        let rgba = (0..40 * 20)
+            .flat_map(|i| if (i % 40 + i / 40) % 2 == 0 { [255, 0, 0, 255] } else { [0, 0, 255, 128] })
+            .collect();
+        ClipboardImage::from_rgba(40, 20, rgba).unwrap()
+    }
+    
+    #[test]
+    fn test_resize_and_convert() {
+        let image = checkerboard();
+        assert_eq!(image.format, ImageFormat::Png);
+        
+        let resized = resize(&image, 10).unwrap();
+        assert_eq!(resized.dimensions().unwrap(), (10, 5));
+        assert_eq!(resize(&image, 100).unwrap(), image);
+        
+        let jpeg = convert(&image, ImageFormat::Jpeg).unwrap();
+        assert_eq!(ImageFormat::detect(&jpeg.bytes), Some(ImageFormat::Jpeg));
+        assert_eq!(jpeg.dimensions().unwrap(), (40, 20));
+        
+        let (_, _, gray) = grayscale(&image).unwrap().to_rgba().unwrap();
+        assert!(gray.chunks(4).all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]));
+        assert!(gray.chunks(4).any(|pixel| pixel[3] == 128));
+    }
+    
+    #[test]
+    fn test_strip_metadata() {
+        // A PNG with a comment after the header
+        let png = checkerboard();
+        let mut bytes = png.bytes[..33].to_vec();
+        bytes.extend_from_slice(&[0, 0, 0, 7]);
+        bytes.extend_from_slice(b"tEXtKey\0abc");
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&png.bytes[33..]);
+        let stripped = strip_metadata(&ClipboardImage::new(ImageFormat::Png, bytes)).unwrap();
+        assert_eq!(stripped, png);
+        
+        // A JPEG with an EXIF segment after the JFIF header
+        let jpeg = convert(&png, ImageFormat::Jpeg).unwrap();
+        let header_len = 4 + u16::from_be_bytes([jpeg.bytes[4], jpeg.bytes[5]]) as usize;
+        let mut bytes = jpeg.bytes[..header_len].to_vec();
+        bytes.extend_from_slice(&[0xFF, 0xE1, 0, 10]);
+        bytes.extend_from_slice(b"Exif\0\0MM");
+        bytes.extend_from_slice(&jpeg.bytes[header_len..]);
+        let stripped = strip_metadata(&ClipboardImage::new(ImageFormat::Jpeg, bytes)).unwrap();
+        assert_eq!(stripped, jpeg);
+        
+        assert!(strip_metadata(&ClipboardImage::new(ImageFormat::Jpeg, vec![0xFF, 0xD8, 0x00])).is_err());
+    }
+}
@@ -1,87 +1,359 @@
 //! IPC (Inter-Process Communication) module
 //!
-//! Provides communication between the dashboard and background service
-//! using a simple TCP socket on localhost.
+//! Provides communication between the dashboard, CLI scripts and the
+//! background service. On Unix this uses a domain socket that only the
+//! owning user can access; elsewhere it falls back to a TCP socket on
+//! localhost.
+//!
+//! The protocol is newline-delimited JSON. Every connection starts with a
+//! `hello` handshake carrying the protocol version, after which the client
+//! may send any number of commands, each answered by exactly one response:
+//!
+//! ```text
+//! > {"command":"hello","version":1}
+//! < {"response":"hello","version":1}
+//! > {"command":"get_status"}
+//! < {"response":"status","version":"0.1.6","transform_enabled":true,...}
+//! ```
 
-use std::io::{Read, Write};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(not(unix))]
 use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use anyhow::{Result, Context, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, info};
+use uuid::Uuid;
 
-/// Default port for IPC communication
+use crate::backend::{ClipboardContent, Selection};
+use crate::history::HistoryEntry;
+#[cfg(unix)]
+use crate::config::Config;
+
+/// Default port for IPC communication (platforms without Unix sockets)
 pub const IPC_PORT: u16 = 9549;
 
+/// File name of the IPC socket (Unix)
+#[cfg(unix)]
+const SOCKET_NAME: &str = "ipc.sock";
+
+/// Version of the IPC protocol, checked during the handshake
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Timeout for reading and writing IPC messages
+const IPC_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the server keeps an idle client connection open
+const IPC_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Commands that can be sent via IPC
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
 pub enum IpcCommand {
+    /// Handshake, must be the first message on a connection
+    Hello { version: u32 },
     /// Reload the active recipe from disk
     ReloadRecipe,
     /// Toggle transformation
     ToggleTransformation,
     /// Ping to check if service is running
     Ping,
+    /// Apply a recipe to the current clipboard once
+    ApplyRecipe { id: Uuid },
+    /// Set the active recipe, or deactivate all recipes with `None`
+    SetActiveRecipe { id: Option<Uuid> },
+    /// Get the current service status
+    GetStatus,
+    /// Get the most recent history entries
+    GetHistory {
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Transform text with a recipe (name or ID) without touching the clipboard
+    Transform { text: String, recipe: String },
+    /// Restore the clipboard from before the last transformation
+    Undo,
+    /// Reopen the encrypted history, with a passphrase or the configured key
+    UnlockHistory {
+        #[serde(default)]
+        passphrase: Option<Secret>,
+    },
+    /// Set a selection and keep serving it, for processes about to exit
+    SetClipboard {
+        text: String,
+        #[serde(default)]
+        html: Option<String>,
+        #[serde(default)]
+        selection: Selection,
+    },
+}
+
+impl IpcCommand {
+    /// Name of the command as sent over the wire. Logs use this rather than
+    /// the command itself, which may carry clipboard content.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Hello { .. } => "hello",
+            Self::ReloadRecipe => "reload_recipe",
+            Self::ToggleTransformation => "toggle_transformation",
+            Self::Ping => "ping",
+            Self::ApplyRecipe { .. } => "apply_recipe",
+            Self::SetActiveRecipe { .. } => "set_active_recipe",
+            Self::GetStatus => "get_status",
+            Self::GetHistory { .. } => "get_history",
+            Self::Transform { .. } => "transform",
+            Self::Undo => "undo",
+            Self::UnlockHistory { .. } => "unlock_history",
+            Self::SetClipboard { .. } => "set_clipboard",
+        }
+    }
+}
+
+/// A string that is kept out of logs
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(pub String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+/// Responses sent back by the background service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum IpcResponse {
+    /// Handshake accepted
+    Hello { version: u32 },
+    /// Command completed
+    Ok,
+    /// Reply to a ping
+    Pong,
+    /// Current service status
+    Status(ServiceStatus),
+    /// History entries, newest first
+    History { entries: Vec<HistoryEntry> },
+    /// Result of a transformation
+    Transformed { text: String },
+    /// Command failed
+    Error { message: String },
+}
+
+/// Status of the background service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    /// Version of the running service
+    pub version: String,
+    /// Whether automatic transformation is enabled
+    pub transform_enabled: bool,
+    /// ID of the active recipe
+    pub active_recipe_id: Option<Uuid>,
+    /// Name of the active recipe
+    pub active_recipe_name: Option<String>,
+    /// Whether history is being recorded
+    pub history_enabled: bool,
+}
+
+/// A command received by the server, together with the channel for its response
+#[derive(Debug)]
+pub struct IpcRequest {
+    /// The command to handle
+    pub command: IpcCommand,
+    reply: oneshot::Sender<IpcResponse>,
+}
+
+impl IpcRequest {
+    /// Send the response back to the client
+    pub fn respond(self, response: IpcResponse) {
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Get the path of the IPC socket
+///
+/// Lives in `$XDG_RUNTIME_DIR/9paste/`, or the data directory where there is
+/// no runtime directory (e.g. macOS). Either directory is made owner-only
+/// (0700), so the socket is private from the moment it is bound.
+#[cfg(unix)]
+pub fn socket_path() -> Result<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    
+    let dir = match dirs::runtime_dir() {
+        Some(dir) => dir.join("9paste"),
+        None => Config::data_dir()?,
+    };
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .context("Failed to create socket directory")?;
+    // The mode only applies to new directories
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+        .context("Failed to set socket directory permissions")?;
+    
+    Ok(dir.join(SOCKET_NAME))
+}
+
+/// Stream types the IPC protocol can run over
+trait IpcStream: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> std::io::Result<Self>;
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()>;
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+}
+
+macro_rules! impl_ipc_stream {
+    ($stream:ty) => {
+        impl IpcStream for $stream {
+            fn try_clone(&self) -> std::io::Result<Self> {
+                <$stream>::try_clone(self)
+            }
+            fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+                <$stream>::set_nonblocking(self, nonblocking)
+            }
+            fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+                <$stream>::set_read_timeout(self, timeout)
+            }
+            fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+                <$stream>::set_write_timeout(self, timeout)
+            }
+        }
+    };
+}
+
+#[cfg(unix)]
+impl_ipc_stream!(UnixStream);
+#[cfg(not(unix))]
+impl_ipc_stream!(TcpStream);
+
+/// Bind the IPC socket, owner-only
+#[cfg(unix)]
+fn bind() -> Result<UnixListener> {
+    use std::os::unix::fs::PermissionsExt;
+    
+    let path = socket_path()?;
+    
+    if path.exists() {
+        // A live service still owns the socket
+        if UnixStream::connect(&path).is_ok() {
+            bail!("Another instance is listening on {}", path.display());
+        }
+        // Otherwise it was left behind by a crashed service
+        std::fs::remove_file(&path)
+            .context("Failed to remove stale IPC socket")?;
+    }
+    
+    // The directory already keeps others out; restrict the socket too in case
+    // the directory is opened up later
+    let listener = UnixListener::bind(&path)
+        .context("Failed to bind IPC socket")?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .context("Failed to set IPC socket permissions")?;
+    
+    info!("IPC server listening on {}", path.display());
+    Ok(listener)
+}
+
+/// Bind the IPC port on localhost
+#[cfg(not(unix))]
+fn bind() -> Result<TcpListener> {
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", IPC_PORT))
+        .context(format!("Failed to bind port {}", IPC_PORT))?;
+    
+    info!("IPC server listening on port {}", IPC_PORT);
+    Ok(listener)
+}
+
+/// Connect to the background service
+#[cfg(unix)]
+fn connect() -> Result<UnixStream> {
+    UnixStream::connect(socket_path()?)
+        .context("Background service is not running")
+}
+
+/// Connect to the background service
+#[cfg(not(unix))]
+fn connect() -> Result<TcpStream> {
+    TcpStream::connect(format!("127.0.0.1:{}", IPC_PORT))
+        .context("Background service is not running")
+}
+
+/// Write a message as a single JSON line
+fn write_message<T: Serialize>(stream: &mut impl Write, message: &T) -> Result<()> {
+    let mut line = serde_json::to_string(message)
+        .context("Failed to serialize IPC message")?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+        .context("Failed to write IPC message")?;
+    stream.flush()
+        .context("Failed to flush IPC message")
+}
+
+/// Read a single JSON line
+fn read_message<T: for<'de> Deserialize<'de>>(reader: &mut impl BufRead) -> Result<T> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).context("Failed to read IPC message")? == 0 {
+        bail!("Connection closed");
+    }
+    serde_json::from_str(&line)
+        .context("Failed to parse IPC message")
 }
 
 /// IPC Server - runs in the background service
 pub struct IpcServer {
     running: Arc<AtomicBool>,
+    /// Whether this server owns the socket
+    bound: AtomicBool,
 }
 
 impl IpcServer {
     pub fn new() -> Self {
         Self {
             running: Arc::new(AtomicBool::new(false)),
+            bound: AtomicBool::new(false),
         }
     }
     
-    /// Start the IPC server, returns a receiver for commands
-    pub fn start(&self) -> Option<mpsc::Receiver<IpcCommand>> {
+    /// Start the IPC server, returns a receiver for requests
+    pub fn start(&self) -> Option<mpsc::Receiver<IpcRequest>> {
         let (tx, rx) = mpsc::channel(32);
         let running = Arc::clone(&self.running);
         running.store(true, Ordering::SeqCst);
         
-        // Try to bind to the port
-        let listener = match TcpListener::bind(format!("127.0.0.1:{}", IPC_PORT)) {
+        // Try to bind the socket
+        let listener = match bind() {
             Ok(l) => {
                 // Set non-blocking so we can check the running flag
                 l.set_nonblocking(true).ok();
                 l
             }
             Err(e) => {
-                debug!("Could not start IPC server on port {}: {}", IPC_PORT, e);
+                debug!("Could not start IPC server: {:#}", e);
                 return None;
             }
         };
-        
-        info!("IPC server listening on port {}", IPC_PORT);
+        self.bound.store(true, Ordering::SeqCst);
         
         std::thread::spawn(move || {
             while running.load(Ordering::SeqCst) {
                 match listener.accept() {
-                    Ok((mut stream, _addr)) => {
-                        // Read command
-                        let mut buf = [0u8; 32];
-                        if let Ok(n) = stream.read(&mut buf) {
-                            if n > 0 {
-                                let cmd_str = String::from_utf8_lossy(&buf[..n]);
-                                let command = match cmd_str.trim() {
-                                    "RELOAD" => Some(IpcCommand::ReloadRecipe),
-                                    "TRANSFORM" => Some(IpcCommand::ToggleTransformation),
-                                    "PING" => {
-                                        // Respond to ping
-                                        let _ = stream.write_all(b"PONG");
-                                        Some(IpcCommand::Ping)
-                                    }
-                                    _ => None,
-                                };
-                                
-                                if let Some(cmd) = command {
-                                    let _ = tx.blocking_send(cmd);
-                                }
+                    Ok((stream, _addr)) => {
+                        // Handle each client on its own thread so a slow one can't stall the rest
+                        let tx = tx.clone();
+                        std::thread::spawn(move || {
+                            if let Err(e) = Self::handle_connection(stream, tx) {
+                                debug!("IPC connection error: {}", e);
                             }
-                        }
+                        });
                     }
                     Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                         // No connection waiting, sleep a bit
@@ -98,8 +370,71 @@ impl IpcServer {
         Some(rx)
     }
     
+    /// Serve one client connection until it disconnects
+    fn handle_connection(stream: impl IpcStream, tx: mpsc::Sender<IpcRequest>) -> Result<()> {
+        // Accepted sockets may inherit non-blocking mode from the listener
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(IPC_IDLE_TIMEOUT))?;
+        stream.set_write_timeout(Some(IPC_TIMEOUT))?;
+        
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        let mut handshake_done = false;
+        let mut line = String::new();
+        
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            
+            let response = match serde_json::from_str::<IpcCommand>(&line) {
+                Err(e) => IpcResponse::Error {
+                    message: format!("Invalid request: {}", e),
+                },
+                Ok(IpcCommand::Hello { version }) if version == PROTOCOL_VERSION => {
+                    handshake_done = true;
+                    IpcResponse::Hello { version: PROTOCOL_VERSION }
+                }
+                Ok(IpcCommand::Hello { version }) => IpcResponse::Error {
+                    message: format!(
+                        "Unsupported protocol version {} (expected {})",
+                        version, PROTOCOL_VERSION
+                    ),
+                },
+                Ok(_) if !handshake_done => IpcResponse::Error {
+                    message: "Handshake required".into(),
+                },
+                Ok(IpcCommand::Ping) => IpcResponse::Pong,
+                Ok(command) => {
+                    debug!("Received IPC command: {}", command.name());
+                    let (reply, reply_rx) = oneshot::channel();
+                    if tx.blocking_send(IpcRequest { command, reply }).is_err() {
+                        return Ok(());
+                    }
+                    reply_rx.blocking_recv().unwrap_or_else(|_| IpcResponse::Error {
+                        message: "Service did not respond".into(),
+                    })
+                }
+            };
+            
+            write_message(&mut writer, &response)?;
+        }
+    }
+    
     pub fn stop(&self) {
         self.running.store(false, Ordering::SeqCst);
+        
+        // Clean up the socket file, but never one owned by another instance
+        #[cfg(unix)]
+        if self.bound.swap(false, Ordering::SeqCst) {
+            if let Ok(path) = socket_path() {
+                let _ = std::fs::remove_file(path);
+            }
+        }
     }
 }
 
@@ -109,49 +444,105 @@ impl Default for IpcServer {
     }
 }
 
-/// IPC Client - used by the dashboard to send commands
+/// IPC Client - used by the dashboard and CLI to send commands
 pub struct IpcClient;
 
 impl IpcClient {
-    /// Send a command to the background service
-    /// Returns true if successful, false if service not running
-    pub fn send(command: IpcCommand) -> bool {
-        let cmd_str = match command {
-            IpcCommand::ReloadRecipe => "RELOAD",
-            IpcCommand::ToggleTransformation => "TRANSFORM",
-            IpcCommand::Ping => "PING",
-        };
+    /// Send a command to the background service and wait for its response
+    /// Fails if the service is not running or reports an error
+    pub fn send(command: IpcCommand) -> Result<IpcResponse> {
+        let mut stream = connect()?;
+        stream.set_read_timeout(Some(IPC_TIMEOUT)).ok();
+        stream.set_write_timeout(Some(IPC_TIMEOUT)).ok();
         
-        match TcpStream::connect(format!("127.0.0.1:{}", IPC_PORT)) {
-            Ok(mut stream) => {
-                stream.set_write_timeout(Some(std::time::Duration::from_millis(100))).ok();
-                if stream.write_all(cmd_str.as_bytes()).is_ok() {
-                    debug!("Sent IPC command: {:?}", command);
-                    true
-                } else {
-                    false
-                }
-            }
-            Err(_) => {
-                // Service not running, that's OK
-                false
-            }
+        let mut reader = BufReader::new(stream.try_clone()?);
+        
+        write_message(&mut stream, &IpcCommand::Hello { version: PROTOCOL_VERSION })?;
+        match read_message(&mut reader)? {
+            IpcResponse::Hello { .. } => {}
+            IpcResponse::Error { message } => bail!("Handshake failed: {}", message),
+            other => bail!("Unexpected handshake response: {:?}", other),
+        }
+        
+        write_message(&mut stream, &command)?;
+        debug!("Sent IPC command: {}", command.name());
+        
+        match read_message(&mut reader)? {
+            IpcResponse::Error { message } => Err(anyhow!(message)),
+            response => Ok(response),
         }
     }
     
     /// Check if the background service is running
     pub fn is_service_running() -> bool {
-        if let Ok(mut stream) = TcpStream::connect(format!("127.0.0.1:{}", IPC_PORT)) {
-            stream.set_read_timeout(Some(std::time::Duration::from_millis(100))).ok();
-            stream.set_write_timeout(Some(std::time::Duration::from_millis(100))).ok();
-            
-            if stream.write_all(b"PING").is_ok() {
-                let mut buf = [0u8; 4];
-                if stream.read(&mut buf).is_ok() {
-                    return &buf == b"PONG";
-                }
-            }
+        matches!(Self::send(IpcCommand::Ping), Ok(IpcResponse::Pong))
+    }
+    
+    /// Hand content to the background service, which keeps the selection
+    /// after this process exits. Fails if the service isn't running.
+    pub fn set_clipboard(content: &ClipboardContent, selection: Selection) -> Result<()> {
+        let command = IpcCommand::SetClipboard {
+            text: content.text.clone(),
+            html: content.html.clone(),
+            selection,
+        };
+        match Self::send(command)? {
+            IpcResponse::Ok => Ok(()),
+            other => bail!("Unexpected response: {:?}", other),
         }
-        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_command_wire_format() {
+        let json = serde_json::to_string(&IpcCommand::GetStatus).unwrap();
+        assert_eq!(json, r#"{"command":"get_status"}"#);
+        
+        let command: IpcCommand = serde_json::from_str(
+            r#"{"command":"transform","text":"Hello","recipe":"Plain Text"}"#
+        ).unwrap();
+        assert_eq!(command, IpcCommand::Transform {
+            text: "Hello".into(),
+            recipe: "Plain Text".into(),
+        });
+        
+        // Optional arguments may be omitted
+        let command: IpcCommand = serde_json::from_str(r#"{"command":"get_history"}"#).unwrap();
+        assert_eq!(command, IpcCommand::GetHistory { limit: None });
+        let command: IpcCommand = serde_json::from_str(r#"{"command":"set_clipboard","text":"Hi"}"#).unwrap();
+        assert_eq!(command, IpcCommand::SetClipboard {
+            text: "Hi".into(),
+            html: None,
+            selection: Selection::Clipboard,
+        });
+        
+        // Secrets go over the wire as plain strings, but never into logs
+        let command: IpcCommand = serde_json::from_str(
+            r#"{"command":"unlock_history","passphrase":"hunter2"}"#
+        ).unwrap();
+        assert_eq!(command, IpcCommand::UnlockHistory { passphrase: Some(Secret("hunter2".into())) });
+        assert!(!format!("{:?}", command).contains("hunter2"));
+    }
+    
+    #[test]
+    fn test_command_name() {
+        let command = IpcCommand::Transform { text: "hunter2".into(), recipe: "Plain Text".into() };
+        let json = serde_json::to_value(&command).unwrap();
+        assert_eq!(json["command"], command.name());
+        assert_eq!(command.name(), "transform");
+    }
+    
+    #[test]
+    fn test_message_roundtrip() {
+        let mut buf = Vec::new();
+        write_message(&mut buf, &IpcResponse::Transformed { text: "a\nb".into() }).unwrap();
+        assert_eq!(buf.iter().filter(|&&b| b == b'\n').count(), 1);
+        
+        let response: IpcResponse = read_message(&mut buf.as_slice()).unwrap();
+        assert!(matches!(response, IpcResponse::Transformed { text } if text == "a\nb"));
     }
 }
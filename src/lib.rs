@@ -3,15 +3,29 @@
 //! A Rust utility that automatically cleans, formats, and transforms clipboard text.
 //! Create reusable "recipes" to standardize pasting with Ctrl+V.
 
+pub mod apps;
+pub mod backend;
 pub mod clipboard;
+pub mod classify;
+pub mod watcher;
 pub mod config;
+pub mod formats;
+pub mod history;
+pub mod html;
+pub mod images;
+pub mod query;
 pub mod recipe;
+pub mod sensitive;
+pub mod tables;
 pub mod transformers;
 pub mod tray;
 pub mod dashboard;
 pub mod hotkeys;
 pub mod ipc;
 pub mod quick_menu;
+pub mod typing;
+#[cfg(target_os = "linux")]
+pub mod wayland;
 
 pub use clipboard::ClipboardManager;
 pub use config::Config;
@@ -3,25 +3,34 @@
 //! A Rust utility that automatically cleans, formats, and transforms clipboard text.
 //! Create reusable "recipes" to standardize pasting.
 
+use std::io::{IsTerminal, Read, Write};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::process::Command;
 use anyhow::{Result, Context};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use tracing::{info, error, Level};
+use tokio::sync::mpsc;
 use tracing_subscriber::FmtSubscriber;
 
 use ninepaste::{
     ClipboardManager,
+    backend::{self, ArboardBackend, ClipboardBackend, ClipboardContent, Selection},
+    images::{ClipboardImage, ImageFormat},
     Config,
+    config::{HistoryEncryption, PasteMode},
     RecipeManager,
     Recipe,
-    config::{HistoryManager, HistoryEntry},
+    recipe::CompiledRecipe,
+    apps::{self, AppRule, AppRules},
+    history::{HistoryManager, HistoryEntry, HistoryKey, HistoryQuery},
     dashboard::Dashboard,
     quick_menu::QuickMenu,
     tray::TrayManager,
+    typing,
     hotkeys::{HotkeyManager, HotkeyAction},
-    clipboard::ClipboardEvent,
-    ipc::{IpcServer, IpcCommand},
+    clipboard::{AppliedRecipe, ClipboardEvent},
+    ipc::{IpcServer, IpcClient, IpcCommand, IpcResponse, ServiceStatus},
 };
 
 #[derive(Parser)]
@@ -49,29 +58,284 @@ enum Commands {
     Apply {
         /// Recipe name or ID
         recipe: String,
+        
+        #[command(flatten)]
+        io: IoArgs,
     },
     
     /// List all recipes
     List,
     
     /// Show clipboard content
-    Show,
+    Show {
+        /// Selection to show: clipboard or primary (Linux only)
+        #[arg(long, default_value_t = Selection::Clipboard)]
+        selection: Selection,
+    },
     
     /// Run a quick transformation on clipboard
     Transform {
         /// Transformation to apply (e.g., "lowercase", "trim", "remove-duplicates")
         transformation: String,
+        
+        #[command(flatten)]
+        io: IoArgs,
     },
     
     /// Toggle transformation on/off
     Toggle,
+    
+    /// Restore the clipboard from before the last transformation
+    /// (needs the background service)
+    Undo,
+    
+    /// Search and manage the encrypted clipboard history
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+        
+        #[command(flatten)]
+        key: HistoryKeyArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// List history entries, newest first
+    List {
+        /// Maximum number of entries to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+        
+        /// Only show entries containing these words
+        #[arg(short, long)]
+        search: Option<String>,
+        
+        /// Only show entries transformed by this recipe (name or ID)
+        #[arg(short, long)]
+        recipe: Option<String>,
+        
+        /// Print entries as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    
+    /// Delete all history entries
+    Clear,
+    
+    /// Print the history key stored in the OS keyring, for use with --key
+    Key,
+}
+
+/// Environment variable holding the history passphrase, for scripts
+const PASSPHRASE_ENV: &str = "NINEPASTE_HISTORY_PASSPHRASE";
+
+/// How to decrypt history from the command line.
+/// Without either option the passphrase from `NINEPASTE_HISTORY_PASSPHRASE`
+/// or the configured key is used.
+#[derive(Args)]
+struct HistoryKeyArgs {
+    /// Decrypt history with this key (64 hex digits, see `9paste history key`)
+    #[arg(long, global = true, value_name = "HEX", conflicts_with = "passphrase")]
+    key: Option<String>,
+    
+    /// Prompt for the passphrase to decrypt history with
+    #[arg(long, global = true)]
+    passphrase: bool,
+}
+
+impl HistoryKeyArgs {
+    /// Get the key to decrypt history with. The passphrase is never taken
+    /// from the command line, where other users could read it.
+    fn resolve(&self, config: &Config) -> Result<HistoryKey> {
+        if let Some(ref hex) = self.key {
+            return HistoryKey::from_hex(hex);
+        }
+        if self.passphrase {
+            let passphrase = rpassword::prompt_password("History passphrase: ")
+                .context("Failed to read the passphrase")?;
+            return Ok(HistoryKey::Passphrase(passphrase));
+        }
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+            return Ok(HistoryKey::Passphrase(passphrase));
+        }
+        HistoryKey::from_config(config)?
+            .with_context(|| format!("History is protected by a passphrase, enter it with --passphrase or set {}", PASSPHRASE_ENV))
+    }
+}
+
+/// Input/output options for commands that transform text.
+/// By default text is read from and written back to the clipboard.
+#[derive(Args)]
+struct IoArgs {
+    /// Read input from stdin instead of the clipboard
+    /// (implied by --stdout when stdin is piped)
+    #[arg(long, conflicts_with = "input")]
+    stdin: bool,
+    
+    /// Read input from a file instead of the clipboard
+    #[arg(long, value_name = "FILE")]
+    input: Option<PathBuf>,
+    
+    /// Write the result to stdout instead of the clipboard
+    #[arg(long, conflicts_with = "output")]
+    stdout: bool,
+    
+    /// Write the result to a file instead of the clipboard
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    
+    /// Selection to read from and write to: clipboard or primary (Linux only)
+    #[arg(long, default_value_t = Selection::Clipboard)]
+    selection: Selection,
+    
+    /// Type the result into the focused window instead of writing it to the
+    /// clipboard, e.g. from a window manager shortcut (X11)
+    #[arg(long = "type", conflicts_with_all = ["stdout", "output"])]
+    type_result: bool,
+}
+
+impl IoArgs {
+    /// The selection to read from and write to
+    fn clipboard(&self) -> ArboardBackend {
+        ArboardBackend::new(self.selection)
+    }
+    
+    /// Whether input should come from stdin
+    fn reads_stdin(&self) -> bool {
+        self.stdin || (self.stdout && self.input.is_none() && !std::io::stdin().is_terminal())
+    }
+    
+    /// Read the input text from stdin, a file, or the clipboard
+    fn read(&self) -> Result<String> {
+        Ok(self.read_content(false)?.text)
+    }
+    
+    /// Read the input from stdin, a file, or the clipboard, including the
+    /// clipboard's HTML if asked for
+    fn read_content(&self, with_html: bool) -> Result<ClipboardContent> {
+        match self.read_bytes()? {
+            Some(bytes) => bytes_to_content(bytes),
+            None => self.clipboard().get_content(with_html),
+        }
+    }
+    
+    /// Read the input for a recipe: an image if the recipe has image steps
+    /// and the input is one, text otherwise
+    fn read_input(&self, recipe: &Recipe) -> Result<Input> {
+        if !recipe.uses_images() {
+            return self.read_content(recipe.uses_html()).map(Input::Content);
+        }
+        
+        match self.read_bytes()? {
+            Some(bytes) => match ImageFormat::detect(&bytes) {
+                Some(format) => Ok(Input::Image(ClipboardImage::new(format, bytes))),
+                None => bytes_to_content(bytes).map(Input::Content),
+            },
+            None => match self.clipboard().get_image()? {
+                Some(image) => Ok(Input::Image(image)),
+                None => self.clipboard().get_content(recipe.uses_html()).map(Input::Content),
+            },
+        }
+    }
+    
+    /// Read the raw input from stdin or a file, or None if it comes from the clipboard
+    fn read_bytes(&self) -> Result<Option<Vec<u8>>> {
+        if self.reads_stdin() {
+            let mut bytes = Vec::new();
+            std::io::stdin().read_to_end(&mut bytes)
+                .context("Failed to read from stdin")?;
+            Ok(Some(bytes))
+        } else if let Some(ref path) = self.input {
+            std::fs::read(path)
+                .map(Some)
+                .context(format!("Failed to read input file: {}", path.display()))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    /// Write the result text to stdout, a file, or the clipboard
+    fn write(&self, text: &str) -> Result<()> {
+        self.write_content(&ClipboardContent::text(text))
+    }
+    
+    /// Write the result to stdout or a file (as HTML if it has any), or the
+    /// clipboard, which the background service keeps serving if it runs
+    fn write_content(&self, content: &ClipboardContent) -> Result<()> {
+        if self.type_result {
+            return typing::type_text(&content.text);
+        }
+        
+        let text = content.html.as_deref().unwrap_or(&content.text);
+        if self.write_bytes(text.as_bytes())? {
+            Ok(())
+        } else {
+            ClipboardManager::set_content(content, self.selection)
+        }
+    }
+    
+    /// Write a result image to stdout, a file, or the clipboard
+    fn write_image(&self, image: &ClipboardImage) -> Result<()> {
+        if self.type_result {
+            anyhow::bail!("Images can't be typed");
+        }
+        if self.write_bytes(&image.bytes)? {
+            Ok(())
+        } else {
+            self.clipboard().set_image(image)
+        }
+    }
+    
+    /// Write raw output to stdout or a file, returning false if it goes to the clipboard instead
+    fn write_bytes(&self, bytes: &[u8]) -> Result<bool> {
+        if self.stdout {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(bytes)
+                .context("Failed to write to stdout")?;
+            stdout.flush()
+                .context("Failed to flush stdout")?;
+            Ok(true)
+        } else if let Some(ref path) = self.output {
+            std::fs::write(path, bytes)
+                .context(format!("Failed to write output file: {}", path.display()))?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+    
+    /// Print a status line, keeping stdout clean when it carries the result
+    fn report(&self, message: &str) {
+        if self.stdout {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+}
+
+/// Input for a recipe
+enum Input {
+    /// Text, with the clipboard's HTML if it was asked for
+    Content(ClipboardContent),
+    /// An encoded image
+    Image(ClipboardImage),
+}
+
+/// Take input read from stdin or a file as text
+fn bytes_to_content(bytes: Vec<u8>) -> Result<ClipboardContent> {
+    String::from_utf8(bytes)
+        .map(ClipboardContent::text)
+        .context("Input is not valid UTF-8 text")
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
+    // Initialize logging (on stderr, so stdout can be used in pipelines)
     let _subscriber = FmtSubscriber::builder()
         .with_max_level(Level::INFO)
+        .with_writer(std::io::stderr)
         .with_target(false)
         .compact()
         .init();
@@ -82,11 +346,13 @@ async fn main() -> Result<()> {
         Some(Commands::Dashboard) => run_dashboard()?,
         Some(Commands::QuickMenu) => run_quick_menu()?,
         Some(Commands::Start) => run_background_service().await?,
-        Some(Commands::Apply { recipe }) => apply_recipe(&recipe)?,
+        Some(Commands::Apply { recipe, io }) => apply_recipe(&recipe, &io)?,
         Some(Commands::List) => list_recipes()?,
-        Some(Commands::Show) => show_clipboard()?,
-        Some(Commands::Transform { transformation }) => quick_transform(&transformation)?,
+        Some(Commands::Show { selection }) => show_clipboard(selection)?,
+        Some(Commands::Transform { transformation, io }) => quick_transform(&transformation, &io)?,
         Some(Commands::Toggle) => toggle_transformation()?,
+        Some(Commands::Undo) => undo_transformation()?,
+        Some(Commands::History { command, key }) => history_command(&command, &key)?,
         None => {
             // Default: run dashboard
             run_dashboard()?;
@@ -169,19 +435,51 @@ fn spawn_quick_menu() {
 /// Run the background clipboard monitoring service
 async fn run_background_service() -> Result<()> {
     info!("Starting 9Paste background service...");
+    // We outlive the quick menu and CLI, so serve the clipboard for them
+    backend::set_background_service();
     
     let config = Config::load()?;
     let recipe_manager = Arc::new(Mutex::new(RecipeManager::new()?));
     
     // Get the active recipe
-    let active_recipe: Arc<Mutex<Option<Recipe>>> = {
+    let active_recipe: Arc<Mutex<Option<CompiledRecipe>>> = {
         let rm = recipe_manager.lock().unwrap();
-        Arc::new(Mutex::new(rm.get_active_recipe().cloned()))
+        Arc::new(Mutex::new(rm.get_active_recipe().map(Recipe::compile)))
     };
     
-    // Start clipboard monitoring
+    // Rules that override the active recipe for some applications
+    let app_rules = Arc::new(Mutex::new(AppRules::default()));
+    install_app_rules(&app_rules, &config.app_rules, &recipe_manager.lock().unwrap().recipes);
+    
+    // Start clipboard monitoring. The manager is needed even if only PRIMARY
+    // is monitored, since hotkeys and undo act on the clipboard.
     let mut clipboard_manager = ClipboardManager::new();
-    let mut clipboard_rx = clipboard_manager.start_monitoring(Some(Arc::clone(&active_recipe)));
+    clipboard_manager.set_poll_interval(std::time::Duration::from_millis(config.poll_interval_ms));
+    clipboard_manager.set_detect_sensitive(config.detect_sensitive);
+    clipboard_manager.set_transform_sensitive(config.transform_sensitive);
+    clipboard_manager.set_app_rules(Arc::clone(&app_rules));
+    clipboard_manager.set_window_source(apps::default_window_source());
+    let mut receivers = Vec::new();
+    if config.selections.includes(Selection::Clipboard) {
+        receivers.push(clipboard_manager.start_monitoring(Some(Arc::clone(&active_recipe))));
+    }
+    
+    // The PRIMARY selection has its own recipe, and no app rules
+    let primary_recipe = Arc::new(Mutex::new(None));
+    install_primary_recipe(&primary_recipe, config.primary_recipe.as_deref(), &recipe_manager.lock().unwrap().recipes);
+    let mut primary_manager = ClipboardManager::for_selection(Selection::Primary);
+    primary_manager.set_poll_interval(std::time::Duration::from_millis(config.poll_interval_ms));
+    primary_manager.set_detect_sensitive(config.detect_sensitive);
+    primary_manager.set_transform_sensitive(config.transform_sensitive);
+    primary_manager.share_transform_enabled(&clipboard_manager);
+    if config.selections.includes(Selection::Primary) {
+        if cfg!(target_os = "linux") {
+            receivers.push(primary_manager.start_monitoring(Some(Arc::clone(&primary_recipe))));
+        } else {
+            println!("⚠️  The primary selection only exists on Linux, not monitoring it");
+        }
+    }
+    let mut clipboard_rx = merge_events(receivers);
     
     // Set up hotkeys if configured
     let mut hotkey_manager = HotkeyManager::new().ok();
@@ -203,6 +501,17 @@ async fn run_background_service() -> Result<()> {
                 info!("Registered dashboard hotkey: {}", hotkey);
             }
         }
+        if let Some(ref hotkey) = config.undo_hotkey {
+            if hm.register(hotkey, HotkeyAction::Undo).is_ok() {
+                info!("Registered undo hotkey: {}", hotkey);
+            }
+        }
+        if let Some(ref hotkey) = config.paste_hotkey {
+            if hm.register(hotkey, HotkeyAction::TransformAndPaste).is_ok() {
+                info!("Registered transform and paste hotkey: {}", hotkey);
+            }
+        }
+        register_recipe_hotkeys(hm, &recipe_manager.lock().unwrap().recipes);
         hotkey_rx = Some(hm.start());
     }
     
@@ -216,13 +525,17 @@ async fn run_background_service() -> Result<()> {
     
     // History manager for recording transformations
     let mut history_manager = if config.keep_history {
-        match HistoryManager::new(config.max_history_size) {
-            Ok(hm) => {
+        match open_history(&config, None) {
+            Ok(Some(hm)) => {
                 info!("History manager initialized (max {} entries)", config.max_history_size);
                 Some(hm)
             }
+            Ok(None) => {
+                println!("🔒 History is locked. Unlock it in the dashboard to record history.");
+                None
+            }
             Err(e) => {
-                error!("Failed to create history manager: {}", e);
+                error!("Failed to open history: {:#}", e);
                 None
             }
         }
@@ -236,10 +549,15 @@ async fn run_background_service() -> Result<()> {
     
     if let Some(ref recipe) = *active_recipe.lock().unwrap() {
         println!("Active recipe: {}", recipe.name);
+        report_recipe_problems(recipe);
     } else {
         println!("No active recipe. Set one in the dashboard.");
     }
     
+    // Pastes into the focused window run off the event loop, and ask it to
+    // restore the clipboard once the application has read it
+    let (restore_tx, mut restore_rx) = mpsc::channel::<ClipboardContent>(4);
+    
     // Debounce for hotkeys to prevent double-firing
     let mut last_hotkey_time = std::time::Instant::now() - std::time::Duration::from_secs(1);
     const HOTKEY_DEBOUNCE_MS: u128 = 300;
@@ -253,20 +571,19 @@ async fn run_background_service() -> Result<()> {
                     ClipboardEvent::Changed(text) => {
                         info!("Clipboard changed: {} chars", text.len());
                     }
-                    ClipboardEvent::Transformed { original, result } => {
+                    ClipboardEvent::Sensitive(kind) => {
+                        if config.show_notifications {
+                            println!("🔐 Copied a {}, keeping it out of history", kind);
+                        }
+                    }
+                    ClipboardEvent::Transformed { original, result, recipe, sensitive } => {
                         info!("Transformed: {} -> {} chars", original.len(), result.len());
                         
                         // Save to history
-                        if let Some(ref mut hm) = history_manager {
-                            let recipe_info = active_recipe.lock().unwrap();
-                            let entry = HistoryEntry {
-                                original: original.clone(),
-                                transformed: Some(result.clone()),
-                                recipe_id: recipe_info.as_ref().map(|r| r.id.to_string()),
-                                recipe_name: recipe_info.as_ref().map(|r| r.name.clone()),
-                                timestamp: chrono::Utc::now(),
-                            };
-                            drop(recipe_info);
+                        if sensitive {
+                            info!("Sensitive content, skipping history save");
+                        } else if let Some(ref mut hm) = history_manager {
+                            let entry = HistoryEntry::new(original.clone(), Some(result.clone()), Some(&*recipe));
                             match hm.add(entry) {
                                 Ok(_) => info!("Saved to history"),
                                 Err(e) => error!("Failed to save history: {}", e),
@@ -279,44 +596,150 @@ async fn run_background_service() -> Result<()> {
                             println!("✨ Clipboard transformed!");
                         }
                     }
+                    ClipboardEvent::ImageTransformed { format, recipe } => {
+                        info!("Transformed clipboard image with {} ({})", recipe.name, format);
+                        if config.show_notifications {
+                            println!("🖼️  Clipboard image transformed!");
+                        }
+                    }
                     ClipboardEvent::Error(err) => {
                         error!("Clipboard error: {}", err);
                     }
                 }
             }
             
-            // Handle IPC commands from dashboard
-            Some(cmd) = async {
+            // Handle IPC requests from the dashboard and scripts
+            Some(request) = async {
                 if let Some(ref mut rx) = ipc_rx {
                     rx.recv().await
                 } else {
                     std::future::pending().await
                 }
             } => {
-                match cmd {
+                let response = match request.command.clone() {
                     IpcCommand::ReloadRecipe => {
-                        // Reload active recipe from disk
-                        if let Ok(rm) = RecipeManager::new() {
-                            let new_active = rm.get_active_recipe().cloned();
-                            let mut current = active_recipe_for_ipc.lock().unwrap();
-                            
-                            if let Some(ref recipe) = new_active {
-                                println!("📝 Active recipe: {}", recipe.name);
-                            } else {
-                                println!("📝 Recipe deactivated");
+                        // Reload recipes from disk, along with app rules the dashboard may have changed
+                        let config = Config::load().unwrap_or_else(|_| config.clone());
+                        match RecipeManager::new() {
+                            Ok(rm) => {
+                                install_app_rules(&app_rules, &config.app_rules, &rm.recipes);
+                                install_primary_recipe(&primary_recipe, config.primary_recipe.as_deref(), &rm.recipes);
+                                install_recipes(rm, &recipe_manager, &active_recipe_for_ipc, &mut hotkey_manager);
+                                IpcResponse::Ok
                             }
-                            *current = new_active;
+                            Err(e) => IpcResponse::Error { message: e.to_string() },
                         }
                     }
                     IpcCommand::ToggleTransformation => {
                         let enabled = clipboard_manager.is_transform_enabled();
                         clipboard_manager.set_transform_enabled(!enabled);
                         println!("Transformation: {}", if !enabled { "enabled" } else { "disabled" });
+                        IpcResponse::Ok
                     }
-                    IpcCommand::Ping => {
-                        // Just a ping, nothing to do
+                    IpcCommand::ApplyRecipe { id } => {
+                        let recipe = recipe_manager.lock().unwrap().get_recipe(id).cloned();
+                        match recipe {
+                            Some(recipe) => match apply_recipe_once(&clipboard_manager, &mut history_manager, &recipe) {
+                                Ok(Some(applied)) => IpcResponse::Transformed { text: applied.result },
+                                Ok(None) => IpcResponse::Ok,
+                                Err(e) => IpcResponse::Error { message: e.to_string() },
+                            },
+                            None => IpcResponse::Error { message: format!("Recipe not found: {}", id) },
+                        }
                     }
-                }
+                    IpcCommand::SetActiveRecipe { id } => {
+                        // Start from disk so edits made in the dashboard aren't lost
+                        let result = RecipeManager::new().and_then(|mut rm| {
+                            match id {
+                                Some(id) if rm.get_recipe(id).is_none() => {
+                                    anyhow::bail!("Recipe not found: {}", id)
+                                }
+                                Some(id) => rm.set_active(id)?,
+                                None => rm.deactivate_all()?,
+                            }
+                            Ok(rm)
+                        });
+                        match result {
+                            Ok(rm) => {
+                                // App rules and the primary recipe may name the recipes that changed
+                                let config = Config::load().unwrap_or_else(|_| config.clone());
+                                install_app_rules(&app_rules, &config.app_rules, &rm.recipes);
+                                install_primary_recipe(&primary_recipe, config.primary_recipe.as_deref(), &rm.recipes);
+                                install_recipes(rm, &recipe_manager, &active_recipe_for_ipc, &mut hotkey_manager);
+                                IpcResponse::Ok
+                            }
+                            Err(e) => IpcResponse::Error { message: e.to_string() },
+                        }
+                    }
+                    IpcCommand::GetStatus => {
+                        let active = active_recipe.lock().unwrap();
+                        IpcResponse::Status(ServiceStatus {
+                            version: env!("CARGO_PKG_VERSION").to_string(),
+                            transform_enabled: clipboard_manager.is_transform_enabled(),
+                            active_recipe_id: active.as_ref().map(|r| r.id),
+                            active_recipe_name: active.as_ref().map(|r| r.name.clone()),
+                            history_enabled: history_manager.is_some(),
+                        })
+                    }
+                    IpcCommand::GetHistory { limit } => {
+                        if let Some(ref hm) = history_manager {
+                            let query = HistoryQuery { limit, ..Default::default() };
+                            match hm.query(&query) {
+                                Ok(entries) => IpcResponse::History { entries },
+                                Err(e) => IpcResponse::Error { message: e.to_string() },
+                            }
+                        } else {
+                            IpcResponse::Error { message: "History is disabled".into() }
+                        }
+                    }
+                    IpcCommand::Transform { text, recipe } => {
+                        let rm = recipe_manager.lock().unwrap();
+                        match rm.find(&recipe) {
+                            Some(recipe) => match recipe.try_apply_content(&ClipboardContent::text(text)) {
+                                Ok(content) => IpcResponse::Transformed { text: content.html.unwrap_or(content.text) },
+                                Err(e) => IpcResponse::Error { message: e.to_string() },
+                            },
+                            None => IpcResponse::Error { message: format!("Recipe not found: {}", recipe) },
+                        }
+                    }
+                    IpcCommand::Undo => match clipboard_manager.undo() {
+                        Ok(Some(_)) => IpcResponse::Ok,
+                        Ok(None) => IpcResponse::Error { message: "Nothing to undo".into() },
+                        Err(e) => IpcResponse::Error { message: e.to_string() },
+                    },
+                    IpcCommand::UnlockHistory { passphrase } => {
+                        // The dashboard may have changed how history is encrypted
+                        let config = Config::load().unwrap_or_else(|_| config.clone());
+                        if !config.keep_history {
+                            IpcResponse::Error { message: "History is disabled".into() }
+                        } else {
+                            match open_history(&config, passphrase.map(|p| p.0)) {
+                                Ok(Some(hm)) => {
+                                    history_manager = Some(hm);
+                                    println!("🔓 History unlocked");
+                                    IpcResponse::Ok
+                                }
+                                Ok(None) => IpcResponse::Error { message: "A passphrase is required to unlock history".into() },
+                                Err(e) => IpcResponse::Error { message: format!("{:#}", e) },
+                            }
+                        }
+                    }
+                    IpcCommand::SetClipboard { text, html, selection } => {
+                        let manager = match selection {
+                            Selection::Clipboard => &clipboard_manager,
+                            Selection::Primary => &primary_manager,
+                        };
+                        match manager.take_over(&ClipboardContent { text, html }) {
+                            Ok(()) => IpcResponse::Ok,
+                            Err(e) => IpcResponse::Error { message: e.to_string() },
+                        }
+                    }
+                    IpcCommand::Hello { .. } | IpcCommand::Ping => {
+                        // Answered by the IPC server itself
+                        IpcResponse::Ok
+                    }
+                };
+                request.respond(response);
             }
             
             // Handle hotkey events
@@ -351,6 +774,43 @@ async fn run_background_service() -> Result<()> {
                     HotkeyAction::OpenDashboard => {
                         spawn_dashboard();
                     }
+                    HotkeyAction::Undo => {
+                        undo_last_transform(&clipboard_manager, &config);
+                    }
+                    HotkeyAction::TransformAndPaste => {
+                        let recipe = active_recipe.lock().unwrap().clone();
+                        match recipe {
+                            Some(recipe) => {
+                                if let Err(e) = transform_and_paste(&clipboard_manager, &mut history_manager, &recipe, &config, &restore_tx) {
+                                    error!("Failed to paste with {}: {:#}", recipe.name, e);
+                                }
+                            }
+                            None => println!("No active recipe to paste with. Set one in the dashboard."),
+                        }
+                    }
+                    HotkeyAction::ApplyRecipe(id) => {
+                        let recipe = recipe_manager.lock().unwrap().get_recipe(id).cloned();
+                        let Some(recipe) = recipe else {
+                            error!("Recipe for hotkey not found: {}", id);
+                            continue;
+                        };
+                        
+                        match apply_recipe_once(&clipboard_manager, &mut history_manager, &recipe) {
+                            Ok(_) => {
+                                if config.show_notifications {
+                                    println!("✨ Applied recipe: {}", recipe.name);
+                                }
+                            }
+                            Err(e) => error!("Failed to apply recipe {}: {}", recipe.name, e),
+                        }
+                    }
+                }
+            }
+            
+            // Restore the clipboard after a transform and paste
+            Some(original) = restore_rx.recv() => {
+                if let Err(e) = clipboard_manager.take_over(&original) {
+                    error!("Failed to restore the clipboard: {}", e);
                 }
             }
             
@@ -384,6 +844,9 @@ async fn run_background_service() -> Result<()> {
                     TrayCommand::ShowQuickMenu => {
                         spawn_quick_menu();
                     }
+                    TrayCommand::Undo => {
+                        undo_last_transform(&clipboard_manager, &config);
+                    }
                     _ => {}
                 }
             }
@@ -401,6 +864,7 @@ async fn run_background_service() -> Result<()> {
     
     // Stop clipboard first to prevent any clipboard operations blocking us
     clipboard_manager.stop_monitoring();
+    primary_manager.stop_monitoring();
     
     if let Some(ref hm) = hotkey_manager {
         hm.stop();
@@ -414,21 +878,287 @@ async fn run_background_service() -> Result<()> {
     std::process::exit(0);
 }
 
-/// Apply a recipe to clipboard content
-fn apply_recipe(recipe_name: &str) -> Result<()> {
+/// Replace the service's recipes with freshly loaded ones, updating the
+/// active recipe and re-registering recipe hotkeys
+fn install_recipes(
+    rm: RecipeManager,
+    recipe_manager: &Mutex<RecipeManager>,
+    active_recipe: &Mutex<Option<CompiledRecipe>>,
+    hotkey_manager: &mut Option<HotkeyManager>,
+) {
+    let new_active = rm.get_active_recipe().map(Recipe::compile);
+    if let Some(ref recipe) = new_active {
+        println!("📝 Active recipe: {}", recipe.name);
+        report_recipe_problems(recipe);
+    } else {
+        println!("📝 Recipe deactivated");
+    }
+    *active_recipe.lock().unwrap() = new_active;
+    
+    // Recipe hotkeys may have changed too
+    if let Some(ref mut hm) = hotkey_manager {
+        hm.unregister_recipe_hotkeys().ok();
+        register_recipe_hotkeys(hm, &rm.recipes);
+    }
+    *recipe_manager.lock().unwrap() = rm;
+}
+
+/// Replace the service's app rules, looking up their recipes
+fn install_app_rules(app_rules: &Mutex<AppRules>, rules: &[AppRule], recipes: &[Recipe]) {
+    let new_rules = AppRules::new(rules, recipes);
+    for problem in new_rules.problems() {
+        println!("⚠️  {}", problem);
+    }
+    *app_rules.lock().unwrap() = new_rules;
+}
+
+/// Replace the recipe transforming the PRIMARY selection, looking it up by name or ID
+fn install_primary_recipe(primary_recipe: &Mutex<Option<CompiledRecipe>>, name: Option<&str>, recipes: &[Recipe]) {
+    let recipe = name.and_then(|name| {
+        let recipe = recipes.iter().find(|r| r.matches_name(name));
+        if recipe.is_none() {
+            println!("⚠️  Primary selection recipe not found: {}", name);
+        }
+        recipe
+    });
+    *primary_recipe.lock().unwrap() = recipe.map(Recipe::compile);
+}
+
+/// Forward the events of several clipboard monitors into one channel
+fn merge_events(receivers: Vec<mpsc::Receiver<ClipboardEvent>>) -> mpsc::Receiver<ClipboardEvent> {
+    let (tx, rx) = mpsc::channel(100);
+    for mut receiver in receivers {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    rx
+}
+
+/// Warn about steps of the active recipe that will stop it from applying
+fn report_recipe_problems(recipe: &CompiledRecipe) {
+    for problem in recipe.validate() {
+        println!("⚠️  {}", problem);
+    }
+}
+
+/// Open the encrypted history with a passphrase, or with the configured key.
+/// Returns None if history is protected by a passphrase that wasn't given.
+fn open_history(config: &Config, passphrase: Option<String>) -> Result<Option<HistoryManager>> {
+    let key = match passphrase {
+        Some(passphrase) => HistoryKey::Passphrase(passphrase),
+        None => match HistoryKey::from_config(config)? {
+            Some(key) => key,
+            None => return Ok(None),
+        },
+    };
+    HistoryManager::new(config.max_history_size, &key).map(Some)
+}
+
+/// Apply a recipe to the clipboard once and record it in history.
+/// Returns what was applied, or None if a clipboard image was transformed.
+fn apply_recipe_once(
+    clipboard_manager: &ClipboardManager,
+    history_manager: &mut Option<HistoryManager>,
+    recipe: &Recipe,
+) -> Result<Option<AppliedRecipe>> {
+    if let Some(image) = clipboard_manager.apply_recipe_to_image(recipe)? {
+        info!("Applied recipe {} to a {} image", recipe.name, image.format);
+        return Ok(None);
+    }
+    
+    let applied = clipboard_manager.apply_recipe_once(recipe)?;
+    info!("Applied recipe: {}", recipe.name);
+    
+    if let Some(kind) = applied.sensitive {
+        info!("Clipboard held a {}, skipping history save", kind);
+    } else if let Some(ref mut hm) = history_manager {
+        let entry = HistoryEntry::new(applied.original.text.clone(), Some(applied.result.clone()), Some(recipe));
+        if let Err(e) = hm.add(entry) {
+            error!("Failed to save history: {}", e);
+        }
+    }
+    
+    Ok(Some(applied))
+}
+
+/// Apply a recipe to the clipboard and paste the result into the focused
+/// window, by pressing Ctrl+V or by typing it out. The key presses happen in
+/// the background; with `restore_clipboard`, the original content is sent on
+/// `restore_tx` afterwards, to be put back on the clipboard.
+fn transform_and_paste(
+    clipboard_manager: &ClipboardManager,
+    history_manager: &mut Option<HistoryManager>,
+    recipe: &Recipe,
+    config: &Config,
+    restore_tx: &mpsc::Sender<ClipboardContent>,
+) -> Result<()> {
+    /// How long the application gets to read the clipboard before it is restored
+    const RESTORE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+    
+    match config.paste_mode {
+        PasteMode::Paste => {
+            let applied = apply_recipe_once(clipboard_manager, history_manager, recipe)?;
+            // Images aren't restored
+            let restore = applied.filter(|_| config.restore_clipboard).map(|applied| applied.original);
+            let restore_tx = restore_tx.clone();
+            let name = recipe.name.clone();
+            tokio::spawn(async move {
+                if press_keys(&name, typing::paste).await {
+                    if let Some(original) = restore {
+                        tokio::time::sleep(RESTORE_DELAY).await;
+                        restore_tx.send(original).await.ok();
+                    }
+                }
+            });
+        }
+        PasteMode::Type => {
+            let original = clipboard_manager.backend().get_content(recipe.uses_html())?;
+            let sensitive = clipboard_manager.check_transformable(&original.text)?;
+            let transformed = recipe.try_apply_content(&original)?;
+            let text = transformed.text.clone();
+            let name = recipe.name.clone();
+            tokio::spawn(async move {
+                press_keys(&name, move || typing::type_text(&text)).await;
+            });
+            
+            if sensitive.is_none() {
+                if let Some(ref mut hm) = history_manager {
+                    let entry = HistoryEntry::new(original.text, Some(transformed.text), Some(recipe));
+                    if let Err(e) = hm.add(entry) {
+                        error!("Failed to save history: {}", e);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Press keys on a blocking thread, since typing long text takes a while.
+/// Returns whether it worked, logging the error otherwise.
+async fn press_keys(recipe: &str, keys: impl FnOnce() -> Result<()> + Send + 'static) -> bool {
+    let result = tokio::task::spawn_blocking(keys).await.unwrap_or_else(|e| Err(e.into()));
+    if let Err(ref e) = result {
+        error!("Failed to paste with {}: {:#}", recipe, e);
+    }
+    result.is_ok()
+}
+
+/// Undo the last transformation from a hotkey or the tray
+fn undo_last_transform(clipboard_manager: &ClipboardManager, config: &Config) {
+    match clipboard_manager.undo() {
+        Ok(Some(_)) => {
+            if config.show_notifications {
+                println!("↩️  Undid last transformation");
+            }
+        }
+        Ok(None) => info!("Nothing to undo"),
+        Err(e) => error!("Failed to undo: {}", e),
+    }
+}
+
+/// Register the global hotkeys of all recipes that have one
+fn register_recipe_hotkeys(hotkey_manager: &mut HotkeyManager, recipes: &[Recipe]) {
+    for recipe in recipes {
+        if let Some(ref hotkey) = recipe.hotkey {
+            match hotkey_manager.register(hotkey, HotkeyAction::ApplyRecipe(recipe.id)) {
+                Ok(()) => info!("Registered hotkey {} for recipe {}", hotkey, recipe.name),
+                Err(e) => error!("Failed to register hotkey {} for recipe {}: {}", hotkey, recipe.name, e),
+            }
+        }
+    }
+}
+
+/// Apply a recipe to clipboard content (or stdin/file input)
+fn apply_recipe(recipe_name: &str, io: &IoArgs) -> Result<()> {
     let recipe_manager = RecipeManager::new()?;
     
     // Find recipe by name or ID
-    let recipe = recipe_manager.recipes.iter()
-        .find(|r| r.name.eq_ignore_ascii_case(recipe_name) || r.id.to_string() == recipe_name)
+    let recipe = recipe_manager.find(recipe_name)
         .context(format!("Recipe not found: {}", recipe_name))?;
     
-    let original = ClipboardManager::get_text()?;
-    let transformed = recipe.apply(&original);
-    ClipboardManager::set_text(&transformed)?;
+    let original = match io.read_input(recipe)? {
+        Input::Content(content) => content,
+        Input::Image(image) => {
+            let transformed = recipe.try_apply_image(&image)
+                .context(format!("Recipe {} cannot be applied", recipe.name))?;
+            io.write_image(&transformed)?;
+            
+            io.report(&format!("✨ Applied recipe: {}", recipe.name));
+            io.report(&format!("   {} image, {} bytes → {} image, {} bytes",
+                image.format, image.bytes.len(), transformed.format, transformed.bytes.len()));
+            return Ok(());
+        }
+    };
+    let transformed = recipe.try_apply_content(&original)
+        .context(format!("Recipe {} cannot be applied", recipe.name))?;
+    io.write_content(&transformed)?;
+    
+    io.report(&format!("✨ Applied recipe: {}", recipe.name));
+    io.report(&format!("   {} chars → {} chars", original.text.len(), transformed.text.len()));
+    
+    Ok(())
+}
+
+/// Search and manage clipboard history from the command line
+fn history_command(command: &HistoryCommand, key_args: &HistoryKeyArgs) -> Result<()> {
+    let config = Config::load()?;
+    
+    if let HistoryCommand::Key = command {
+        if config.history_encryption == HistoryEncryption::Passphrase {
+            anyhow::bail!("History is protected by a passphrase, not a keyring key");
+        }
+        println!("{}", HistoryKey::from_keyring()?.to_hex().unwrap_or_default());
+        return Ok(());
+    }
     
-    println!("✨ Applied recipe: {}", recipe.name);
-    println!("   {} chars → {} chars", original.len(), transformed.len());
+    let key = key_args.resolve(&config)?;
+    let mut history = HistoryManager::new(config.max_history_size, &key)?;
+    
+    match command {
+        HistoryCommand::List { limit, search, recipe, json } => {
+            // Accept recipe names, falling back to the raw ID for deleted recipes
+            let recipe_id = match recipe {
+                Some(recipe) => Some(RecipeManager::new()?.find(recipe)
+                    .map_or_else(|| recipe.clone(), |r| r.id.to_string())),
+                None => None,
+            };
+            let query = HistoryQuery {
+                search: search.clone(),
+                recipe_id,
+                limit: Some(*limit),
+                ..Default::default()
+            };
+            let entries = history.query(&query)?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else if entries.is_empty() {
+                println!("No history entries.");
+            } else {
+                for entry in &entries {
+                    let first_line = entry.original.lines().next().unwrap_or_default();
+                    let preview: String = first_line.chars().take(60).collect();
+                    println!("{}  {:<16}  {}",
+                        entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                        entry.recipe_name.as_deref().unwrap_or("-"),
+                        preview,
+                    );
+                }
+            }
+        }
+        HistoryCommand::Clear => {
+            history.clear()?;
+            println!("🗑️  History cleared");
+        }
+        HistoryCommand::Key => {}
+    }
     
     Ok(())
 }
@@ -456,13 +1186,17 @@ fn list_recipes() -> Result<()> {
 }
 
 /// Show current clipboard content
-fn show_clipboard() -> Result<()> {
-    let text = ClipboardManager::get_text()?;
+fn show_clipboard(selection: Selection) -> Result<()> {
+    let text = ArboardBackend::new(selection).get_text()?;
+    let title = match selection {
+        Selection::Clipboard => "Clipboard",
+        Selection::Primary => "Primary selection",
+    };
     
     if text.is_empty() {
-        println!("(clipboard is empty)");
+        println!("({} is empty)", title.to_lowercase());
     } else {
-        println!("📋 Clipboard ({} chars):\n", text.len());
+        println!("📋 {} ({} chars):\n", title, text.len());
         println!("{}", text);
     }
     
@@ -470,8 +1204,10 @@ fn show_clipboard() -> Result<()> {
 }
 
 /// Quick transformation
-fn quick_transform(transformation: &str) -> Result<()> {
+fn quick_transform(transformation: &str, io: &IoArgs) -> Result<()> {
+    use ninepaste::formats::DataFormat;
     use ninepaste::recipe::Transformation;
+    use ninepaste::tables::TableFormat;
     
     let transform = match transformation.to_lowercase().as_str() {
         "lowercase" | "lower" => Transformation::ToLowercase,
@@ -496,25 +1232,51 @@ fn quick_transform(transformation: &str) -> Result<()> {
         "html-decode" => Transformation::DecodeHtmlEntities,
         "unix" | "lf" => Transformation::ToUnixLineEndings,
         "windows" | "crlf" => Transformation::ToWindowsLineEndings,
+        "json-pretty" | "json" => Transformation::JsonPrettyPrint { indent: 2 },
+        "json-minify" => Transformation::JsonMinify,
+        "json-sort" | "json-sort-keys" => Transformation::JsonSortKeys,
+        "json-validate" => Transformation::JsonValidate,
+        "to-json" => Transformation::ConvertFormat { from: None, to: DataFormat::Json },
+        "to-yaml" => Transformation::ConvertFormat { from: None, to: DataFormat::Yaml },
+        "to-toml" => Transformation::ConvertFormat { from: None, to: DataFormat::Toml },
+        "to-query" => Transformation::ConvertFormat { from: None, to: DataFormat::QueryString },
+        "md-table" | "markdown-table" => Transformation::ConvertTable { to: TableFormat::Markdown },
+        "ascii-table" => Transformation::ConvertTable { to: TableFormat::Ascii },
+        "html-table" => Transformation::ConvertTable { to: TableFormat::Html },
+        "csv" => Transformation::ConvertTable { to: TableFormat::Csv },
+        "tsv" => Transformation::ConvertTable { to: TableFormat::Tsv },
         _ => {
-            println!("Unknown transformation: {}", transformation);
-            println!("\nAvailable transformations:");
-            println!("  lowercase, uppercase, titlecase, sentencecase");
-            println!("  camelcase, pascalcase, snakecase, kebabcase");
-            println!("  trim, normalize, remove-empty, remove-duplicates");
-            println!("  sort, reverse, smartquotes, remove-emojis");
-            println!("  strip, slugify, html-encode, html-decode");
-            println!("  unix, windows");
-            return Ok(());
+            io.report("Available transformations:");
+            io.report("  lowercase, uppercase, titlecase, sentencecase");
+            io.report("  camelcase, pascalcase, snakecase, kebabcase");
+            io.report("  trim, normalize, remove-empty, remove-duplicates");
+            io.report("  sort, reverse, smartquotes, remove-emojis");
+            io.report("  strip, slugify, html-encode, html-decode");
+            io.report("  unix, windows");
+            io.report("  json-pretty, json-minify, json-sort, json-validate");
+            io.report("  to-json, to-yaml, to-toml, to-query");
+            io.report("  md-table, ascii-table, html-table, csv, tsv");
+            anyhow::bail!("Unknown transformation: {}", transformation);
         }
     };
     
-    let original = ClipboardManager::get_text()?;
-    let result = transform.apply(&original);
-    ClipboardManager::set_text(&result)?;
+    let original = io.read()?;
+    let result = transform.try_apply(&original).map_err(anyhow::Error::msg)?;
+    io.write(&result)?;
     
-    println!("✨ Applied: {}", transform.display_name());
-    println!("   {} chars → {} chars", original.len(), result.len());
+    io.report(&format!("✨ Applied: {}", transform.display_name()));
+    io.report(&format!("   {} chars → {} chars", original.len(), result.len()));
+    
+    Ok(())
+}
+
+/// Ask the background service to undo its last transformation
+fn undo_transformation() -> Result<()> {
+    if !IpcClient::is_service_running() {
+        anyhow::bail!("The background service is not running (start it with `9paste start`)");
+    }
+    IpcClient::send(IpcCommand::Undo)?;
+    println!("↩️  Restored the clipboard from before the last transformation");
     
     Ok(())
 }
@@ -529,3 +1291,38 @@ fn toggle_transformation() -> Result<()> {
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn file_io(input: &std::path::Path, output: &std::path::Path) -> IoArgs {
+        IoArgs {
+            stdin: false,
+            input: Some(input.to_path_buf()),
+            stdout: false,
+            output: Some(output.to_path_buf()),
+            selection: Selection::Clipboard,
+            type_result: false,
+        }
+    }
+    
+    #[test]
+    fn test_quick_transform_files() {
+        let dir = std::env::temp_dir();
+        let id = uuid::Uuid::new_v4();
+        let (input, output) = (dir.join(format!("9paste-in-{}.txt", id)), dir.join(format!("9paste-out-{}.txt", id)));
+        std::fs::write(&input, "hello world").unwrap();
+        let io = file_io(&input, &output);
+        
+        let result = quick_transform("upper", &io);
+        let written = std::fs::read_to_string(&output);
+        let unknown = quick_transform("uper", &io);
+        std::fs::remove_file(&input).ok();
+        std::fs::remove_file(&output).ok();
+        
+        result.unwrap();
+        assert_eq!(written.unwrap(), "HELLO WORLD");
+        assert_eq!(unknown.unwrap_err().to_string(), "Unknown transformation: uper");
+    }
+}
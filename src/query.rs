@@ -0,0 +1,520 @@
+//! JSON queries
+//!
+//! A practical subset of jq: `.field`, `."quoted field"`, `.[index]` (negative
+//! indexes count from the end), `.[start:end]` slices, `.[]` iteration,
+//! `select(...)` filters and `keys`, chained with `|`. JSONPath's leading `$`
+//! and `[*]` are accepted too, so `$.items[*].name` works like `.items[].name`.
+
+use std::cmp::Ordering;
+use serde_json::Value;
+
+/// A parsed query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    /// Filters to run in order, each on every result of the one before
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    /// Follow a path from the current value
+    Path(Vec<Step>),
+    /// Keep the current value if the condition holds
+    Select(Condition),
+    /// Sorted keys of an object, or indexes of an array
+    Keys,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Field(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Iterate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Compare(Operand, Op, Operand),
+    /// The operand is neither null nor false
+    Truthy(Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Path(Vec<Step>),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Query {
+    /// Parse a query, describing where it is malformed otherwise
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let mut parser = Parser { chars: expr.chars().collect(), pos: 0 };
+        let mut filters = Vec::new();
+        loop {
+            filters.push(parser.filter()?);
+            parser.skip_whitespace();
+            if parser.at_end() {
+                break;
+            }
+            parser.expect('|')?;
+        }
+        Ok(Self { filters })
+    }
+    
+    /// Run the query, returning every value it produces
+    pub fn run(&self, input: &Value) -> Result<Vec<Value>, String> {
+        self.filters.iter().try_fold(vec![input.clone()], |values, filter| {
+            let mut results = Vec::new();
+            for value in &values {
+                results.extend(filter.run(value)?);
+            }
+            Ok(results)
+        })
+    }
+}
+
+impl Filter {
+    fn run(&self, value: &Value) -> Result<Vec<Value>, String> {
+        match self {
+            Self::Path(steps) => follow(steps, value),
+            Self::Select(condition) => Ok(if condition.holds(value)? { vec![value.clone()] } else { vec![] }),
+            Self::Keys => match value {
+                Value::Object(map) => {
+                    let mut keys: Vec<_> = map.keys().cloned().collect();
+                    keys.sort();
+                    Ok(vec![Value::Array(keys.into_iter().map(Value::String).collect())])
+                }
+                Value::Array(items) => Ok(vec![Value::Array((0..items.len()).map(Value::from).collect())]),
+                _ => Err(format!("Cannot get keys of {}", type_name(value))),
+            },
+        }
+    }
+}
+
+impl Condition {
+    /// Check the condition. A path yielding several values satisfies it if any of them does.
+    fn holds(&self, value: &Value) -> Result<bool, String> {
+        Ok(match self {
+            Self::Compare(left, op, right) => {
+                let (left, right) = (left.values(value)?, right.values(value)?);
+                left.iter().any(|a| right.iter().any(|b| op.holds(a, b)))
+            }
+            Self::Truthy(operand) => operand.values(value)?
+                .iter()
+                .any(|v| !matches!(v, Value::Null | Value::Bool(false))),
+            Self::And(a, b) => a.holds(value)? && b.holds(value)?,
+            Self::Or(a, b) => a.holds(value)? || b.holds(value)?,
+        })
+    }
+}
+
+impl Operand {
+    fn values(&self, value: &Value) -> Result<Vec<Value>, String> {
+        match self {
+            Self::Path(steps) => follow(steps, value),
+            Self::Literal(literal) => Ok(vec![literal.clone()]),
+        }
+    }
+}
+
+impl Op {
+    fn holds(&self, a: &Value, b: &Value) -> bool {
+        let ordering = compare(a, b);
+        match self {
+            Self::Eq => ordering == Some(Ordering::Equal),
+            Self::Ne => ordering != Some(Ordering::Equal),
+            Self::Lt => ordering == Some(Ordering::Less),
+            Self::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Self::Gt => ordering == Some(Ordering::Greater),
+            Self::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+/// Follow path steps from a value. Missing fields and indexes give null, as in
+/// jq, and iterating over null gives nothing, so optional arrays can be skipped.
+fn follow(steps: &[Step], value: &Value) -> Result<Vec<Value>, String> {
+    steps.iter().try_fold(vec![value.clone()], |values, step| {
+        let mut results = Vec::new();
+        for value in values {
+            match (step, value) {
+                (Step::Iterate, Value::Array(items)) => results.extend(items),
+                (Step::Iterate, Value::Object(map)) => results.extend(map.into_values()),
+                (Step::Iterate, Value::Null) => {}
+                (Step::Iterate, value) => return Err(format!("Cannot iterate over {}", type_name(&value))),
+                (_, Value::Null) => results.push(Value::Null),
+                (Step::Field(name), Value::Object(mut map)) => {
+                    results.push(map.remove(name).unwrap_or(Value::Null));
+                }
+                (Step::Index(index), Value::Array(mut items)) => {
+                    let index = resolve_index(*index, items.len());
+                    results.push(if index < items.len() { items.swap_remove(index) } else { Value::Null });
+                }
+                (Step::Slice(start, end), Value::Array(items)) => {
+                    let (start, end) = resolve_range(*start, *end, items.len());
+                    results.push(Value::Array(items[start..end].to_vec()));
+                }
+                (Step::Slice(start, end), Value::String(text)) => {
+                    let chars: Vec<char> = text.chars().collect();
+                    let (start, end) = resolve_range(*start, *end, chars.len());
+                    results.push(Value::String(chars[start..end].iter().collect()));
+                }
+                (Step::Field(name), value) => {
+                    return Err(format!("Cannot get field {:?} of {}", name, type_name(&value)));
+                }
+                (_, value) => return Err(format!("Cannot index {} with a number", type_name(&value))),
+            }
+        }
+        Ok(results)
+    })
+}
+
+/// Turn a possibly negative index into one from the start. Indexes before
+/// the start end up past the end, so they find nothing.
+fn resolve_index(index: i64, len: usize) -> usize {
+    if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize).unwrap_or(usize::MAX)
+    } else {
+        index as usize
+    }
+}
+
+/// Turn possibly negative slice bounds into a range within `len`
+fn resolve_range(start: Option<i64>, end: Option<i64>, len: usize) -> (usize, usize) {
+    let clamp = |bound: i64| if bound < 0 { len.saturating_sub(bound.unsigned_abs() as usize) } else { (bound as usize).min(len) };
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp);
+    (start, end.max(start))
+}
+
+/// Order two values the way jq does: null, false, true, numbers, strings,
+/// arrays, objects. Arrays and objects only compare as equal or not.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    let rank = |value: &Value| match value {
+        Value::Null => 0,
+        Value::Bool(false) => 1,
+        Value::Bool(true) => 2,
+        Value::Number(_) => 3,
+        Value::String(_) => 4,
+        Value::Array(_) => 5,
+        Value::Object(_) => 6,
+    };
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Array(_), Value::Array(_)) | (Value::Object(_), Value::Object(_)) => (a == b).then_some(Ordering::Equal),
+        _ => Some(rank(a).cmp(&rank(b))),
+    }
+}
+
+/// Describe the type of a value, for error messages
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn filter(&mut self) -> Result<Filter, String> {
+        self.skip_whitespace();
+        if self.eat_word("select") {
+            self.skip_whitespace();
+            self.expect('(')?;
+            let condition = self.or()?;
+            self.skip_whitespace();
+            self.expect(')')?;
+            Ok(Filter::Select(condition))
+        } else if self.eat_word("keys") {
+            Ok(Filter::Keys)
+        } else if matches!(self.peek(), Some('.' | '$')) {
+            Ok(Filter::Path(self.path()?))
+        } else {
+            Err(self.error("a path, select(...) or keys"))
+        }
+    }
+    
+    fn path(&mut self) -> Result<Vec<Step>, String> {
+        let mut steps = Vec::new();
+        let path_start = self.pos;
+        // JSONPath's root, which may be followed by a bracket straight away
+        let root = self.eat('$');
+        if !root && self.peek() != Some('.') {
+            return Err(self.error("a path"));
+        }
+        
+        loop {
+            if self.eat('.') {
+                match self.peek() {
+                    Some(c) if c.is_alphabetic() || c == '_' => {
+                        let start = self.pos;
+                        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                            self.pos += 1;
+                        }
+                        steps.push(Step::Field(self.chars[start..self.pos].iter().collect()));
+                    }
+                    Some('"') => steps.push(Step::Field(self.string()?)),
+                    Some('[') => {}
+                    Some('.') => return Err(format!("Recursive descent (`..`) at position {} isn't supported", self.pos)),
+                    // A lone `.` is the input itself
+                    _ if self.pos == path_start + 1 => {}
+                    _ => return Err(self.error("a field name")),
+                }
+            } else if self.eat('[') {
+                self.skip_whitespace();
+                let step = if self.eat(']') {
+                    Step::Iterate
+                } else if self.eat('*') {
+                    self.expect(']')?;
+                    Step::Iterate
+                } else if self.peek() == Some('"') {
+                    let name = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(']')?;
+                    Step::Field(name)
+                } else {
+                    let start = self.integer()?;
+                    self.skip_whitespace();
+                    let step = if self.eat(':') {
+                        self.skip_whitespace();
+                        Step::Slice(start, self.integer()?)
+                    } else {
+                        Step::Index(start.ok_or_else(|| self.error("an index"))?)
+                    };
+                    self.skip_whitespace();
+                    self.expect(']')?;
+                    step
+                };
+                steps.push(step);
+            } else {
+                return Ok(steps);
+            }
+        }
+    }
+    
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.eat_keyword("or") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+    
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.comparison()?;
+        while self.eat_keyword("and") {
+            condition = Condition::And(Box::new(condition), Box::new(self.comparison()?));
+        }
+        Ok(condition)
+    }
+    
+    fn comparison(&mut self) -> Result<Condition, String> {
+        let left = self.operand()?;
+        self.skip_whitespace();
+        let op = [("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)]
+            .into_iter()
+            .find(|(token, _)| self.eat_str(token));
+        match op {
+            Some((_, op)) => Ok(Condition::Compare(left, op, self.operand()?)),
+            None => Ok(Condition::Truthy(left)),
+        }
+    }
+    
+    fn operand(&mut self) -> Result<Operand, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('.' | '$') => Ok(Operand::Path(self.path()?)),
+            Some('"') => Ok(Operand::Literal(Value::String(self.string()?))),
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+                    self.pos += 1;
+                }
+                let token: String = self.chars[start..self.pos].iter().collect();
+                match serde_json::from_str::<Value>(&token) {
+                    Ok(value) if !token.is_empty() => Ok(Operand::Literal(value)),
+                    _ => {
+                        self.pos = start;
+                        Err(self.error("a path or a value"))
+                    }
+                }
+            }
+        }
+    }
+    
+    /// A JSON string literal, escapes and all
+    fn string(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        self.expect('"')?;
+        let mut escaped = false;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '"' if !escaped => {
+                    let literal: String = self.chars[start..self.pos].iter().collect();
+                    return serde_json::from_str(&literal).map_err(|e| format!("Invalid string at position {}: {}", start + 1, e));
+                }
+                '\\' => escaped = !escaped,
+                _ => escaped = false,
+            }
+        }
+        Err(format!("Unterminated string at position {}", start + 1))
+    }
+    
+    /// An optional, possibly negative integer
+    fn integer(&mut self) -> Result<Option<i64>, String> {
+        let start = self.pos;
+        self.eat('-');
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Ok(None);
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().map(Some).map_err(|_| format!("Invalid index at position {}", start + 1))
+    }
+    
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    
+    fn at_end(&self) -> bool {
+        self.pos == self.chars.len()
+    }
+    
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+    
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    
+    fn eat_str(&mut self, token: &str) -> bool {
+        let len = token.chars().count();
+        let found = self.chars.get(self.pos..self.pos + len).is_some_and(|chars| chars.iter().copied().eq(token.chars()));
+        if found {
+            self.pos += len;
+        }
+        found
+    }
+    
+    /// Consume a word, unless it is only the start of a longer one
+    fn eat_word(&mut self, word: &str) -> bool {
+        let start = self.pos;
+        if !self.eat_str(word) {
+            return false;
+        }
+        if self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos = start;
+            return false;
+        }
+        true
+    }
+    
+    /// Consume a word after whitespace, leaving the position alone otherwise
+    fn eat_keyword(&mut self, word: &str) -> bool {
+        let start = self.pos;
+        self.skip_whitespace();
+        if self.eat_word(word) {
+            return true;
+        }
+        self.pos = start;
+        false
+    }
+    
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("`{}`", c)))
+        }
+    }
+    
+    fn error(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(c) => format!("Expected {} at position {}, found `{}`", expected, self.pos + 1, c),
+            None => format!("Expected {} at the end of the query", expected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn run(expr: &str, input: &Value) -> Vec<Value> {
+        Query::parse(expr).unwrap().run(input).unwrap()
+    }
+    
+    #[test]
+    fn test_paths() {
+        let input = json!({"items": [{"name": "a", "n": 1}, {"name": "b", "n": 5}, {"name": "c"}], "my key": true});
+        assert_eq!(run(".", &input), vec![input.clone()]);
+        assert_eq!(run(".items[1].name", &input), vec![json!("b")]);
+        assert_eq!(run(".items[-1].name", &input), vec![json!("c")]);
+        assert_eq!(run(".items[5]", &input), vec![Value::Null]);
+        assert_eq!(run(".items[-2:] | .[].name", &input), vec![json!("b"), json!("c")]);
+        assert_eq!(run(".items[:2] | .[].n", &input), vec![json!(1), json!(5)]);
+        assert_eq!(run(".items[].name", &input), vec![json!("a"), json!("b"), json!("c")]);
+        assert_eq!(run("$.items[*].name", &input), run(".items[].name", &input));
+        assert_eq!(run(r#"."my key""#, &input), vec![json!(true)]);
+        assert_eq!(run(r#".["my key"]"#, &input), vec![json!(true)]);
+        assert_eq!(run(".items[0] | keys", &input), vec![json!(["n", "name"])]);
+    }
+    
+    #[test]
+    fn test_select() {
+        let input = json!([{"name": "a", "n": 1}, {"name": "b", "n": 5.0}, {"name": "c", "tags": ["x"]}]);
+        assert_eq!(run(".[] | select(.n > 2) | .name", &input), vec![json!("b")]);
+        assert_eq!(run(".[] | select(.n == 5) | .name", &input), vec![json!("b")]);
+        assert_eq!(run(r#".[] | select(.name != "a" and .tags) | .name"#, &input), vec![json!("c")]);
+        assert_eq!(run(r#".[] | select(.tags[] == "x" or .n <= 1) | .name"#, &input), vec![json!("a"), json!("c")]);
+    }
+    
+    #[test]
+    fn test_errors() {
+        assert_eq!(Query::parse(".items[").unwrap_err(), "Expected an index at the end of the query");
+        assert_eq!(Query::parse("items").unwrap_err(), "Expected a path, select(...) or keys at position 1, found `i`");
+        assert_eq!(Query::parse(".a b").unwrap_err(), "Expected `|` at position 4, found `b`");
+        assert_eq!(Query::parse("..").unwrap_err(), "Recursive descent (`..`) at position 1 isn't supported");
+        assert_eq!(Query::parse(".a..b").unwrap_err(), "Recursive descent (`..`) at position 3 isn't supported");
+        assert_eq!(Query::parse(".a.").unwrap_err(), "Expected a field name at the end of the query");
+        assert_eq!(Query::parse("$.").unwrap_err(), "Expected a field name at the end of the query");
+        assert_eq!(
+            Query::parse(".a.b").unwrap().run(&json!({"a": "text"})).unwrap_err(),
+            "Cannot get field \"b\" of a string"
+        );
+        assert!(Query::parse(".[]").unwrap().run(&json!(3)).is_err());
+    }
+}
@@ -6,8 +6,11 @@ use eframe::egui;
 use std::sync::{Arc, Mutex};
 
 use crate::recipe::RecipeManager;
+use crate::backend::{ArboardBackend, ClipboardBackend, Selection};
 use crate::clipboard::ClipboardManager;
-use crate::config::{Config, HistoryManager, HistoryEntry};
+use crate::config::Config;
+use crate::sensitive;
+use crate::history::{HistoryManager, HistoryEntry, HistoryKey};
 use egui_phosphor::regular::*;
 
 /// Quick Menu application state
@@ -20,6 +23,8 @@ pub struct QuickMenu {
     should_close: bool,
     /// First frame flag for focus
     first_frame: bool,
+    /// Why the last selected recipe could not be applied
+    error: Option<String>,
 }
 
 impl QuickMenu {
@@ -30,6 +35,7 @@ impl QuickMenu {
             search_query: String::new(),
             should_close: false,
             first_frame: true,
+            error: None,
         }
     }
     
@@ -125,27 +131,38 @@ impl eframe::App for QuickMenu {
                         let label = format!("{} {}", icon, recipe.name);
                         
                         if ui.button(label).clicked() {
-                            if let Ok(text) = ClipboardManager::get_text() {
-                                let transformed = recipe.apply(&text);
+                            if let Ok(content) = ArboardBackend::CLIPBOARD.get_content(recipe.uses_html()) {
+                                let text = &content.text;
+                                let transformed = match recipe.try_apply_content(&content) {
+                                    Ok(transformed) => transformed,
+                                    Err(e) => {
+                                        // Stay open so the problem can be read
+                                        self.error = Some(format!("{}: {}", recipe.name, e));
+                                        continue;
+                                    }
+                                };
                                 
                                 // Save to history
+                                // (skipped for secrets, and while history is locked behind a passphrase)
                                 if let Ok(config) = Config::load() {
-                                    if config.keep_history {
-                                        if let Ok(mut hm) = HistoryManager::new(config.max_history_size) {
-                                            let entry = HistoryEntry {
-                                                original: text.clone(),
-                                                transformed: Some(transformed.clone()),
-                                                recipe_id: Some(recipe.id.to_string()),
-                                                recipe_name: Some(recipe.name.clone()),
-                                                timestamp: chrono::Utc::now(),
-                                            };
+                                    let sensitive = config.detect_sensitive
+                                        && sensitive::detect_clipboard(&ArboardBackend::CLIPBOARD, text).is_some();
+                                    if config.keep_history && !sensitive {
+                                        let key = HistoryKey::from_config(&config).ok().flatten();
+                                        if let Some(mut hm) = key.and_then(|key| HistoryManager::new(config.max_history_size, &key).ok()) {
+                                            let entry = HistoryEntry::new(
+                                                text.clone(),
+                                                Some(transformed.text.clone()),
+                                                Some(&recipe),
+                                            );
                                             let _ = hm.add(entry);
                                         }
                                     }
                                 }
                                 
-                                // Use non-blocking clipboard set so we can close immediately
-                                let _ = ClipboardManager::set_text_background(&transformed);
+                                // Hand the result to the background service (or set it without
+                                // blocking) so we can close immediately
+                                let _ = ClipboardManager::set_content_background(&transformed, Selection::Clipboard);
                             }
                             self.should_close = true;
                         }
@@ -153,6 +170,10 @@ impl eframe::App for QuickMenu {
                 }
             });
             
+            if let Some(ref error) = self.error {
+                ui.colored_label(ui.visuals().error_fg_color, format!("{} {}", WARNING, error));
+            }
+            
             ui.add_space(5.0);
             ui.separator();
             ui.horizontal(|ui| {
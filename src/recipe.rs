@@ -6,10 +6,18 @@
 use std::path::PathBuf;
 use std::fs;
 use anyhow::{Result, Context};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+use crate::backend::ClipboardContent;
+use crate::classify::{classify, ContentType};
+use crate::formats::{self, DataFormat};
+use crate::html;
+use crate::images::{self, ClipboardImage, ImageFormat};
+use crate::query::Query;
+use crate::tables::{self, TableFormat};
 use crate::transformers;
 
 /// Available transformation types
@@ -43,6 +51,18 @@ pub enum Transformation {
     SplitToLines { delimiter: String },
     WrapLines { width: usize },
     
+    // Table operations, reading CSV, TSV or Markdown tables
+    ConvertTable { to: TableFormat },
+    TableToSql { table: String },
+    
+    // Column operations on delimited text, with columns numbered from 1
+    SelectColumns { delimiter: char, columns: Vec<usize> },
+    DropColumns { delimiter: char, columns: Vec<usize> },
+    SortByColumn { delimiter: char, column: usize, numeric: bool },
+    DedupeByColumn { delimiter: char, column: usize },
+    TransposeColumns { delimiter: char },
+    AlignColumns { delimiter: char },
+    
     // Character cleanup
     FixSmartQuotes,
     RemoveNonAscii,
@@ -67,13 +87,32 @@ pub enum Transformation {
     // Extraction
     ExtractNumbers,
     
+    // JSON operations
+    JsonPrettyPrint { indent: usize },
+    JsonMinify,
+    JsonSortKeys,
+    JsonValidate,
+    JsonQuery { expr: String, raw: bool },
+    
+    // Data format conversion, detecting the source format without `from`
+    ConvertFormat { from: Option<DataFormat>, to: DataFormat },
+    
     // HTML operations
     EncodeHtmlEntities,
     DecodeHtmlEntities,
+    SanitizeHtml { allowed_tags: Vec<String> },
+    HtmlToMarkdown,
+    HtmlToPlainText { keep_links: bool },
     
     // URL operations
     Slugify,
     
+    // Image operations
+    ResizeImage { max_dimension: u32 },
+    ConvertImage { format: ImageFormat },
+    StripImageMetadata,
+    GrayscaleImage,
+    
     // Custom regex replacement
     RegexReplace { pattern: String, replacement: String },
     
@@ -85,6 +124,83 @@ pub enum Transformation {
     AddSuffix { suffix: String },
     RemovePrefix { prefix: String },
     RemoveSuffix { suffix: String },
+    
+    // Conditional steps, applied only when the text at this point matches
+    When { condition: Condition, steps: Vec<Transformation> },
+}
+
+/// A clipboard flavour, which recipes can read and write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Flavor {
+    /// Plain text
+    #[default]
+    Text,
+    /// HTML, always written together with a plain-text version
+    Html,
+}
+
+impl Flavor {
+    /// Get a human-readable name for the flavour
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Text => "Plain Text",
+            Self::Html => "HTML",
+        }
+    }
+}
+
+/// Condition for applying conditional steps
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Condition {
+    /// The text is of this content type
+    Is { content_type: ContentType },
+    /// The text is not of this content type
+    IsNot { content_type: ContentType },
+    /// The text matches a regex
+    Matches { pattern: String },
+    /// The text does not match a regex
+    DoesNotMatch { pattern: String },
+}
+
+impl Condition {
+    /// Get a human-readable description of the condition
+    pub fn label(&self) -> String {
+        match self {
+            Self::Is { content_type } => format!("If {}", content_type.display_name()),
+            Self::IsNot { content_type } => format!("Unless {}", content_type.display_name()),
+            Self::Matches { pattern } => format!("If matches /{}/", pattern),
+            Self::DoesNotMatch { pattern } => format!("Unless matches /{}/", pattern),
+        }
+    }
+    
+    /// The regex pattern this condition checks, if any
+    fn pattern(&self) -> Option<&str> {
+        match self {
+            Self::Matches { pattern } | Self::DoesNotMatch { pattern } => Some(pattern),
+            Self::Is { .. } | Self::IsNot { .. } => None,
+        }
+    }
+    
+    /// Check that the condition is usable
+    pub fn validate(&self) -> Result<(), String> {
+        match self.pattern() {
+            Some(pattern) => Regex::new(pattern)
+                .map(|_| ())
+                .map_err(|e| format!("Invalid condition regex: {}", e)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Check the condition against text, using its compiled pattern.
+    /// An invalid regex never matches.
+    fn matches(&self, text: &str, pattern: Option<&Regex>) -> bool {
+        match self {
+            Self::Is { content_type } => classify(text) == *content_type,
+            Self::IsNot { content_type } => classify(text) != *content_type,
+            Self::Matches { .. } => pattern.is_some_and(|re| re.is_match(text)),
+            Self::DoesNotMatch { .. } => pattern.is_some_and(|re| !re.is_match(text)),
+        }
+    }
 }
 
 impl Transformation {
@@ -113,6 +229,19 @@ impl Transformation {
             Self::JoinLines { .. } => "Join Lines",
             Self::SplitToLines { .. } => "Split to Lines",
             Self::WrapLines { .. } => "Wrap Lines",
+            Self::ConvertTable { to: TableFormat::Csv } => "Table → CSV",
+            Self::ConvertTable { to: TableFormat::Tsv } => "Table → TSV",
+            Self::ConvertTable { to: TableFormat::Markdown } => "Table → Markdown",
+            Self::ConvertTable { to: TableFormat::Ascii } => "Table → ASCII",
+            Self::ConvertTable { to: TableFormat::Html } => "Table → HTML",
+            Self::ConvertTable { to: TableFormat::Json } => "Table → JSON",
+            Self::TableToSql { .. } => "Table → SQL INSERT",
+            Self::SelectColumns { .. } => "Select Columns",
+            Self::DropColumns { .. } => "Drop Columns",
+            Self::SortByColumn { .. } => "Sort by Column",
+            Self::DedupeByColumn { .. } => "Deduplicate by Column",
+            Self::TransposeColumns { .. } => "Transpose Rows/Columns",
+            Self::AlignColumns { .. } => "Align Columns",
             Self::FixSmartQuotes => "Fix Smart Quotes",
             Self::RemoveNonAscii => "Remove Non-ASCII",
             Self::NormalizeUnicode => "Normalize Unicode",
@@ -127,15 +256,34 @@ impl Transformation {
             Self::ToUnixLineEndings => "Unix Line Endings (LF)",
             Self::ToWindowsLineEndings => "Windows Line Endings (CRLF)",
             Self::ExtractNumbers => "Extract Numbers",
+            Self::JsonPrettyPrint { .. } => "Pretty-Print JSON",
+            Self::JsonMinify => "Minify JSON",
+            Self::JsonSortKeys => "Sort JSON Keys",
+            Self::JsonValidate => "Validate JSON",
+            Self::JsonQuery { .. } => "JSON Query",
+            Self::ConvertFormat { to: DataFormat::Json, .. } => "Convert to JSON",
+            Self::ConvertFormat { to: DataFormat::Yaml, .. } => "Convert to YAML",
+            Self::ConvertFormat { to: DataFormat::Toml, .. } => "Convert to TOML",
+            Self::ConvertFormat { to: DataFormat::QueryString, .. } => "Convert to Query String",
             Self::EncodeHtmlEntities => "Encode HTML Entities",
             Self::DecodeHtmlEntities => "Decode HTML Entities",
+            Self::SanitizeHtml { .. } => "Sanitize HTML",
+            Self::HtmlToMarkdown => "HTML → Markdown",
+            Self::HtmlToPlainText { .. } => "HTML → Plain Text",
             Self::Slugify => "Slugify (URL-safe)",
+            Self::ResizeImage { .. } => "Resize Image",
+            Self::ConvertImage { format: ImageFormat::Png } => "Convert Image to PNG",
+            Self::ConvertImage { format: ImageFormat::Jpeg } => "Convert Image to JPEG",
+            Self::ConvertImage { format: ImageFormat::Bmp } => "Convert Image to BMP",
+            Self::StripImageMetadata => "Strip Image Metadata",
+            Self::GrayscaleImage => "Grayscale Image",
             Self::RegexReplace { .. } => "Regex Replace",
             Self::FindReplace { .. } => "Find & Replace",
             Self::AddPrefix { .. } => "Add Prefix",
             Self::AddSuffix { .. } => "Add Suffix",
             Self::RemovePrefix { .. } => "Remove Prefix",
             Self::RemoveSuffix { .. } => "Remove Suffix",
+            Self::When { .. } => "Conditional Steps",
         }
     }
     
@@ -150,18 +298,95 @@ impl Transformation {
             Self::ReverseLines | Self::AddLineNumbers | Self::RemoveLineNumbers |
             Self::RemoveLineNumbersStuck | Self::JoinLines { .. } | 
             Self::SplitToLines { .. } | Self::WrapLines { .. } => "Line Operations",
+            Self::ConvertTable { .. } | Self::TableToSql { .. } => "Tables",
+            Self::SelectColumns { .. } | Self::DropColumns { .. } | Self::SortByColumn { .. } |
+            Self::DedupeByColumn { .. } | Self::TransposeColumns { .. } |
+            Self::AlignColumns { .. } => "Columns",
             Self::FixSmartQuotes | Self::RemoveNonAscii | Self::NormalizeUnicode |
             Self::RemoveEmojis | Self::StripFormatting => "Character Cleanup",
             Self::TabsToSpaces { .. } | Self::SpacesToTabs { .. } => "Indentation",
             Self::RemoveUrls | Self::RemoveEmails | Self::RemovePhoneNumbers |
             Self::RemoveMarkdown => "Content Removal",
             Self::ToUnixLineEndings | Self::ToWindowsLineEndings => "Line Endings",
-            Self::ExtractNumbers => "Extraction",
-            Self::EncodeHtmlEntities | Self::DecodeHtmlEntities => "HTML",
+            Self::ExtractNumbers | Self::JsonQuery { .. } => "Extraction",
+            Self::JsonPrettyPrint { .. } | Self::JsonMinify | Self::JsonSortKeys |
+            Self::JsonValidate => "JSON",
+            Self::ConvertFormat { .. } => "Data Formats",
+            Self::EncodeHtmlEntities | Self::DecodeHtmlEntities | Self::SanitizeHtml { .. } |
+            Self::HtmlToMarkdown | Self::HtmlToPlainText { .. } => "HTML",
             Self::Slugify => "URL",
+            Self::ResizeImage { .. } | Self::ConvertImage { .. } |
+            Self::StripImageMetadata | Self::GrayscaleImage => "Images",
             Self::RegexReplace { .. } | Self::FindReplace { .. } => "Search & Replace",
             Self::AddPrefix { .. } | Self::AddSuffix { .. } |
             Self::RemovePrefix { .. } | Self::RemoveSuffix { .. } => "Prefix/Suffix",
+            Self::When { .. } => "Conditions",
+        }
+    }
+    
+    /// Check that this transformation's parameters are usable
+    /// Returns a description of the problem otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::RegexReplace { pattern, .. } => Regex::new(pattern)
+                .map(|_| ())
+                .map_err(|e| format!("Invalid regex: {}", e)),
+            Self::JsonQuery { expr, .. } => Query::parse(expr)
+                .map(|_| ())
+                .map_err(|e| format!("Invalid query: {}", e)),
+            Self::FindReplace { find, .. } if find.is_empty() => {
+                Err("Find text must not be empty".to_string())
+            }
+            Self::SplitToLines { delimiter } if delimiter.is_empty() => {
+                Err("Delimiter must not be empty".to_string())
+            }
+            Self::WrapLines { width: 0 } => {
+                Err("Wrap width must be greater than 0".to_string())
+            }
+            Self::TableToSql { table } if table.trim().is_empty() => {
+                Err("Table name must not be empty".to_string())
+            }
+            Self::SelectColumns { columns, .. } | Self::DropColumns { columns, .. } if columns.is_empty() => {
+                Err("Pick at least one column".to_string())
+            }
+            Self::SelectColumns { columns, .. } | Self::DropColumns { columns, .. } if columns.contains(&0) => {
+                Err("Columns are numbered from 1".to_string())
+            }
+            Self::SortByColumn { column: 0, .. } | Self::DedupeByColumn { column: 0, .. } => {
+                Err("Columns are numbered from 1".to_string())
+            }
+            Self::SpacesToTabs { spaces_per_tab: 0 } => {
+                Err("Spaces per tab must be greater than 0".to_string())
+            }
+            Self::ResizeImage { max_dimension: 0 } => {
+                Err("Maximum dimension must be greater than 0".to_string())
+            }
+            Self::When { condition, steps } => {
+                condition.validate()?;
+                for (i, step) in steps.iter().enumerate() {
+                    step.validate().map_err(|message| nested_step_error(i, step.display_name(), &message))?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    /// Sanitize HTML down to the default set of allowed tags
+    pub fn sanitize_html() -> Self {
+        Self::SanitizeHtml {
+            allowed_tags: html::DEFAULT_ALLOWED_TAGS.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+    
+    /// Flavour of this step's result, given the flavour of its input.
+    /// HTML conversions change it; every other step keeps it.
+    pub fn result_flavor(&self, input: Flavor) -> Flavor {
+        match self {
+            Self::SanitizeHtml { .. } => Flavor::Html,
+            Self::HtmlToMarkdown | Self::HtmlToPlainText { .. } => Flavor::Text,
+            Self::When { steps, .. } => steps.iter().fold(input, |flavor, step| step.result_flavor(flavor)),
+            _ => input,
         }
     }
     
@@ -204,11 +429,25 @@ impl Transformation {
             Self::ToUnixLineEndings => transformers::to_unix_line_endings(text),
             Self::ToWindowsLineEndings => transformers::to_windows_line_endings(text),
             Self::ExtractNumbers => transformers::extract_numbers(text),
+            // Text the JSON, data format, table and column steps can't parse is left alone
+            Self::JsonPrettyPrint { .. } | Self::JsonMinify | Self::JsonSortKeys |
+            Self::JsonValidate | Self::JsonQuery { .. } | Self::ConvertFormat { .. } |
+            Self::ConvertTable { .. } | Self::TableToSql { .. } |
+            Self::SelectColumns { .. } | Self::DropColumns { .. } | Self::SortByColumn { .. } |
+            Self::DedupeByColumn { .. } | Self::TransposeColumns { .. } | Self::AlignColumns { .. } => {
+                self.try_apply(text).unwrap_or_else(|_| text.to_string())
+            }
             Self::EncodeHtmlEntities => transformers::encode_html_entities(text),
             Self::DecodeHtmlEntities => transformers::decode_html_entities(text),
+            Self::SanitizeHtml { allowed_tags } => html::sanitize(text, allowed_tags),
+            Self::HtmlToMarkdown => html::to_markdown(text),
+            Self::HtmlToPlainText { keep_links } => html::to_plain_text(text, *keep_links),
             Self::Slugify => transformers::slugify(text),
+            // Image steps leave text alone
+            Self::ResizeImage { .. } | Self::ConvertImage { .. } |
+            Self::StripImageMetadata | Self::GrayscaleImage => text.to_string(),
             Self::RegexReplace { pattern, replacement } => {
-                if let Ok(re) = regex::Regex::new(pattern) {
+                if let Ok(re) = Regex::new(pattern) {
                     re.replace_all(text, replacement.as_str()).to_string()
                 } else {
                     text.to_string()
@@ -223,6 +462,80 @@ impl Transformation {
             Self::RemoveSuffix { suffix } => {
                 text.strip_suffix(suffix).unwrap_or(text).to_string()
             }
+            // Compiles the condition's pattern once for all nested steps
+            Self::When { .. } => CompiledStep::new(self).0.apply(text),
+        }
+    }
+    
+    /// Apply this transformation to text, failing if the step can't process
+    /// it (e.g. JSON steps on text that isn't JSON)
+    pub fn try_apply(&self, text: &str) -> Result<String, String> {
+        match self {
+            Self::JsonPrettyPrint { indent } => transformers::json_pretty_print(text, *indent),
+            Self::JsonMinify => transformers::json_minify(text),
+            Self::JsonSortKeys => transformers::json_sort_keys(text),
+            Self::JsonValidate => transformers::json_validate(text).map(|()| text.to_string()),
+            Self::JsonQuery { expr, raw } => transformers::json_query(text, expr, *raw),
+            Self::ConvertFormat { from, to } => formats::convert(text, *from, *to),
+            Self::ConvertTable { to } => tables::convert(text, *to),
+            Self::TableToSql { table } => tables::to_sql(text, table),
+            Self::SelectColumns { delimiter, columns } => tables::select_columns(text, *delimiter, columns),
+            Self::DropColumns { delimiter, columns } => tables::drop_columns(text, *delimiter, columns),
+            Self::SortByColumn { delimiter, column, numeric } => {
+                tables::sort_by_column(text, *delimiter, *column, *numeric)
+            }
+            Self::DedupeByColumn { delimiter, column } => tables::dedupe_by_column(text, *delimiter, *column),
+            Self::TransposeColumns { delimiter } => tables::transpose(text, *delimiter),
+            Self::AlignColumns { delimiter } => tables::align_columns(text, *delimiter),
+            Self::When { .. } => CompiledStep::new(self).0.try_apply(text),
+            _ => Ok(self.apply(text)),
+        }
+    }
+    
+    /// Whether this step works on images rather than text
+    pub fn is_image_step(&self) -> bool {
+        matches!(
+            self,
+            Self::ResizeImage { .. } | Self::ConvertImage { .. } | Self::StripImageMetadata | Self::GrayscaleImage
+        )
+    }
+    
+    /// Apply this transformation to an image. Text steps leave images alone,
+    /// and so do conditional steps, whose conditions look at text.
+    pub fn apply_image(&self, image: &ClipboardImage) -> Result<ClipboardImage> {
+        match self {
+            Self::ResizeImage { max_dimension } => images::resize(image, *max_dimension),
+            Self::ConvertImage { format } => images::convert(image, *format),
+            Self::StripImageMetadata => images::strip_metadata(image),
+            Self::GrayscaleImage => images::grayscale(image),
+            _ => Ok(image.clone()),
+        }
+    }
+}
+
+/// Describe a problem with a step nested in conditional steps
+fn nested_step_error(index: usize, name: &str, message: &str) -> String {
+    format!("step {} ({}): {}", index + 1, name, message)
+}
+
+/// A problem with one step of a recipe
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Step {} ({}): {message}", .index + 1, .name)]
+pub struct StepError {
+    /// Index of the step in the recipe
+    pub index: usize,
+    /// Display name of the step's transformation
+    pub name: &'static str,
+    /// What is wrong with the step
+    pub message: String,
+}
+
+impl StepError {
+    fn new(index: usize, transformation: &Transformation, message: String) -> Self {
+        Self {
+            index,
+            name: transformation.display_name(),
+            message,
         }
     }
 }
@@ -248,6 +561,13 @@ pub struct Recipe {
     pub modified_at: DateTime<Utc>,
     /// Icon for the recipe (emoji or text)
     pub icon: Option<String>,
+    /// Clipboard flavour the steps read. Without HTML on the clipboard,
+    /// HTML recipes read the plain text as HTML.
+    #[serde(default)]
+    pub input: Flavor,
+    /// Clipboard flavour the recipe writes
+    #[serde(default)]
+    pub output: Flavor,
 }
 
 impl Recipe {
@@ -264,28 +584,294 @@ impl Recipe {
             created_at: now,
             modified_at: now,
             icon: None,
+            input: Flavor::Text,
+            output: Flavor::Text,
         }
     }
     
+    /// Check if the recipe has a name (case-insensitive) or ID
+    pub fn matches_name(&self, name_or_id: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name_or_id) || self.id.to_string() == name_or_id
+    }
+    
     /// Add a transformation to the recipe
     pub fn add_transformation(&mut self, transformation: Transformation) {
         self.transformations.push(transformation);
         self.modified_at = Utc::now();
     }
     
-    /// Apply all transformations to text
+    /// Apply all transformations to text, compiling the patterns for this
+    /// one use. Use `compile` to apply the recipe repeatedly.
     pub fn apply(&self, text: &str) -> String {
-        let mut result = text.to_string();
-        for transformation in &self.transformations {
-            result = transformation.apply(&result);
+        self.compile().apply(text)
+    }
+    
+    /// Apply all transformations to text, failing on the first invalid step
+    /// or one that can't process the text, instead of skipping it
+    pub fn try_apply(&self, text: &str) -> Result<String, StepError> {
+        self.compile().try_apply(text)
+    }
+    
+    /// Apply all transformations to clipboard content, reading and writing
+    /// the recipe's flavours
+    pub fn try_apply_content(&self, content: &ClipboardContent) -> Result<ClipboardContent, StepError> {
+        self.compile().try_apply_content(content)
+    }
+    
+    /// Apply the image steps to an image, failing on the first invalid step
+    /// or one that can't process the image
+    pub fn try_apply_image(&self, image: &ClipboardImage) -> Result<ClipboardImage, StepError> {
+        if let Some(error) = self.validate().into_iter().next() {
+            return Err(error);
         }
-        result
+        apply_image_steps(&self.transformations, image)
+    }
+    
+    /// Whether the recipe reads or writes HTML
+    pub fn uses_html(&self) -> bool {
+        self.input == Flavor::Html || self.output == Flavor::Html
+    }
+    
+    /// Whether the recipe has steps for images
+    pub fn uses_images(&self) -> bool {
+        self.transformations.iter().any(Transformation::is_image_step)
+    }
+    
+    /// Flavour of the steps' result
+    pub fn result_flavor(&self) -> Flavor {
+        self.transformations.iter().fold(self.input, |flavor, step| step.result_flavor(flavor))
+    }
+    
+    /// Check every step of the recipe, returning one diagnostic per invalid step
+    pub fn validate(&self) -> Vec<StepError> {
+        self.transformations.iter()
+            .enumerate()
+            .filter_map(|(i, t)| t.validate().err().map(|message| StepError::new(i, t, message)))
+            .collect()
     }
     
     /// Check if this recipe has any transformations
     pub fn is_empty(&self) -> bool {
         self.transformations.is_empty()
     }
+    
+    /// Compile the recipe's user patterns for repeated use
+    pub fn compile(&self) -> CompiledRecipe {
+        CompiledRecipe::new(self.clone())
+    }
+}
+
+/// A recipe with its regex patterns compiled once, for applying it repeatedly
+/// (e.g. to every clipboard change)
+///
+/// Rebuild it whenever the recipe is edited.
+#[derive(Debug, Clone)]
+pub struct CompiledRecipe {
+    recipe: Recipe,
+    /// The recipe's steps with their patterns compiled
+    steps: Vec<CompiledStep>,
+    /// Problems found while compiling
+    errors: Vec<StepError>,
+}
+
+/// A recipe step with its regex patterns compiled
+#[derive(Debug, Clone)]
+enum CompiledStep {
+    /// A regex replacement with a valid pattern
+    RegexReplace { re: Regex, replacement: String },
+    /// Conditional steps, with the condition's pattern if it has a valid one
+    When { condition: Condition, pattern: Option<Regex>, steps: Vec<CompiledStep> },
+    /// Any other step, applied as is
+    Other(Transformation),
+}
+
+impl CompiledStep {
+    /// Compile a step, along with the problem if it is invalid.
+    /// Patterns are compiled once and kept, rather than validated separately.
+    fn new(transformation: &Transformation) -> (Self, Result<(), String>) {
+        match transformation {
+            Transformation::RegexReplace { pattern, replacement } => match Regex::new(pattern) {
+                Ok(re) => (Self::RegexReplace { re, replacement: replacement.clone() }, Ok(())),
+                Err(e) => (Self::Other(transformation.clone()), Err(format!("Invalid regex: {}", e))),
+            },
+            Transformation::When { condition, steps } => {
+                let (pattern, mut result) = match condition.pattern().map(Regex::new) {
+                    Some(Ok(re)) => (Some(re), Ok(())),
+                    Some(Err(e)) => (None, Err(format!("Invalid condition regex: {}", e))),
+                    None => (None, Ok(())),
+                };
+                
+                let mut compiled = Vec::with_capacity(steps.len());
+                for (i, step) in steps.iter().enumerate() {
+                    let (step_compiled, step_result) = Self::new(step);
+                    if let (Ok(()), Err(message)) = (&result, step_result) {
+                        result = Err(nested_step_error(i, step.display_name(), &message));
+                    }
+                    compiled.push(step_compiled);
+                }
+                
+                let step = Self::When { condition: condition.clone(), pattern, steps: compiled };
+                (step, result)
+            }
+            _ => (Self::Other(transformation.clone()), transformation.validate()),
+        }
+    }
+    
+    /// Apply the step to text
+    fn apply(&self, text: &str) -> String {
+        match self {
+            Self::RegexReplace { re, replacement } => re.replace_all(text, replacement.as_str()).to_string(),
+            Self::When { condition, pattern, steps } => {
+                if condition.matches(text, pattern.as_ref()) {
+                    steps.iter().fold(text.to_string(), |result, step| step.apply(&result))
+                } else {
+                    text.to_string()
+                }
+            }
+            Self::Other(transformation) => transformation.apply(text),
+        }
+    }
+    
+    /// Apply the step to text, failing if it can't process the text
+    fn try_apply(&self, text: &str) -> Result<String, String> {
+        match self {
+            Self::RegexReplace { .. } => Ok(self.apply(text)),
+            Self::When { condition, pattern, steps } => {
+                if condition.matches(text, pattern.as_ref()) {
+                    steps.iter().enumerate().try_fold(text.to_string(), |result, (i, step)| {
+                        step.try_apply(&result).map_err(|message| nested_step_error(i, step.display_name(), &message))
+                    })
+                } else {
+                    Ok(text.to_string())
+                }
+            }
+            Self::Other(transformation) => transformation.try_apply(text),
+        }
+    }
+    
+    /// Display name of the step's transformation
+    fn display_name(&self) -> &'static str {
+        match self {
+            Self::RegexReplace { .. } => "Regex Replace",
+            Self::When { .. } => "Conditional Steps",
+            Self::Other(transformation) => transformation.display_name(),
+        }
+    }
+}
+
+impl CompiledRecipe {
+    /// Compile a recipe
+    pub fn new(recipe: Recipe) -> Self {
+        let mut steps = Vec::with_capacity(recipe.transformations.len());
+        let mut errors = Vec::new();
+        
+        for (i, t) in recipe.transformations.iter().enumerate() {
+            let (step, result) = CompiledStep::new(t);
+            if let Err(message) = result {
+                errors.push(StepError::new(i, t, message));
+            }
+            steps.push(step);
+        }
+        
+        Self { recipe, steps, errors }
+    }
+    
+    /// Get the underlying recipe
+    pub fn recipe(&self) -> &Recipe {
+        &self.recipe
+    }
+    
+    /// Get the diagnostics for the recipe's invalid steps
+    pub fn validate(&self) -> &[StepError] {
+        &self.errors
+    }
+    
+    /// Apply all transformations to text, failing if any step is invalid
+    /// or one can't process the text
+    pub fn try_apply(&self, text: &str) -> Result<String, StepError> {
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone());
+        }
+        let steps = self.steps.iter().zip(&self.recipe.transformations);
+        steps.enumerate().try_fold(text.to_string(), |result, (i, (step, t))| {
+            step.try_apply(&result).map_err(|message| StepError::new(i, t, message))
+        })
+    }
+    
+    /// Apply all transformations to clipboard content, reading and writing
+    /// the recipe's flavours
+    pub fn try_apply_content(&self, content: &ClipboardContent) -> Result<ClipboardContent, StepError> {
+        apply_flavors(&self.recipe, content, |text| self.try_apply(text))
+    }
+    
+    /// Apply the image steps to an image, failing if any step is invalid
+    /// or can't process the image
+    pub fn try_apply_image(&self, image: &ClipboardImage) -> Result<ClipboardImage, StepError> {
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => apply_image_steps(&self.recipe.transformations, image),
+        }
+    }
+    
+    /// Apply all transformations to text
+    pub fn apply(&self, text: &str) -> String {
+        let mut result = text.to_string();
+        for step in &self.steps {
+            result = step.apply(&result);
+        }
+        result
+    }
+}
+
+/// Run the image steps among `steps` on an image, in order
+fn apply_image_steps(steps: &[Transformation], image: &ClipboardImage) -> Result<ClipboardImage, StepError> {
+    let mut result = image.clone();
+    for (i, step) in steps.iter().enumerate() {
+        result = step.apply_image(&result)
+            .map_err(|e| StepError::new(i, step, format!("{:#}", e)))?;
+    }
+    Ok(result)
+}
+
+/// Run a recipe's steps on the flavour it reads, and convert the result to
+/// the flavour it writes
+fn apply_flavors(
+    recipe: &Recipe,
+    content: &ClipboardContent,
+    apply: impl FnOnce(&str) -> Result<String, StepError>,
+) -> Result<ClipboardContent, StepError> {
+    let result = match (recipe.input, &content.html) {
+        (Flavor::Html, Some(html)) => apply(html)?,
+        (Flavor::Html, None) => apply(&html::from_text(&content.text))?,
+        (Flavor::Text, _) => apply(&content.text)?,
+    };
+    
+    Ok(match (recipe.result_flavor(), recipe.output) {
+        (Flavor::Text, Flavor::Text) => ClipboardContent::text(result),
+        (Flavor::Html, Flavor::Text) => ClipboardContent::text(html::to_plain_text(&result, true)),
+        (Flavor::Text, Flavor::Html) => ClipboardContent {
+            html: Some(html::from_text(&result)),
+            text: result,
+        },
+        (Flavor::Html, Flavor::Html) => ClipboardContent {
+            text: html::to_plain_text(&result, true),
+            html: Some(result),
+        },
+    })
+}
+
+impl From<Recipe> for CompiledRecipe {
+    fn from(recipe: Recipe) -> Self {
+        Self::new(recipe)
+    }
+}
+
+impl std::ops::Deref for CompiledRecipe {
+    type Target = Recipe;
+    
+    fn deref(&self) -> &Recipe {
+        &self.recipe
+    }
 }
 
 /// Default built-in recipes
@@ -338,9 +924,12 @@ impl RecipeManager {
         let mut plain_text = Recipe::new("Plain Text");
         plain_text.description = Some("Strip all formatting and normalize whitespace".into());
         plain_text.icon = Some("📝".into());
-        plain_text.add_transformation(Transformation::StripFormatting);
+        // Stripping formatting also collapses whitespace, which would break code
+        plain_text.add_transformation(Transformation::When {
+            condition: Condition::IsNot { content_type: ContentType::Code },
+            steps: vec![Transformation::StripFormatting],
+        });
         plain_text.add_transformation(Transformation::FixSmartQuotes);
-        plain_text.add_transformation(Transformation::NormalizeWhitespace);
         recipes.push(plain_text);
         
         // Clean code recipe
@@ -395,6 +984,24 @@ impl RecipeManager {
         no_emoji.add_transformation(Transformation::RemoveEmojis);
         recipes.push(no_emoji);
         
+        // Web page to Markdown
+        let mut markdown = Recipe::new("Markdown");
+        markdown.description = Some("Turn copied web pages into Markdown".into());
+        markdown.icon = Some("📑".into());
+        markdown.input = Flavor::Html;
+        markdown.add_transformation(Transformation::HtmlToMarkdown);
+        recipes.push(markdown);
+        
+        // Clean rich text
+        let mut clean_html = Recipe::new("Clean HTML");
+        clean_html.description = Some("Keep basic formatting, drop styles and scripts".into());
+        clean_html.icon = Some("🌐".into());
+        clean_html.input = Flavor::Html;
+        clean_html.output = Flavor::Html;
+        clean_html.add_transformation(Transformation::sanitize_html());
+        clean_html.add_transformation(Transformation::FixSmartQuotes);
+        recipes.push(clean_html);
+        
         recipes
     }
     
@@ -424,6 +1031,11 @@ impl RecipeManager {
         self.recipes.iter().find(|r| r.id == id)
     }
     
+    /// Find a recipe by name (case-insensitive) or ID
+    pub fn find(&self, name_or_id: &str) -> Option<&Recipe> {
+        self.recipes.iter().find(|r| r.matches_name(name_or_id))
+    }
+    
     /// Get a mutable recipe by ID
     pub fn get_recipe_mut(&mut self, id: Uuid) -> Option<&mut Recipe> {
         self.recipes.iter_mut().find(|r| r.id == id)
@@ -497,4 +1109,205 @@ mod tests {
         let result = recipe.apply(input);
         assert_eq!(result, "\"hello\" World");
     }
+    
+    #[test]
+    fn test_compiled_recipe_matches_recipe() {
+        let mut recipe = Recipe::new("Test");
+        recipe.add_transformation(Transformation::RegexReplace {
+            pattern: r"(\d+)-(\d+)".to_string(),
+            replacement: "$2-$1".to_string(),
+        });
+        recipe.add_transformation(Transformation::RegexReplace {
+            pattern: "(unclosed".to_string(),
+            replacement: "x".to_string(),
+        });
+        recipe.add_transformation(Transformation::ToUppercase);
+        
+        let input = "order 12-34 shipped";
+        assert_eq!(recipe.compile().apply(input), recipe.apply(input));
+        assert_eq!(recipe.compile().apply(input), "ORDER 34-12 SHIPPED");
+    }
+    
+    #[test]
+    fn test_validate_reports_invalid_steps() {
+        let mut recipe = Recipe::new("Test");
+        recipe.add_transformation(Transformation::TrimLines);
+        recipe.add_transformation(Transformation::RegexReplace {
+            pattern: "(unclosed".to_string(),
+            replacement: String::new(),
+        });
+        recipe.add_transformation(Transformation::WrapLines { width: 0 });
+        recipe.add_transformation(Transformation::FindReplace {
+            find: String::new(),
+            replace: "x".to_string(),
+        });
+        
+        let errors = recipe.validate();
+        assert_eq!(errors.iter().map(|e| e.index).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(errors[0].message.starts_with("Invalid regex"));
+        assert_eq!(errors[1].to_string(), "Step 3 (Wrap Lines): Wrap width must be greater than 0");
+        assert_eq!(recipe.compile().validate(), errors.as_slice());
+        
+        let failed = recipe.try_apply("text").unwrap_err();
+        assert_eq!(failed.index, 1);
+        assert_eq!(recipe.compile().try_apply("text").unwrap_err(), failed);
+        
+        recipe.transformations.drain(1..);
+        assert_eq!(recipe.try_apply("  text  ").unwrap(), "text");
+    }
+    
+    #[test]
+    fn test_conditional_steps() {
+        let mut recipe = Recipe::new("Test");
+        recipe.add_transformation(Transformation::When {
+            condition: Condition::IsNot { content_type: ContentType::Code },
+            steps: vec![Transformation::NormalizeWhitespace],
+        });
+        recipe.add_transformation(Transformation::When {
+            condition: Condition::Matches { pattern: r"^\d+$".to_string() },
+            steps: vec![Transformation::AddPrefix { prefix: "#".to_string() }],
+        });
+        
+        let code = "fn main() {\n    run();\n}";
+        let cases = [
+            ("  some   prose  ", "some prose"),
+            (code, code),
+            ("42", "#42"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(recipe.apply(input), expected);
+            assert_eq!(recipe.compile().apply(input), expected);
+        }
+    }
+    
+    #[test]
+    fn test_default_plain_text_keeps_code() {
+        let recipes = RecipeManager::default_recipes();
+        let plain_text = recipes.iter().find(|r| r.name == "Plain Text").unwrap();
+        
+        let code = "fn main() {\n    if ready {\n        println!(\u{201C}go\u{201D});\n    }\n}";
+        assert_eq!(plain_text.apply(code), "fn main() {\n    if ready {\n        println!(\"go\");\n    }\n}");
+        assert_eq!(plain_text.apply("<b>Some</b>   \u{2018}bold\u{2019}\n\n text"), "Some 'bold' text");
+    }
+    
+    #[test]
+    fn test_json_steps_report_invalid_json() {
+        let mut recipe = Recipe::new("Test");
+        recipe.add_transformation(Transformation::TrimLines);
+        recipe.add_transformation(Transformation::When {
+            condition: Condition::Matches { pattern: "^[{\\[]".to_string() },
+            steps: vec![Transformation::JsonValidate, Transformation::JsonMinify],
+        });
+        
+        assert_eq!(recipe.try_apply("  { \"a\": [1, 2] }").unwrap(), r#"{"a":[1,2]}"#);
+        assert_eq!(recipe.compile().try_apply("not json").unwrap(), "not json");
+        
+        let failed = recipe.try_apply("{\"a\": }").unwrap_err();
+        assert_eq!(
+            failed.to_string(),
+            "Step 2 (Conditional Steps): step 1 (Validate JSON): Invalid JSON: expected value at line 1 column 7"
+        );
+        assert_eq!(recipe.compile().try_apply("{\"a\": }").unwrap_err(), failed);
+        // Applying without checking leaves the text alone
+        assert_eq!(recipe.apply("{\"a\": }"), "{\"a\": }");
+    }
+    
+    #[test]
+    fn test_validate_conditional_steps() {
+        let mut recipe = Recipe::new("Test");
+        recipe.add_transformation(Transformation::When {
+            condition: Condition::DoesNotMatch { pattern: "(unclosed".to_string() },
+            steps: vec![],
+        });
+        recipe.add_transformation(Transformation::When {
+            condition: Condition::Is { content_type: ContentType::Prose },
+            steps: vec![Transformation::TrimLines, Transformation::WrapLines { width: 0 }],
+        });
+        
+        let errors = recipe.validate();
+        assert!(errors[0].message.starts_with("Invalid condition regex"));
+        assert_eq!(
+            errors[1].to_string(),
+            "Step 2 (Conditional Steps): step 2 (Wrap Lines): Wrap width must be greater than 0"
+        );
+        assert_eq!(recipe.compile().validate(), errors.as_slice());
+        
+        // An invalid condition never matches
+        let step = Transformation::When {
+            condition: Condition::DoesNotMatch { pattern: "(unclosed".to_string() },
+            steps: vec![Transformation::ToUppercase],
+        };
+        assert_eq!(step.apply("text"), "text");
+    }
+    
+    #[test]
+    fn test_apply_flavors() {
+        let browser_copy = ClipboardContent {
+            text: "Hello world".to_string(),
+            html: Some(r#"<p style="color:red">Hello <b>world</b></p><script>x()</script>"#.to_string()),
+        };
+        
+        let mut recipe = Recipe::new("Test");
+        recipe.input = Flavor::Html;
+        recipe.add_transformation(Transformation::HtmlToMarkdown);
+        assert_eq!(recipe.try_apply_content(&browser_copy).unwrap(), ClipboardContent::text("Hello **world**"));
+        
+        let mut recipe = Recipe::new("Test");
+        recipe.input = Flavor::Html;
+        recipe.output = Flavor::Html;
+        recipe.add_transformation(Transformation::sanitize_html());
+        let result = recipe.compile().try_apply_content(&browser_copy).unwrap();
+        assert_eq!(result.html.as_deref(), Some("<p>Hello <b>world</b></p>"));
+        assert_eq!(result.text, "Hello world");
+        
+        // Plain text written as HTML keeps its lines
+        let mut recipe = Recipe::new("Test");
+        recipe.output = Flavor::Html;
+        recipe.add_transformation(Transformation::ToUppercase);
+        let result = recipe.try_apply_content(&ClipboardContent::text("a\nb")).unwrap();
+        assert_eq!(result.html.as_deref(), Some("A<br>\nB"));
+        assert_eq!(result.text, "A\nB");
+        
+        // Recipes saved before flavours existed stay plain text
+        let recipe: Recipe = serde_json::from_str(
+            r#"{"id":"5f0c3a5e-7d4b-4c8e-9b1a-2f6d8e0a1b2c","name":"Old","description":null,"transformations":[],"is_active":false,"hotkey":null,"created_at":"2024-01-01T00:00:00Z","modified_at":"2024-01-01T00:00:00Z","icon":null}"#
+        ).unwrap();
+        assert_eq!((recipe.input, recipe.output), (Flavor::Text, Flavor::Text));
+    }
+    
+    #[test]
+    fn test_apply_image() {
+        let image = ClipboardImage::from_rgba(64, 32, vec![200; 64 * 32 * 4]).unwrap();
+        
+        let mut recipe = Recipe::new("Screenshots");
+        recipe.add_transformation(Transformation::TrimLines);
+        recipe.add_transformation(Transformation::ResizeImage { max_dimension: 16 });
+        recipe.add_transformation(Transformation::ConvertImage { format: ImageFormat::Jpeg });
+        assert!(recipe.uses_images());
+        assert_eq!(recipe.apply("  text  "), "text");
+        
+        let result = recipe.compile().try_apply_image(&image).unwrap();
+        assert_eq!(result.format, ImageFormat::Jpeg);
+        assert_eq!(result.dimensions().unwrap(), (16, 8));
+        
+        // Undecodable images fail at the first step that needs the pixels
+        let broken = ClipboardImage::new(ImageFormat::Png, image.bytes[..40].to_vec());
+        let error = recipe.try_apply_image(&broken).unwrap_err();
+        assert_eq!(error.index, 1);
+        
+        recipe.transformations[1] = Transformation::ResizeImage { max_dimension: 0 };
+        assert_eq!(recipe.try_apply_image(&image).unwrap_err().index, 1);
+        assert!(!Recipe::new("Text").uses_images());
+    }
+    
+    #[test]
+    fn test_condition_serialization() {
+        let step = Transformation::When {
+            condition: Condition::Is { content_type: ContentType::Json },
+            steps: vec![Transformation::TrimLines],
+        };
+        let json = serde_json::to_string(&step).unwrap();
+        assert_eq!(json, r#"{"When":{"condition":{"Is":{"content_type":"Json"}},"steps":["TrimLines"]}}"#);
+        assert_eq!(serde_json::from_str::<Transformation>(&json).unwrap(), step);
+    }
 }
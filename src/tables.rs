@@ -0,0 +1,564 @@
+//! Tables
+//!
+//! Reads CSV, TSV (as copied from spreadsheets) and Markdown tables, and
+//! writes them as CSV, TSV, Markdown, aligned ASCII, HTML, JSON or SQL. The
+//! first row is taken as the header.
+//!
+//! Column operations work on delimited text row by row instead, with no
+//! header, and number columns from 1 like `cut` does.
+
+use std::collections::HashSet;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+use crate::html;
+use crate::transformers::regex;
+
+/// Formats tables can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TableFormat {
+    Csv,
+    Tsv,
+    Markdown,
+    /// Plain text with aligned columns and borders
+    Ascii,
+    Html,
+    /// An array of objects keyed by the header
+    Json,
+}
+
+impl TableFormat {
+    /// All supported formats
+    pub const ALL: [TableFormat; 6] = [Self::Csv, Self::Tsv, Self::Markdown, Self::Ascii, Self::Html, Self::Json];
+    
+    /// Get a human-readable name for the format
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Csv => "CSV",
+            Self::Tsv => "TSV",
+            Self::Markdown => "Markdown",
+            Self::Ascii => "ASCII",
+            Self::Html => "HTML",
+            Self::Json => "JSON",
+        }
+    }
+}
+
+impl fmt::Display for TableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.display_name())
+    }
+}
+
+/// A table of text cells, with every row as wide as the header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Parse a Markdown table, or CSV or TSV with quoted fields. Tabs in the
+    /// first line mean TSV, and semicolons without commas mean CSV using semicolons.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim_matches(['\r', '\n']);
+        if text.trim().is_empty() {
+            return Err("No table to convert".to_string());
+        }
+        
+        let rows = if is_markdown_table(text) {
+            parse_markdown(text)
+        } else {
+            let first_line = text.lines().next().unwrap_or_default();
+            let delimiter = if first_line.contains('\t') {
+                '\t'
+            } else if first_line.contains(';') && !first_line.contains(',') {
+                ';'
+            } else {
+                ','
+            };
+            parse_delimited(text, delimiter)?
+        };
+        Ok(Self::from_rows(rows))
+    }
+    
+    /// Make a table from rows, the first being the header, padding short rows
+    pub fn from_rows(mut rows: Vec<Vec<String>>) -> Self {
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        for row in &mut rows {
+            row.resize(width, String::new());
+        }
+        let header = if rows.is_empty() { Vec::new() } else { rows.remove(0) };
+        Self { header, rows }
+    }
+    
+    /// Write the table in a format
+    pub fn write(&self, format: TableFormat) -> String {
+        match format {
+            TableFormat::Csv => self.write_delimited(','),
+            TableFormat::Tsv => self.write_delimited('\t'),
+            TableFormat::Markdown => self.write_markdown(),
+            TableFormat::Ascii => self.write_ascii(),
+            TableFormat::Html => self.write_html(),
+            TableFormat::Json => self.write_json(),
+        }
+    }
+    
+    /// Write an `INSERT` statement for each row. Cells that are numbers are
+    /// written as numbers, and empty cells as NULL.
+    pub fn to_sql(&self, table: &str) -> String {
+        let table: Vec<_> = table.split('.').map(sql_identifier).collect();
+        let columns: Vec<_> = self.column_names().iter().map(|name| sql_identifier(name)).collect();
+        self.rows.iter()
+            .map(|row| {
+                let values: Vec<_> = row.iter().map(|cell| sql_value(cell)).collect();
+                format!("INSERT INTO {} ({}) VALUES ({});", table.join("."), columns.join(", "), values.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    
+    /// Header cells, naming unnamed columns after their position
+    fn column_names(&self) -> Vec<String> {
+        self.header.iter()
+            .enumerate()
+            .map(|(i, name)| if name.trim().is_empty() { format!("column{}", i + 1) } else { name.clone() })
+            .collect()
+    }
+    
+    /// The header followed by the rows
+    fn all_rows(&self) -> impl Iterator<Item = &Vec<String>> {
+        std::iter::once(&self.header).chain(&self.rows)
+    }
+    
+    /// Whether every filled cell of a column below the header is a number
+    fn is_numeric_column(&self, column: usize) -> bool {
+        let mut cells = self.rows.iter().map(|row| row[column].trim()).filter(|cell| !cell.is_empty()).peekable();
+        cells.peek().is_some() && cells.all(|cell| regex!(r"^[-+]?(\d+\.?\d*|\.\d+)([eE][-+]?\d+)?$").is_match(cell))
+    }
+    
+    /// Width of each column in characters, after escaping cells with `escape`
+    fn column_widths(&self, escape: impl Fn(&str) -> String) -> Vec<usize> {
+        (0..self.header.len())
+            .map(|column| self.all_rows().map(|row| escape(&row[column]).chars().count()).max().unwrap_or(0))
+            .collect()
+    }
+    
+    fn write_delimited(&self, delimiter: char) -> String {
+        write_rows(self.all_rows(), delimiter)
+    }
+    
+    fn write_markdown(&self) -> String {
+        let escape = |cell: &str| cell.replace('|', "\\|").replace('\n', "<br>");
+        let widths: Vec<_> = self.column_widths(escape).into_iter().map(|width| width.max(3)).collect();
+        let numeric: Vec<_> = (0..self.header.len()).map(|column| self.is_numeric_column(column)).collect();
+        
+        let line = |row: &Vec<String>| {
+            let cells: Vec<_> = row.iter()
+                .enumerate()
+                .map(|(i, cell)| pad(&escape(cell), widths[i], numeric[i]))
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        let separator: Vec<_> = widths.iter()
+            .zip(&numeric)
+            .map(|(&width, &numeric)| if numeric { format!("{}:", "-".repeat(width - 1)) } else { "-".repeat(width) })
+            .collect();
+        
+        let mut lines = vec![line(&self.header), format!("| {} |", separator.join(" | "))];
+        lines.extend(self.rows.iter().map(line));
+        lines.join("\n")
+    }
+    
+    fn write_ascii(&self) -> String {
+        let escape = |cell: &str| cell.replace(['\r', '\n'], " ");
+        let widths = self.column_widths(escape);
+        let numeric: Vec<_> = (0..self.header.len()).map(|column| self.is_numeric_column(column)).collect();
+        
+        let border = widths.iter().map(|&width| "-".repeat(width + 2)).collect::<Vec<_>>().join("+");
+        let border = format!("+{}+", border);
+        let line = |row: &Vec<String>| {
+            let cells: Vec<_> = row.iter()
+                .enumerate()
+                .map(|(i, cell)| pad(&escape(cell), widths[i], numeric[i]))
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        
+        let mut lines = vec![border.clone(), line(&self.header), border.clone()];
+        lines.extend(self.rows.iter().map(line));
+        if !self.rows.is_empty() {
+            lines.push(border);
+        }
+        lines.join("\n")
+    }
+    
+    fn write_html(&self) -> String {
+        let cell = |tag: &str, text: &str| format!("<{}>{}</{}>", tag, html::escape(text).replace('\n', "<br>"), tag);
+        let row = |tag: &str, row: &Vec<String>| {
+            format!("    <tr>{}</tr>", row.iter().map(|text| cell(tag, text)).collect::<String>())
+        };
+        
+        let mut lines = vec!["<table>".to_string(), "  <thead>".to_string(), row("th", &self.header), "  </thead>".to_string()];
+        if !self.rows.is_empty() {
+            lines.push("  <tbody>".to_string());
+            lines.extend(self.rows.iter().map(|r| row("td", r)));
+            lines.push("  </tbody>".to_string());
+        }
+        lines.push("</table>".to_string());
+        lines.join("\n")
+    }
+    
+    /// Write the rows as objects keyed by the header. Cells that are numbers
+    /// are written as numbers.
+    fn write_json(&self) -> String {
+        let names = self.column_names();
+        let rows: Vec<_> = self.rows.iter()
+            .map(|row| {
+                let object: Map<_, _> = names.iter()
+                    .zip(row)
+                    .map(|(name, cell)| {
+                        let value = number(cell).map_or_else(|| Value::String(cell.clone()), Value::Number);
+                        (name.clone(), value)
+                    })
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+        serde_json::to_string_pretty(&rows).unwrap_or_default()
+    }
+}
+
+/// Read a table in any supported input format and write it in another
+pub fn convert(text: &str, to: TableFormat) -> Result<String, String> {
+    Ok(Table::parse(text)?.write(to))
+}
+
+/// Read a table in any supported input format and write `INSERT` statements into `table`
+pub fn to_sql(text: &str, table: &str) -> Result<String, String> {
+    Ok(Table::parse(text)?.to_sql(table))
+}
+
+/// Keep only some columns, in the order given. Rows missing a column get an empty cell.
+pub fn select_columns(text: &str, delimiter: char, columns: &[usize]) -> Result<String, String> {
+    let rows = parse_rows(text, delimiter)?;
+    let rows: Vec<Vec<String>> = rows.into_iter()
+        .map(|row| columns.iter().map(|&column| cell(&row, column).to_string()).collect())
+        .collect();
+    Ok(write_rows(&rows, delimiter))
+}
+
+/// Remove some columns
+pub fn drop_columns(text: &str, delimiter: char, columns: &[usize]) -> Result<String, String> {
+    let rows = parse_rows(text, delimiter)?;
+    let rows: Vec<Vec<String>> = rows.into_iter()
+        .map(|row| {
+            row.into_iter()
+                .enumerate()
+                .filter(|(i, _)| !columns.contains(&(i + 1)))
+                .map(|(_, cell)| cell)
+                .collect()
+        })
+        .collect();
+    Ok(write_rows(&rows, delimiter))
+}
+
+/// Sort rows by a column, keeping the order of rows that tie. Sorting
+/// numerically puts cells that aren't numbers last.
+pub fn sort_by_column(text: &str, delimiter: char, column: usize, numeric: bool) -> Result<String, String> {
+    let mut rows = parse_rows(text, delimiter)?;
+    if numeric {
+        let key = |row: &Vec<String>| cell(row, column).trim().parse::<f64>().ok();
+        rows.sort_by(|a, b| match (key(a), key(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => a.is_none().cmp(&b.is_none()),
+        });
+    } else {
+        rows.sort_by(|a, b| cell(a, column).cmp(cell(b, column)));
+    }
+    Ok(write_rows(&rows, delimiter))
+}
+
+/// Keep only the first row for each value of a column
+pub fn dedupe_by_column(text: &str, delimiter: char, column: usize) -> Result<String, String> {
+    let mut rows = parse_rows(text, delimiter)?;
+    let mut seen = HashSet::new();
+    rows.retain(|row| seen.insert(cell(row, column).to_string()));
+    Ok(write_rows(&rows, delimiter))
+}
+
+/// Swap rows and columns
+pub fn transpose(text: &str, delimiter: char) -> Result<String, String> {
+    let rows = parse_rows(text, delimiter)?;
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let columns: Vec<Vec<String>> = (1..=width)
+        .map(|column| rows.iter().map(|row| cell(row, column).to_string()).collect())
+        .collect();
+    Ok(write_rows(&columns, delimiter))
+}
+
+/// Pad every column to the width of its widest cell, separating columns by
+/// two spaces instead of the delimiter
+pub fn align_columns(text: &str, delimiter: char) -> Result<String, String> {
+    let rows = parse_rows(text, delimiter)?;
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (1..=width)
+        .map(|column| rows.iter().map(|row| cell(row, column).chars().count()).max().unwrap_or(0))
+        .collect();
+    Ok(rows.iter()
+        .map(|row| {
+            let cells: Vec<_> = row.iter().enumerate().map(|(i, cell)| pad(cell, widths[i], false)).collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Parse delimited rows for column operations, leaving ragged rows as they are.
+/// Runs of spaces count as one delimiter, as in aligned text, while empty
+/// cells between other delimiters (tabs included) are kept.
+fn parse_rows(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = parse_delimited(text.trim_matches(['\r', '\n']), delimiter)?;
+    if delimiter == ' ' {
+        for row in &mut rows {
+            row.retain(|cell| !cell.is_empty());
+        }
+    }
+    Ok(rows)
+}
+
+/// A cell by its column number from 1, or an empty one if the row is too short
+fn cell(row: &[String], column: usize) -> &str {
+    column.checked_sub(1).and_then(|i| row.get(i)).map_or("", String::as_str)
+}
+
+/// Write rows of cells, quoting cells that hold the delimiter, quotes or line breaks
+fn write_rows<'a>(rows: impl IntoIterator<Item = &'a Vec<String>>, delimiter: char) -> String {
+    rows.into_iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| {
+                    if cell.contains([delimiter, '"', '\n', '\r']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(&delimiter.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether text starts with a Markdown table's header and separator lines
+fn is_markdown_table(text: &str) -> bool {
+    let mut lines = text.lines().map(str::trim);
+    let header = lines.next().unwrap_or_default();
+    let separator = lines.next().unwrap_or_default();
+    header.contains('|') && regex!(r"^\|?\s*:?-+:?\s*(\|\s*:?-+:?\s*)*\|?$").is_match(separator)
+}
+
+/// Parse the rows of a Markdown table, skipping its separator line
+fn parse_markdown(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|&(i, line)| i != 1 && !line.is_empty())
+        .map(|(_, line)| {
+            let line = line.strip_prefix('|').unwrap_or(line);
+            let line = if line.ends_with('|') && !line.ends_with("\\|") { &line[..line.len() - 1] } else { line };
+            
+            let mut cells = vec![String::new()];
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' if chars.peek() == Some(&'|') => {
+                        cells.last_mut().unwrap().push('|');
+                        chars.next();
+                    }
+                    '|' => cells.push(String::new()),
+                    _ => cells.last_mut().unwrap().push(c),
+                }
+            }
+            cells.iter()
+                .map(|cell| regex!(r"(?i)<br\s*/?>").replace_all(cell.trim(), "\n").into_owned())
+                .collect()
+        })
+        .collect()
+}
+
+/// Parse delimited text, where fields in double quotes may hold the delimiter,
+/// line breaks and doubled quotes. Blank lines are skipped.
+fn parse_delimited(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    // Line of the quote still open, if any
+    let mut quote_line = None;
+    
+    let mut end_row = |row: &mut Vec<String>, field: &mut String| {
+        row.push(std::mem::take(field));
+        if row.len() > 1 || !row[0].is_empty() {
+            rows.push(std::mem::take(row));
+        }
+        row.clear();
+    };
+    
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+        if quote_line.is_some() {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quote_line = None,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quote_line = Some(line),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => end_row(&mut row, &mut field),
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if let Some(line) = quote_line {
+        return Err(format!("Unclosed quote on line {}", line));
+    }
+    end_row(&mut row, &mut field);
+    Ok(rows)
+}
+
+/// Pad a cell to a width, on the left for numbers so they line up on the right
+fn pad(cell: &str, width: usize, right: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(cell.chars().count()));
+    if right {
+        format!("{}{}", padding, cell)
+    } else {
+        format!("{}{}", cell, padding)
+    }
+}
+
+/// The cell as a number, if writing the number gives back exactly the cell
+fn number(cell: &str) -> Option<Number> {
+    serde_json::from_str::<Number>(cell).ok().filter(|n| n.to_string() == cell)
+}
+
+/// Quote an SQL identifier unless it is a plain word
+fn sql_identifier(name: &str) -> String {
+    if regex!(r"^[A-Za-z_][A-Za-z0-9_]*$").is_match(name) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+fn sql_value(cell: &str) -> String {
+    if cell.is_empty() {
+        "NULL".to_string()
+    } else if number(cell).is_some() {
+        cell.to_string()
+    } else {
+        format!("'{}'", cell.replace('\'', "''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn table(rows: &[&[&str]]) -> Table {
+        Table::from_rows(rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect())
+    }
+    
+    #[test]
+    fn test_parse() {
+        let csv = "name,city,note\r\nBob,\"Paris, France\",\"said \"\"hi\"\"\ntwice\"\n\nAlice,Berlin\n";
+        assert_eq!(Table::parse(csv).unwrap(), table(&[
+            &["name", "city", "note"],
+            &["Bob", "Paris, France", "said \"hi\"\ntwice"],
+            &["Alice", "Berlin", ""],
+        ]));
+        
+        assert_eq!(Table::parse("a\tb\n1\t2,5").unwrap(), table(&[&["a", "b"], &["1", "2,5"]]));
+        assert_eq!(Table::parse("a;b\n1;2").unwrap(), table(&[&["a", "b"], &["1", "2"]]));
+        assert_eq!(
+            Table::parse("| a | b \\| c |\n|---|:--:|\n| 1 | x<br>y |").unwrap(),
+            table(&[&["a", "b | c"], &["1", "x\ny"]])
+        );
+        assert_eq!(Table::parse("a,\"b\n1,2").unwrap_err(), "Unclosed quote on line 1");
+    }
+    
+    #[test]
+    fn test_write() {
+        let t = table(&[&["name", "qty"], &["Widget, large", "12"], &["Nut", "7.5"]]);
+        assert_eq!(t.write(TableFormat::Csv), "name,qty\n\"Widget, large\",12\nNut,7.5");
+        assert_eq!(
+            t.write(TableFormat::Markdown),
+            "| name          | qty |\n| ------------- | --: |\n| Widget, large |  12 |\n| Nut           | 7.5 |"
+        );
+        assert_eq!(
+            t.write(TableFormat::Ascii),
+            "+---------------+-----+\n| name          | qty |\n+---------------+-----+\n| Widget, large |  12 |\n| Nut           | 7.5 |\n+---------------+-----+"
+        );
+        assert_eq!(
+            t.write(TableFormat::Html),
+            "<table>\n  <thead>\n    <tr><th>name</th><th>qty</th></tr>\n  </thead>\n  <tbody>\n    <tr><td>Widget, large</td><td>12</td></tr>\n    <tr><td>Nut</td><td>7.5</td></tr>\n  </tbody>\n</table>"
+        );
+        assert_eq!(
+            t.write(TableFormat::Json),
+            "[\n  {\n    \"name\": \"Widget, large\",\n    \"qty\": 12\n  },\n  {\n    \"name\": \"Nut\",\n    \"qty\": 7.5\n  }\n]"
+        );
+        
+        // Markdown tables read back the same
+        assert_eq!(Table::parse(&t.write(TableFormat::Markdown)).unwrap(), t);
+    }
+    
+    #[test]
+    fn test_to_sql() {
+        let t = table(&[&["id", "full name", ""], &["007", "O'Brien", ""], &["2", "Ada", "x"]]);
+        assert_eq!(
+            t.to_sql("app.users"),
+            "INSERT INTO app.users (id, \"full name\", column3) VALUES ('007', 'O''Brien', NULL);\n\
+             INSERT INTO app.users (id, \"full name\", column3) VALUES (2, 'Ada', 'x');"
+        );
+    }
+    
+    #[test]
+    fn test_column_operations() {
+        let text = "b,10,x\na,9\n\"c, d\",10,y\n";
+        assert_eq!(select_columns(text, ',', &[3, 1]).unwrap(), "x,b\n,a\ny,\"c, d\"");
+        assert_eq!(drop_columns(text, ',', &[2]).unwrap(), "b,x\na\n\"c, d\",y");
+        assert_eq!(sort_by_column(text, ',', 1, false).unwrap(), "a,9\nb,10,x\n\"c, d\",10,y");
+        assert_eq!(dedupe_by_column(text, ',', 2).unwrap(), "b,10,x\na,9");
+        assert_eq!(transpose(text, ',').unwrap(), "b,a,\"c, d\"\n10,9,10\nx,,y");
+        assert_eq!(align_columns(text, ',').unwrap(), "b     10  x\na     9\nc, d  10  y");
+    }
+    
+    #[test]
+    fn test_space_delimited_columns() {
+        let text = "  PID   NAME      CPU\n  812   firefox   12.5\n 1044   cargo     98";
+        assert_eq!(select_columns(text, ' ', &[2, 3]).unwrap(), "NAME CPU\nfirefox 12.5\ncargo 98");
+        assert_eq!(transpose(text, ' ').unwrap(), "PID 812 1044\nNAME firefox cargo\nCPU 12.5 98");
+        
+        let aligned = align_columns(text, ' ').unwrap();
+        assert_eq!(aligned, "PID   NAME     CPU\n812   firefox  12.5\n1044  cargo    98");
+        assert_eq!(align_columns(&aligned, ' ').unwrap(), aligned);
+    }
+    
+    #[test]
+    fn test_sort_by_column_numeric() {
+        let text = "x\t10\ny\tn/a\nz\t9\nw\t-1.5";
+        assert_eq!(sort_by_column(text, '\t', 2, true).unwrap(), "w\t-1.5\nz\t9\nx\t10\ny\tn/a");
+        assert_eq!(sort_by_column(text, '\t', 2, false).unwrap(), "w\t-1.5\nx\t10\nz\t9\ny\tn/a");
+    }
+}
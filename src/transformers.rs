@@ -3,13 +3,24 @@
 //! This module contains all the text transformation functions that can be
 //! applied to clipboard content.
 
-use regex::Regex;
 use std::collections::HashSet;
+use serde::Serialize;
 use unicode_normalization::UnicodeNormalization;
 
+use crate::query::Query;
+
+/// Compile a regex the first time it is used and reuse it afterwards
+macro_rules! regex {
+    ($pattern:expr) => {{
+        static RE: ::std::sync::OnceLock<::regex::Regex> = ::std::sync::OnceLock::new();
+        RE.get_or_init(|| ::regex::Regex::new($pattern).unwrap())
+    }};
+}
+pub(crate) use regex;
+
 /// Remove all extra whitespace and normalize to single spaces
 pub fn normalize_whitespace(text: &str) -> String {
-    let re = Regex::new(r"\s+").unwrap();
+    let re = regex!(r"\s+");
     re.replace_all(text.trim(), " ").to_string()
 }
 
@@ -153,7 +164,7 @@ pub fn remove_emojis(text: &str) -> String {
 /// This is primarily useful for rich text, but for plain text it normalizes whitespace
 pub fn strip_formatting(text: &str) -> String {
     // Remove HTML tags if present
-    let re = Regex::new(r"<[^>]+>").unwrap();
+    let re = regex!(r"<[^>]+>");
     let text = re.replace_all(text, "");
     
     // Normalize whitespace
@@ -185,19 +196,19 @@ pub fn spaces_to_tabs(text: &str, spaces_per_tab: usize) -> String {
 
 /// Remove URLs from text
 pub fn remove_urls(text: &str) -> String {
-    let re = Regex::new(r"https?://\S+").unwrap();
+    let re = regex!(r"https?://\S+");
     re.replace_all(text, "").to_string()
 }
 
 /// Remove email addresses from text
 pub fn remove_emails(text: &str) -> String {
-    let re = Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b").unwrap();
+    let re = regex!(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b");
     re.replace_all(text, "").to_string()
 }
 
 /// Remove phone numbers from text (basic patterns)
 pub fn remove_phone_numbers(text: &str) -> String {
-    let re = Regex::new(r"(\+\d{1,3}[-.\s]?)?\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}").unwrap();
+    let re = regex!(r"(\+\d{1,3}[-.\s]?)?\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}");
     re.replace_all(text, "").to_string()
 }
 
@@ -212,7 +223,7 @@ pub fn add_line_numbers(text: &str) -> String {
 
 /// Remove line numbers (assumes format "N: " or "N. ")
 pub fn remove_line_numbers(text: &str) -> String {
-    let re = Regex::new(r"^\s*\d+[.:]\s*").unwrap();
+    let re = regex!(r"^\s*\d+[.:]\s*");
     text.lines()
         .map(|line| re.replace(line, "").to_string())
         .collect::<Vec<_>>()
@@ -222,7 +233,7 @@ pub fn remove_line_numbers(text: &str) -> String {
 /// Remove line numbers that are directly stuck to the line content (no separator)
 /// Handles formats like "1import React" -> "import React" or "93\t\tconst" -> "\t\tconst"
 pub fn remove_line_numbers_stuck(text: &str) -> String {
-    let re = Regex::new(r"^(\s*)\d+").unwrap();
+    let re = regex!(r"^(\s*)\d+");
     text.lines()
         .map(|line| re.replace(line, "$1").to_string())
         .collect::<Vec<_>>()
@@ -311,9 +322,9 @@ pub fn decode_html_entities(text: &str) -> String {
 /// Slugify text (for URLs)
 pub fn slugify(text: &str) -> String {
     let text = text.to_lowercase();
-    let re = Regex::new(r"[^a-z0-9\s-]").unwrap();
+    let re = regex!(r"[^a-z0-9\s-]");
     let text = re.replace_all(&text, "");
-    let re = Regex::new(r"[\s_]+").unwrap();
+    let re = regex!(r"[\s_]+");
     re.replace_all(&text, "-").trim_matches('-').to_string()
 }
 
@@ -377,32 +388,32 @@ pub fn to_kebab_case(text: &str) -> String {
 /// Remove markdown formatting
 pub fn remove_markdown(text: &str) -> String {
     // Remove headers
-    let re = Regex::new(r"^#{1,6}\s+").unwrap();
+    let re = regex!(r"^#{1,6}\s+");
     let text = text.lines()
         .map(|line| re.replace(line, "").to_string())
         .collect::<Vec<_>>()
         .join("\n");
     
     // Remove bold/italic
-    let re = Regex::new(r"\*\*([^*]+)\*\*").unwrap();
+    let re = regex!(r"\*\*([^*]+)\*\*");
     let text = re.replace_all(&text, "$1");
-    let re = Regex::new(r"\*([^*]+)\*").unwrap();
+    let re = regex!(r"\*([^*]+)\*");
     let text = re.replace_all(&text, "$1");
-    let re = Regex::new(r"__([^_]+)__").unwrap();
+    let re = regex!(r"__([^_]+)__");
     let text = re.replace_all(&text, "$1");
-    let re = Regex::new(r"_([^_]+)_").unwrap();
+    let re = regex!(r"_([^_]+)_");
     let text = re.replace_all(&text, "$1");
     
     // Remove links but keep text
-    let re = Regex::new(r"\[([^\]]+)\]\([^)]+\)").unwrap();
+    let re = regex!(r"\[([^\]]+)\]\([^)]+\)");
     let text = re.replace_all(&text, "$1");
     
     // Remove code blocks
-    let re = Regex::new(r"`([^`]+)`").unwrap();
+    let re = regex!(r"`([^`]+)`");
     let text = re.replace_all(&text, "$1");
     
     // Remove bullet points
-    let re = Regex::new(r"^\s*[-*+]\s+").unwrap();
+    let re = regex!(r"^\s*[-*+]\s+");
     text.lines()
         .map(|line| re.replace(line, "").to_string())
         .collect::<Vec<_>>()
@@ -419,6 +430,62 @@ pub fn split_to_lines(text: &str, delimiter: &str) -> String {
     text.split(delimiter).collect::<Vec<_>>().join("\n")
 }
 
+/// Parse JSON, describing where it is malformed otherwise
+fn parse_json(text: &str) -> Result<serde_json::Value, String> {
+    serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))
+}
+
+/// Format JSON over several lines, indenting by `indent` spaces
+fn pretty_json(value: &serde_json::Value, indent: usize) -> Result<String, String> {
+    let indent = " ".repeat(indent);
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut out = Vec::new();
+    value.serialize(&mut serde_json::Serializer::with_formatter(&mut out, formatter))
+        .map_err(|e| e.to_string())?;
+    String::from_utf8(out).map_err(|e| e.to_string())
+}
+
+/// Pretty-print JSON, keeping the order of object keys
+pub fn json_pretty_print(text: &str, indent: usize) -> Result<String, String> {
+    pretty_json(&parse_json(text)?, indent)
+}
+
+/// Put JSON on a single line without any spaces
+pub fn json_minify(text: &str) -> Result<String, String> {
+    serde_json::to_string(&parse_json(text)?).map_err(|e| e.to_string())
+}
+
+/// Sort object keys alphabetically at every level. Single-line JSON stays on
+/// one line; anything else is pretty-printed.
+pub fn json_sort_keys(text: &str) -> Result<String, String> {
+    let mut value = parse_json(text)?;
+    value.sort_all_objects();
+    if text.trim().contains('\n') {
+        pretty_json(&value, 2)
+    } else {
+        serde_json::to_string(&value).map_err(|e| e.to_string())
+    }
+}
+
+/// Check that text is valid JSON, reporting the line and column where it isn't
+pub fn json_validate(text: &str) -> Result<(), String> {
+    parse_json(text).map(|_| ())
+}
+
+/// Extract values from JSON with a jq-style query, one result after another.
+/// With `raw`, strings are written without quotes.
+pub fn json_query(text: &str, expr: &str, raw: bool) -> Result<String, String> {
+    let query = Query::parse(expr).map_err(|e| format!("Invalid query: {}", e))?;
+    let results = query.run(&parse_json(text)?)?;
+    let results = results.iter()
+        .map(|value| match value {
+            serde_json::Value::String(s) if raw => Ok(s.clone()),
+            _ => pretty_json(value, 2),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(results.join("\n"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -461,4 +528,43 @@ mod tests {
         let expected = "import React from \"react\";\nimport { useState } from \"react\";\n\n\t\tconst foo = 42;\n\t\treturn foo;";
         assert_eq!(remove_line_numbers_stuck(input), expected);
     }
+
+    #[test]
+    fn test_json_formatting() {
+        let json = r#"{"b": [1, 2], "a": {"d": null, "c": "x"}}"#;
+        assert_eq!(json_minify(json).unwrap(), r#"{"b":[1,2],"a":{"d":null,"c":"x"}}"#);
+        assert_eq!(json_sort_keys(json).unwrap(), r#"{"a":{"c":"x","d":null},"b":[1,2]}"#);
+        assert_eq!(
+            json_pretty_print(r#"{"b":[1],"a":true}"#, 2).unwrap(),
+            "{\n  \"b\": [\n    1\n  ],\n  \"a\": true\n}"
+        );
+        assert_eq!(json_sort_keys("{\n\"b\": 1, \"a\": 2}").unwrap(), "{\n  \"a\": 2,\n  \"b\": 1\n}");
+    }
+
+    #[test]
+    fn test_json_keeps_numbers() {
+        let json = r#"{"id":12345678901234567890123,"p":1.50}"#;
+        assert_eq!(json_minify(json).unwrap(), json);
+        assert_eq!(json_sort_keys(json).unwrap(), json);
+        // Exponents are written in one style, but their digits are kept
+        assert_eq!(json_pretty_print("[1e2, -0, 1.0E-5]", 2).unwrap(), "[\n  1e+2,\n  -0,\n  1.0e-5\n]");
+    }
+
+    #[test]
+    fn test_json_query() {
+        let json = r#"{"users": [{"name": "Ada", "id": 1}, {"name": "Alan", "id": 2}]}"#;
+        assert_eq!(json_query(json, ".users[] | select(.id > 1) | .name", true).unwrap(), "Alan");
+        assert_eq!(json_query(json, ".users[].name", false).unwrap(), "\"Ada\"\n\"Alan\"");
+        assert_eq!(json_query(json, ".users[0] | keys", true).unwrap(), "[\n  \"id\",\n  \"name\"\n]");
+        assert!(json_query(json, ".users[", true).unwrap_err().starts_with("Invalid query"));
+    }
+
+    #[test]
+    fn test_json_validate() {
+        assert!(json_validate(" [1, 2] \n").is_ok());
+        assert_eq!(
+            json_validate("{\n  \"a\": 1,\n}").unwrap_err(),
+            "Invalid JSON: trailing comma at line 3 column 1"
+        );
+    }
 }
@@ -18,6 +18,8 @@ pub enum TrayCommand {
     ApplyRecipe(uuid::Uuid),
     /// Show quick menu
     ShowQuickMenu,
+    /// Undo the last transformation
+    Undo,
     /// Quit the application
     Quit,
 }
@@ -79,6 +81,12 @@ impl TrayManager {
             let _ = tx_clone.blocking_send(TrayCommand::ToggleTransformation);
         }).context("Failed to add Toggle menu item")?;
         
+        // Undo
+        let tx_clone = tx.clone();
+        tray.add_menu_item("Undo Last Transform", move || {
+            let _ = tx_clone.blocking_send(TrayCommand::Undo);
+        }).context("Failed to add Undo menu item")?;
+        
         // Quick Menu
         let tx_clone = tx.clone();
         tray.add_menu_item("Quick Menu", move || {
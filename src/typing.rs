@@ -0,0 +1,235 @@
+//! Synthesized keyboard input
+//!
+//! Pastes into the focused window by pressing Ctrl+V, or types text key by key
+//! for fields that refuse pastes (remote desktops, web forms). Uses the X11
+//! XTest extension, so on Wayland it only reaches XWayland applications.
+
+use anyhow::Result;
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+
+/// Keysyms of keys with a name rather than a character
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+mod keysym {
+    pub const RETURN: u32 = 0xff0d;
+    pub const TAB: u32 = 0xff09;
+    pub const SHIFT_L: u32 = 0xffe1;
+    pub const CONTROL_L: u32 = 0xffe3;
+    pub const V: u32 = 0x76;
+}
+
+/// How long to wait for the user to let go of a hotkey's modifiers
+#[cfg(target_os = "linux")]
+const MODIFIER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Pause between typed characters, so applications don't drop any
+#[cfg(target_os = "linux")]
+const KEY_DELAY: Duration = Duration::from_millis(4);
+
+/// Paste the clipboard into the focused window by pressing Ctrl+V
+#[cfg(target_os = "linux")]
+pub fn paste() -> Result<()> {
+    let keyboard = Keyboard::connect()?;
+    keyboard.wait_for_modifiers()?;
+    
+    let control = keyboard.keycode(keysym::CONTROL_L).context("No Control key in the keyboard layout")?.0;
+    let (v, _) = keyboard.keycode(keysym::V).context("No V key in the keyboard layout")?;
+    keyboard.tap(v, &[control])
+}
+
+/// Type text into the focused window. Characters missing from the keyboard
+/// layout are typed by briefly mapping them to a spare key.
+#[cfg(target_os = "linux")]
+pub fn type_text(text: &str) -> Result<()> {
+    let mut keyboard = Keyboard::connect()?;
+    keyboard.wait_for_modifiers()?;
+    
+    let result = text.chars()
+        .filter(|&c| c != '\r')
+        .try_for_each(|c| keyboard.type_char(c));
+    keyboard.release_spare()?;
+    result
+}
+
+/// Paste the clipboard into the focused window by pressing Ctrl+V
+#[cfg(not(target_os = "linux"))]
+pub fn paste() -> Result<()> {
+    anyhow::bail!("Synthesized key presses are only supported on X11")
+}
+
+/// Type text into the focused window
+#[cfg(not(target_os = "linux"))]
+pub fn type_text(_text: &str) -> Result<()> {
+    anyhow::bail!("Synthesized key presses are only supported on X11")
+}
+
+/// Get the keysym that types a character
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn char_keysym(c: char) -> u32 {
+    match c {
+        '\n' => keysym::RETURN,
+        '\t' => keysym::TAB,
+        // Latin-1 keysyms match their code points
+        ' '..='~' | '\u{a0}'..='\u{ff}' => c as u32,
+        _ => 0x0100_0000 | c as u32,
+    }
+}
+
+/// A connection to the X server for sending key presses through XTest
+#[cfg(target_os = "linux")]
+struct Keyboard {
+    conn: x11rb::rust_connection::RustConnection,
+    root: u32,
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    /// The keyboard mapping, `keysyms_per_keycode` keysyms per keycode
+    keysyms: Vec<u32>,
+    /// A keycode without keysyms, mapped to characters missing from the layout
+    spare: Option<u8>,
+    /// Whether the spare keycode is currently mapped
+    spare_mapped: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl Keyboard {
+    fn connect() -> Result<Self> {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::ConnectionExt as _;
+        use x11rb::protocol::xtest::ConnectionExt as _;
+        
+        let (conn, screen_num) = x11rb::connect(None)
+            .context("Failed to connect to X server")?;
+        conn.xtest_get_version(2, 2)?
+            .reply()
+            .context("XTest extension not available")?;
+        
+        let setup = conn.setup();
+        let root = setup.roots[screen_num].root;
+        let min_keycode = setup.min_keycode;
+        let count = setup.max_keycode - min_keycode + 1;
+        let mapping = conn.get_keyboard_mapping(min_keycode, count)?.reply()?;
+        
+        let per = mapping.keysyms_per_keycode.max(1) as usize;
+        // Take the highest unused keycode, which layouts are least likely to claim
+        let spare = mapping.keysyms.chunks(per)
+            .rposition(|syms| syms.iter().all(|&sym| sym == 0))
+            .map(|i| min_keycode + i as u8);
+        
+        Ok(Self {
+            conn,
+            root,
+            min_keycode,
+            keysyms_per_keycode: mapping.keysyms_per_keycode,
+            keysyms: mapping.keysyms,
+            spare,
+            spare_mapped: false,
+        })
+    }
+    
+    /// Find the key typing a keysym, and whether it needs Shift
+    fn keycode(&self, keysym: u32) -> Option<(u8, bool)> {
+        let per = self.keysyms_per_keycode.max(1) as usize;
+        self.keysyms.chunks(per).enumerate().find_map(|(i, syms)| {
+            let keycode = self.min_keycode + i as u8;
+            if syms.first() == Some(&keysym) {
+                Some((keycode, false))
+            } else if syms.get(1) == Some(&keysym) {
+                Some((keycode, true))
+            } else {
+                None
+            }
+        })
+    }
+    
+    /// Wait until the user lets go of the modifiers of the hotkey that got us
+    /// here, so they don't combine with the keys we press
+    fn wait_for_modifiers(&self) -> Result<()> {
+        use x11rb::protocol::xproto::{ConnectionExt as _, KeyButMask};
+        
+        let modifiers = KeyButMask::SHIFT | KeyButMask::CONTROL | KeyButMask::MOD1 | KeyButMask::MOD4;
+        let deadline = Instant::now() + MODIFIER_TIMEOUT;
+        while Instant::now() < deadline {
+            let mask = self.conn.query_pointer(self.root)?.reply()?.mask;
+            if u16::from(mask) & u16::from(modifiers) == 0 {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        anyhow::bail!("Modifier keys are still held down")
+    }
+    
+    fn type_char(&mut self, c: char) -> Result<()> {
+        let keysym = char_keysym(c);
+        if let Some((keycode, shifted)) = self.keycode(keysym) {
+            if !shifted {
+                return self.tap(keycode, &[]);
+            }
+            let shift = self.keycode(keysym::SHIFT_L).context("No Shift key in the keyboard layout")?.0;
+            return self.tap(keycode, &[shift]);
+        }
+        
+        let spare = self.spare.context(format!("Cannot type {:?}: no free key to map it to", c))?;
+        self.map_spare(keysym)?;
+        self.tap(spare, &[])
+    }
+    
+    /// Press and release a key while holding modifiers
+    fn tap(&self, keycode: u8, modifiers: &[u8]) -> Result<()> {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::{KEY_PRESS_EVENT, KEY_RELEASE_EVENT};
+        use x11rb::protocol::xtest::ConnectionExt as _;
+        
+        let presses = modifiers.iter().chain([&keycode]).map(|&key| (KEY_PRESS_EVENT, key));
+        let releases = modifiers.iter().chain([&keycode]).rev().map(|&key| (KEY_RELEASE_EVENT, key));
+        for (event, key) in presses.chain(releases) {
+            self.conn.xtest_fake_input(event, key, x11rb::CURRENT_TIME, self.root, 0, 0, 0)?;
+        }
+        self.conn.flush()?;
+        std::thread::sleep(KEY_DELAY);
+        Ok(())
+    }
+    
+    fn map_spare(&mut self, keysym: u32) -> Result<()> {
+        self.set_spare_mapping(keysym)?;
+        self.spare_mapped = true;
+        Ok(())
+    }
+    
+    /// Put the spare keycode back the way it was
+    fn release_spare(&mut self) -> Result<()> {
+        if self.spare_mapped {
+            self.set_spare_mapping(0)?;
+            self.spare_mapped = false;
+        }
+        Ok(())
+    }
+    
+    fn set_spare_mapping(&self, keysym: u32) -> Result<()> {
+        use x11rb::protocol::xproto::ConnectionExt as _;
+        
+        let Some(spare) = self.spare else {
+            return Ok(());
+        };
+        let keysyms = vec![keysym; self.keysyms_per_keycode as usize];
+        self.conn.change_keyboard_mapping(1, spare, self.keysyms_per_keycode, &keysyms)?;
+        // Wait for the server, then give applications a moment to reload the mapping
+        self.conn.get_input_focus()?.reply()?;
+        std::thread::sleep(KEY_DELAY * 5);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_char_keysym() {
+        assert_eq!(char_keysym('a'), 0x61);
+        assert_eq!(char_keysym('é'), 0xe9);
+        assert_eq!(char_keysym('\n'), keysym::RETURN);
+        assert_eq!(char_keysym('€'), 0x0100_20ac);
+    }
+}
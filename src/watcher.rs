@@ -0,0 +1,333 @@
+//! Clipboard change detection
+//!
+//! Decides when the clipboard monitor should look at the clipboard.
+//! On Wayland compositors with the data-control protocols this listens for
+//! new offers, and on X11 for XFixes selection-owner notifications, so the
+//! monitor reacts immediately and does nothing while the clipboard is idle.
+//! Everywhere else it falls back to polling with an adaptive interval.
+
+use std::time::Duration;
+use anyhow::Result;
+use crate::backend::Selection;
+#[cfg(target_os = "linux")]
+use anyhow::{Context, anyhow};
+#[cfg(target_os = "linux")]
+use crate::backend::{PASSWORD_MANAGER_HINT, TARGETS_TIMEOUT};
+#[cfg(target_os = "linux")]
+use tracing::debug;
+use tracing::info;
+
+/// How long a detector may block before returning, so the monitor can
+/// notice that it has been stopped
+pub const WAIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Maximum factor the polling interval grows to while the clipboard is idle
+const MAX_POLL_BACKOFF: u32 = 4;
+
+/// A backend that tells the clipboard monitor when the clipboard may have changed
+pub trait ChangeDetector: Send {
+    /// Name of the backend, for logging
+    fn name(&self) -> &'static str;
+    
+    /// Block until the clipboard may have changed, or about `WAIT_TIMEOUT` passes.
+    /// Returns whether the clipboard should be read. An error means the
+    /// backend stopped working and the monitor should fall back to polling.
+    fn wait_for_change(&mut self) -> Result<bool>;
+    
+    /// Report whether the clipboard content actually changed after a wakeup
+    fn report_change(&mut self, _changed: bool) {}
+    
+    /// Whether the owner of the content that woke the monitor marked it as
+    /// secret, or None if the detector didn't find out while watching
+    fn sensitive_hint(&self) -> Option<bool> {
+        None
+    }
+}
+
+/// Pick the best change detector available on this system for a selection
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+pub fn default_detector(poll_interval: Duration, selection: Selection) -> Box<dyn ChangeDetector> {
+    // XWayland only sees some clipboard changes, so prefer the compositor's
+    #[cfg(target_os = "linux")]
+    if crate::wayland::is_available() {
+        match DataControlDetector::new(selection) {
+            Ok(detector) => {
+                info!("Using Wayland data-control notifications for the {} selection", selection);
+                return Box::new(detector);
+            }
+            Err(e) => debug!("Wayland data-control notifications unavailable: {:#}", e),
+        }
+    }
+    
+    #[cfg(target_os = "linux")]
+    match XFixesDetector::new(selection) {
+        Ok(detector) => {
+            info!("Using XFixes change notifications for the {} selection", selection);
+            return Box::new(detector);
+        }
+        Err(e) => debug!("XFixes unavailable: {:#}", e),
+    }
+    
+    info!("Polling clipboard every {} ms", poll_interval.as_millis());
+    Box::new(PollingDetector::new(poll_interval))
+}
+
+/// Polls the clipboard, backing off while its content stays the same
+pub struct PollingDetector {
+    /// Configured interval, used right after a change
+    base_interval: Duration,
+    /// Current interval
+    interval: Duration,
+}
+
+impl PollingDetector {
+    /// Create a new PollingDetector
+    pub fn new(base_interval: Duration) -> Self {
+        // A zero interval would spin
+        let base_interval = base_interval.max(Duration::from_millis(10));
+        Self {
+            base_interval,
+            interval: base_interval,
+        }
+    }
+    
+    /// Get the current polling interval
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+impl ChangeDetector for PollingDetector {
+    fn name(&self) -> &'static str {
+        "polling"
+    }
+    
+    fn wait_for_change(&mut self) -> Result<bool> {
+        std::thread::sleep(self.interval);
+        Ok(true)
+    }
+    
+    fn report_change(&mut self, changed: bool) {
+        self.interval = if changed {
+            self.base_interval
+        } else {
+            // Grow by half the base interval per idle poll, up to the cap
+            (self.interval + self.base_interval / 2).min(self.base_interval * MAX_POLL_BACKOFF)
+        };
+    }
+}
+
+/// Listens for selection owner changes through the X11 XFixes extension,
+/// and asks each new owner for its formats on the same connection, to tell
+/// the monitor about password manager hints
+#[cfg(target_os = "linux")]
+pub struct XFixesDetector {
+    /// The new owner's sensitive hint for each change, if it listed its formats
+    events: std::sync::mpsc::Receiver<Option<bool>>,
+    /// Sensitive hint of the latest change
+    hint: Option<bool>,
+}
+
+#[cfg(target_os = "linux")]
+impl XFixesDetector {
+    /// Connect to the X server and subscribe to the selection's events
+    pub fn new(selection: Selection) -> Result<Self> {
+        use std::time::Instant;
+        use x11rb::connection::Connection;
+        use x11rb::protocol::Event;
+        use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+        use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, CreateWindowAux, WindowClass};
+        
+        let (conn, screen_num) = x11rb::connect(None)
+            .context("Failed to connect to X server")?;
+        
+        // The extension version must be negotiated before any other XFixes request
+        conn.xfixes_query_version(5, 0)?
+            .reply()
+            .context("XFixes extension not available")?;
+        
+        // An invisible window to receive the selection events
+        let root = conn.setup().roots[screen_num].root;
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0, 0, 1, 1, 0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )?;
+        
+        let atom = conn.intern_atom(false, selection.atom_name().as_bytes())?.reply()?.atom;
+        let targets = conn.intern_atom(false, b"TARGETS")?.reply()?.atom;
+        let property = conn.intern_atom(false, b"9PASTE_TARGETS")?.reply()?.atom;
+        let hint_atom = conn.intern_atom(false, PASSWORD_MANAGER_HINT.as_bytes())?.reply()?.atom;
+        conn.xfixes_select_selection_input(
+            window,
+            atom,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )?;
+        conn.flush()?;
+        
+        // Block on the X connection in its own thread; it uses no CPU while idle
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            // Format requests still unanswered, and when to stop waiting for them
+            let mut outstanding = 0;
+            let mut deadline = Instant::now();
+            loop {
+                let event = if outstanding > 0 {
+                    conn.poll_for_event()
+                } else {
+                    conn.wait_for_event().map(Some)
+                };
+                let hint = match event {
+                    Ok(Some(Event::XfixesSelectionNotify(event))) => {
+                        let requested = conn.convert_selection(window, atom, targets, property, event.selection_timestamp)
+                            .and_then(|_| conn.flush());
+                        if requested.is_ok() {
+                            outstanding += 1;
+                            deadline = Instant::now() + TARGETS_TIMEOUT;
+                            continue;
+                        }
+                        None
+                    }
+                    Ok(Some(Event::SelectionNotify(event))) if event.requestor == window && outstanding > 0 => {
+                        // Answers come in order, so only the latest owner's matters
+                        outstanding -= 1;
+                        if outstanding > 0 {
+                            continue;
+                        }
+                        // No property means the owner refused to list its formats
+                        let offers = |conn: &x11rb::rust_connection::RustConnection| -> Result<bool> {
+                            let reply = conn.get_property(true, window, property, AtomEnum::ATOM, 0, 1024)?.reply()?;
+                            Ok(reply.value32().is_some_and(|mut atoms| atoms.any(|atom| atom == hint_atom)))
+                        };
+                        Some(event.property != x11rb::NONE && offers(&conn).unwrap_or(false))
+                    }
+                    Ok(Some(_)) => continue,
+                    Ok(None) if Instant::now() < deadline => {
+                        std::thread::sleep(Duration::from_millis(5));
+                        continue;
+                    }
+                    Ok(None) => {
+                        // The owner didn't list its formats in time
+                        outstanding = 0;
+                        None
+                    }
+                    Err(e) => {
+                        debug!("XFixes connection error: {}", e);
+                        break;
+                    }
+                };
+                if tx.send(hint).is_err() {
+                    break;
+                }
+            }
+        });
+        
+        Ok(Self { events: rx, hint: None })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ChangeDetector for XFixesDetector {
+    fn name(&self) -> &'static str {
+        "xfixes"
+    }
+    
+    fn wait_for_change(&mut self) -> Result<bool> {
+        use std::sync::mpsc::RecvTimeoutError;
+        
+        match self.events.recv_timeout(WAIT_TIMEOUT) {
+            Ok(hint) => {
+                // Coalesce bursts of events into a single read
+                self.hint = hint;
+                while let Ok(hint) = self.events.try_recv() {
+                    self.hint = hint;
+                }
+                Ok(true)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(false),
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!("Lost connection to X server")),
+        }
+    }
+    
+    fn sensitive_hint(&self) -> Option<bool> {
+        self.hint
+    }
+}
+
+/// Listens for new selection offers through the Wayland data-control
+/// protocols, on one connection for as long as the monitor runs
+#[cfg(target_os = "linux")]
+pub struct DataControlDetector {
+    /// Whether each new offer carries the password manager hint
+    events: std::sync::mpsc::Receiver<bool>,
+    /// Sensitive hint of the latest offer
+    hint: Option<bool>,
+}
+
+#[cfg(target_os = "linux")]
+impl DataControlDetector {
+    /// Connect to the compositor and start watching the selection
+    pub fn new(selection: Selection) -> Result<Self> {
+        Ok(Self {
+            events: crate::wayland::watch(selection)?,
+            hint: None,
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ChangeDetector for DataControlDetector {
+    fn name(&self) -> &'static str {
+        "data-control"
+    }
+    
+    fn wait_for_change(&mut self) -> Result<bool> {
+        use std::sync::mpsc::RecvTimeoutError;
+        
+        match self.events.recv_timeout(WAIT_TIMEOUT) {
+            Ok(hint) => {
+                // Coalesce bursts of offers into a single read
+                self.hint = Some(hint);
+                while let Ok(hint) = self.events.try_recv() {
+                    self.hint = Some(hint);
+                }
+                Ok(true)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(false),
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!("Lost connection to the Wayland compositor")),
+        }
+    }
+    
+    fn sensitive_hint(&self) -> Option<bool> {
+        self.hint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_polling_backoff() {
+        let base = Duration::from_millis(100);
+        let mut detector = PollingDetector::new(base);
+        
+        detector.report_change(false);
+        assert_eq!(detector.interval(), Duration::from_millis(150));
+        
+        for _ in 0..20 {
+            detector.report_change(false);
+        }
+        assert_eq!(detector.interval(), base * MAX_POLL_BACKOFF);
+        
+        detector.report_change(true);
+        assert_eq!(detector.interval(), base);
+    }
+}
@@ -0,0 +1,638 @@
+//! Native Wayland clipboard access
+//!
+//! Uses the data-control protocols (`ext-data-control-v1`, or the older
+//! `wlr-data-control-unstable-v1`), which let clients without a focused window
+//! read and set the clipboard. wlroots compositors, KDE and others support them.
+//!
+//! Content set from this process is served by a thread that keeps its own
+//! connection to the compositor, so setting the clipboard never has to wait
+//! for another application to read it. The content stays available for as
+//! long as the process runs, or until something else is copied, so only the
+//! background service sets content in the background this way.
+//!
+//! The clipboard monitor watches for new offers on a connection of its own,
+//! instead of connecting again to poll.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, OwnedFd};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::time::Duration;
+use anyhow::{Result, Context};
+use tracing::{debug, warn};
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry::WlRegistry, wl_seat::WlSeat};
+use wayland_client::{delegate_noop, event_created_child, Connection, Dispatch, EventQueue, Proxy, QueueHandle};
+use wayland_protocols::ext::data_control::v1::client::{
+    ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+    ext_data_control_manager_v1::ExtDataControlManagerV1,
+    ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+    ext_data_control_source_v1::{self, ExtDataControlSourceV1},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+use crate::backend::{self, ClipboardBackend, Selection};
+use crate::images::{ClipboardImage, ImageFormat};
+
+/// MIME types text is offered and read as, most preferred first
+const TEXT_MIME_TYPES: [&str; 5] = ["text/plain;charset=utf-8", "UTF8_STRING", "text/plain", "STRING", "TEXT"];
+
+/// MIME type of HTML
+const HTML_MIME_TYPE: &str = "text/html";
+
+/// How long the clipboard owner may go without sending anything before
+/// reading gives up
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The connection serving content set from this process, started on first use
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+
+/// Check whether the compositor supports the data-control protocols.
+/// Checked once, since the session doesn't change while we run.
+pub fn is_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| match Session::connect() {
+        Ok(_) => true,
+        Err(e) => {
+            debug!("Wayland data-control unavailable: {:#}", e);
+            false
+        }
+    })
+}
+
+/// The Wayland clipboard, through the data-control protocols
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WaylandBackend {
+    selection: Selection,
+}
+
+impl WaylandBackend {
+    /// Create a backend for a selection
+    pub fn new(selection: Selection) -> Self {
+        Self { selection }
+    }
+    
+    /// Read the selection in the first of `mime_types` it is offered as,
+    /// or None if it is empty or offered in none of them
+    fn read(&self, mime_types: &[&str]) -> Result<Option<Vec<u8>>> {
+        Session::connect()?.read(self.selection, mime_types)
+    }
+    
+    /// List the MIME types the selection is offered as
+    fn offered(&self) -> Result<Vec<String>> {
+        Ok(Session::connect()?.state.offered(self.selection).to_vec())
+    }
+    
+    /// Hand content to the serving thread, and wait until something else is copied
+    fn serve_and_wait(&self, contents: Contents) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        serve(self.selection, contents.notify(tx))?;
+        // Also returns if the serving thread stops
+        rx.recv().ok();
+        Ok(())
+    }
+}
+
+impl ClipboardBackend for WaylandBackend {
+    /// Fails if the clipboard holds no text, like arboard does
+    fn get_text(&self) -> Result<String> {
+        let bytes = self.read(&TEXT_MIME_TYPES)?
+            .context("The clipboard holds no text")?;
+        String::from_utf8(bytes)
+            .context("Clipboard text is not valid UTF-8")
+    }
+    
+    /// Waits until something else is copied, like arboard does on Linux
+    fn set_text(&self, text: &str) -> Result<()> {
+        self.serve_and_wait(Contents::text(text))
+    }
+    
+    fn set_text_background(&self, text: &str) -> Result<()> {
+        serve(self.selection, Contents::text(text))
+    }
+    
+    fn get_html(&self) -> Result<Option<String>> {
+        Ok(self.read(&[HTML_MIME_TYPE])?.map(|bytes| backend::decode_html_bytes(&bytes)))
+    }
+    
+    /// Waits until something else is copied, like arboard does on Linux
+    fn set_html(&self, html: &str, alt_text: &str) -> Result<()> {
+        self.serve_and_wait(Contents::html(html, alt_text))
+    }
+    
+    fn set_html_background(&self, html: &str, alt_text: &str) -> Result<()> {
+        serve(self.selection, Contents::html(html, alt_text))
+    }
+    
+    /// Keeps the format the image was copied in
+    fn get_image(&self) -> Result<Option<ClipboardImage>> {
+        let offered = self.offered()?;
+        let Some(format) = ImageFormat::ALL.into_iter().find(|format| offered.iter().any(|m| m == format.mime_type())) else {
+            return Ok(None);
+        };
+        Ok(self.read(&[format.mime_type()])?.map(|bytes| ClipboardImage::new(format, bytes)))
+    }
+    
+    /// Offers the image in its own format only.
+    /// Waits until something else is copied, like arboard does on Linux.
+    fn set_image(&self, image: &ClipboardImage) -> Result<()> {
+        self.serve_and_wait(Contents::image(image))
+    }
+    
+    fn set_image_background(&self, image: &ClipboardImage) -> Result<()> {
+        serve(self.selection, Contents::image(image))
+    }
+    
+    fn selection(&self) -> Selection {
+        self.selection
+    }
+    
+    fn has_sensitive_hint(&self) -> bool {
+        self.offered().is_ok_and(|offered| offered.iter().any(|m| m == backend::PASSWORD_MANAGER_HINT))
+    }
+}
+
+/// Content offered to other applications: the bytes sent for each MIME type
+struct Contents {
+    formats: Vec<(&'static str, Arc<[u8]>)>,
+    /// Told when something else is copied
+    replaced: Mutex<Option<mpsc::Sender<()>>>,
+}
+
+impl Contents {
+    fn new(formats: Vec<(&'static str, Arc<[u8]>)>) -> Self {
+        Self {
+            formats,
+            replaced: Mutex::new(None),
+        }
+    }
+    
+    fn text(text: &str) -> Self {
+        let bytes: Arc<[u8]> = text.as_bytes().into();
+        Self::new(TEXT_MIME_TYPES.iter().map(|mime| (*mime, Arc::clone(&bytes))).collect())
+    }
+    
+    fn html(html: &str, alt_text: &str) -> Self {
+        let mut contents = Self::text(alt_text);
+        contents.formats.insert(0, (HTML_MIME_TYPE, html.as_bytes().into()));
+        contents
+    }
+    
+    fn image(image: &ClipboardImage) -> Self {
+        Self::new(vec![(image.format.mime_type(), image.bytes.as_slice().into())])
+    }
+    
+    /// Tell `tx` when something else is copied
+    fn notify(self, tx: mpsc::Sender<()>) -> Self {
+        *self.replaced.lock().unwrap() = Some(tx);
+        self
+    }
+    
+    /// Write the content in the requested format to another application
+    fn send(&self, mime_type: &str, fd: OwnedFd) {
+        let Some((_, bytes)) = self.formats.iter().find(|(mime, _)| *mime == mime_type) else {
+            return;
+        };
+        // A slow reader mustn't hold up other requests
+        let bytes = Arc::clone(bytes);
+        std::thread::spawn(move || {
+            if let Err(e) = std::fs::File::from(fd).write_all(&bytes) {
+                debug!("Failed to send clipboard content: {}", e);
+            }
+        });
+    }
+    
+    fn cancelled(&self) {
+        if let Some(tx) = self.replaced.lock().unwrap().take() {
+            tx.send(()).ok();
+        }
+    }
+}
+
+/// Watch a selection for new offers on one connection, for as long as the
+/// receiver is kept. Sends whether each new offer carries the password manager hint.
+pub fn watch(selection: Selection) -> Result<mpsc::Receiver<bool>> {
+    let Session { conn, mut queue, mut state, manager, device } = Session::connect()?;
+    if selection == Selection::Primary && !manager.supports_primary() {
+        anyhow::bail!("The compositor doesn't support the primary selection");
+    }
+    
+    let (tx, rx) = mpsc::channel();
+    state.watch = Some((selection, tx));
+    std::thread::Builder::new()
+        .name("wayland-clipboard-watch".into())
+        .spawn(move || {
+            // The device only reports offers while the connection is open
+            let _session = (conn, manager, device);
+            while state.watch.is_some() {
+                if let Err(e) = queue.blocking_dispatch(&mut state) {
+                    debug!("Wayland connection error: {}", e);
+                    break;
+                }
+            }
+        })
+        .context("Failed to start the Wayland clipboard watch thread")?;
+    Ok(rx)
+}
+
+/// Set a selection, starting the serving thread if it isn't running
+fn serve(selection: Selection, contents: Contents) -> Result<()> {
+    let mut server = SERVER.lock().unwrap();
+    let server = match *server {
+        Some(ref server) => server,
+        None => server.insert(Server::start()?),
+    };
+    server.set(selection, contents)
+}
+
+/// Handle to the thread serving content set from this process
+struct Server {
+    conn: Connection,
+    qh: QueueHandle<State>,
+    manager: Manager,
+    device: Device,
+}
+
+impl Server {
+    fn start() -> Result<Self> {
+        let Session { conn, mut queue, mut state, manager, device } = Session::connect()?;
+        let server = Self {
+            conn,
+            qh: queue.handle(),
+            manager,
+            device,
+        };
+        
+        std::thread::Builder::new()
+            .name("wayland-clipboard".into())
+            .spawn(move || loop {
+                if let Err(e) = queue.blocking_dispatch(&mut state) {
+                    warn!("Stopped serving the Wayland clipboard: {}", e);
+                    *SERVER.lock().unwrap() = None;
+                    break;
+                }
+            })
+            .context("Failed to start the Wayland clipboard thread")?;
+        Ok(server)
+    }
+    
+    fn set(&self, selection: Selection, contents: Contents) -> Result<()> {
+        if selection == Selection::Primary && !self.manager.supports_primary() {
+            anyhow::bail!("The compositor doesn't support setting the primary selection");
+        }
+        
+        let mime_types: Vec<&'static str> = contents.formats.iter().map(|(mime, _)| *mime).collect();
+        let source = self.manager.create_data_source(&self.qh, Arc::new(contents));
+        for mime in mime_types {
+            source.offer(mime);
+        }
+        self.device.set(selection, &source);
+        self.conn.flush()
+            .context("Failed to set the Wayland clipboard")
+    }
+}
+
+/// A connection to the compositor, with a data-control device for the first seat
+struct Session {
+    conn: Connection,
+    queue: EventQueue<State>,
+    state: State,
+    manager: Manager,
+    device: Device,
+}
+
+impl Session {
+    fn connect() -> Result<Self> {
+        let conn = Connection::connect_to_env()
+            .context("Not running under Wayland")?;
+        let (globals, mut queue) = registry_queue_init::<State>(&conn)
+            .context("Failed to list Wayland globals")?;
+        let qh = queue.handle();
+        
+        let seat: WlSeat = globals.bind(&qh, 1..=1, ())
+            .context("No Wayland seat")?;
+        // Version 2 of the wlr protocol added the primary selection
+        let manager = match globals.bind(&qh, 1..=1, ()) {
+            Ok(manager) => Manager::Ext(manager),
+            Err(_) => Manager::Wlr(globals.bind(&qh, 1..=2, ())
+                .context("The compositor doesn't support the data-control protocols")?),
+        };
+        let device = manager.get_data_device(&seat, &qh);
+        
+        // The device announces the current selections right away
+        let mut state = State::default();
+        queue.roundtrip(&mut state)
+            .context("Failed to read the Wayland clipboard")?;
+        Ok(Self { conn, queue, state, manager, device })
+    }
+    
+    fn read(&self, selection: Selection, mime_types: &[&str]) -> Result<Option<Vec<u8>>> {
+        let Some(offer) = self.state.offer(selection) else {
+            return Ok(None);
+        };
+        let offered = self.state.offered(selection);
+        let Some(mime) = mime_types.iter().find(|mime| offered.iter().any(|m| m == *mime)) else {
+            return Ok(None);
+        };
+        
+        let (reader, writer) = std::io::pipe()
+            .context("Failed to create a pipe")?;
+        offer.receive(mime.to_string(), writer.as_fd());
+        drop(writer);
+        self.conn.flush()
+            .context("Failed to read the Wayland clipboard")?;
+        
+        read_pipe(reader, READ_TIMEOUT).map(Some)
+    }
+}
+
+/// Read everything another application sends through a pipe, giving up if it
+/// stalls for `timeout` without closing its end
+fn read_pipe(mut reader: std::io::PipeReader, timeout: Duration) -> Result<Vec<u8>> {
+    use rustix::event::{poll, PollFd, PollFlags, Timespec};
+    
+    let timeout = Timespec::try_from(timeout)?;
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 64 * 1024];
+    loop {
+        let mut fds = [PollFd::new(&reader, PollFlags::IN)];
+        match poll(&mut fds, Some(&timeout)) {
+            Ok(0) => anyhow::bail!("The clipboard owner stopped sending its content"),
+            Ok(_) => {}
+            Err(rustix::io::Errno::INTR) => continue,
+            Err(e) => return Err(e).context("Failed to read the Wayland clipboard"),
+        }
+        
+        // Readable, or closed by the writer, so this doesn't block
+        match reader.read(&mut chunk) {
+            Ok(0) => return Ok(bytes),
+            Ok(n) => bytes.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e).context("Failed to read the Wayland clipboard"),
+        }
+    }
+}
+
+/// What the compositor told us about the selections
+#[derive(Default)]
+struct State {
+    /// MIME types each offer is available as
+    mime_types: HashMap<ObjectId, Vec<String>>,
+    /// Offer holding the clipboard
+    clipboard: Option<Offer>,
+    /// Offer holding the primary selection
+    primary: Option<Offer>,
+    /// Selection being watched, and where to tell about its new offers
+    watch: Option<(Selection, mpsc::Sender<bool>)>,
+}
+
+impl State {
+    fn offer(&self, selection: Selection) -> Option<&Offer> {
+        match selection {
+            Selection::Clipboard => self.clipboard.as_ref(),
+            Selection::Primary => self.primary.as_ref(),
+        }
+    }
+    
+    /// MIME types the selection is offered as
+    fn offered(&self, selection: Selection) -> &[String] {
+        self.offer(selection)
+            .and_then(|offer| self.mime_types.get(&offer.id()))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+    
+    /// Remember the new offer of a selection, releasing the one it replaces
+    fn set_offer(&mut self, selection: Selection, offer: Option<Offer>) {
+        let slot = match selection {
+            Selection::Clipboard => &mut self.clipboard,
+            Selection::Primary => &mut self.primary,
+        };
+        if let Some(old) = std::mem::replace(slot, offer) {
+            let in_use = [&self.clipboard, &self.primary].into_iter().flatten().any(|o| o.id() == old.id());
+            if !in_use {
+                self.mime_types.remove(&old.id());
+                old.destroy();
+            }
+        }
+        self.notify(selection);
+    }
+    
+    /// Tell the watcher about a new offer of its selection. The offer lists
+    /// its MIME types before it is announced as the selection.
+    fn notify(&mut self, selection: Selection) {
+        let Some((watched, tx)) = &self.watch else {
+            return;
+        };
+        if *watched != selection {
+            return;
+        }
+        let hint = self.offered(selection).iter().any(|m| m == backend::PASSWORD_MANAGER_HINT);
+        if tx.send(hint).is_err() {
+            self.watch = None;
+        }
+    }
+}
+
+/// The data-control manager, from whichever protocol the compositor supports
+enum Manager {
+    Ext(ExtDataControlManagerV1),
+    Wlr(ZwlrDataControlManagerV1),
+}
+
+impl Manager {
+    fn supports_primary(&self) -> bool {
+        match self {
+            Self::Ext(_) => true,
+            Self::Wlr(manager) => manager.version() >= 2,
+        }
+    }
+    
+    fn get_data_device(&self, seat: &WlSeat, qh: &QueueHandle<State>) -> Device {
+        match self {
+            Self::Ext(manager) => Device::Ext(manager.get_data_device(seat, qh, ())),
+            Self::Wlr(manager) => Device::Wlr(manager.get_data_device(seat, qh, ())),
+        }
+    }
+    
+    fn create_data_source(&self, qh: &QueueHandle<State>, contents: Arc<Contents>) -> Source {
+        match self {
+            Self::Ext(manager) => Source::Ext(manager.create_data_source(qh, contents)),
+            Self::Wlr(manager) => Source::Wlr(manager.create_data_source(qh, contents)),
+        }
+    }
+}
+
+/// A data-control device, for setting the selections of a seat
+enum Device {
+    Ext(ExtDataControlDeviceV1),
+    Wlr(ZwlrDataControlDeviceV1),
+}
+
+impl Device {
+    fn set(&self, selection: Selection, source: &Source) {
+        match (self, source, selection) {
+            (Self::Ext(device), Source::Ext(source), Selection::Clipboard) => device.set_selection(Some(source)),
+            (Self::Ext(device), Source::Ext(source), Selection::Primary) => device.set_primary_selection(Some(source)),
+            (Self::Wlr(device), Source::Wlr(source), Selection::Clipboard) => device.set_selection(Some(source)),
+            (Self::Wlr(device), Source::Wlr(source), Selection::Primary) => device.set_primary_selection(Some(source)),
+            // Both come from the same manager
+            _ => unreachable!("Data-control device and source from different protocols"),
+        }
+    }
+}
+
+/// Content we offer to other applications
+enum Source {
+    Ext(ExtDataControlSourceV1),
+    Wlr(ZwlrDataControlSourceV1),
+}
+
+impl Source {
+    fn offer(&self, mime_type: &str) {
+        match self {
+            Self::Ext(source) => source.offer(mime_type.to_string()),
+            Self::Wlr(source) => source.offer(mime_type.to_string()),
+        }
+    }
+}
+
+/// Content another application offers
+enum Offer {
+    Ext(ExtDataControlOfferV1),
+    Wlr(ZwlrDataControlOfferV1),
+}
+
+impl Offer {
+    fn id(&self) -> ObjectId {
+        match self {
+            Self::Ext(offer) => offer.id(),
+            Self::Wlr(offer) => offer.id(),
+        }
+    }
+    
+    fn receive(&self, mime_type: String, fd: std::os::fd::BorrowedFd<'_>) {
+        match self {
+            Self::Ext(offer) => offer.receive(mime_type, fd),
+            Self::Wlr(offer) => offer.receive(mime_type, fd),
+        }
+    }
+    
+    fn destroy(&self) {
+        match self {
+            Self::Ext(offer) => offer.destroy(),
+            Self::Wlr(offer) => offer.destroy(),
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(_: &mut Self, _: &WlRegistry, _: <WlRegistry as Proxy>::Event, _: &GlobalListContents, _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+delegate_noop!(State: ignore WlSeat);
+delegate_noop!(State: ExtDataControlManagerV1);
+delegate_noop!(State: ZwlrDataControlManagerV1);
+
+impl Dispatch<ExtDataControlDeviceV1, ()> for State {
+    fn event(state: &mut Self, _: &ExtDataControlDeviceV1, event: ext_data_control_device_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        use ext_data_control_device_v1::Event;
+        match event {
+            Event::DataOffer { id } => {
+                state.mime_types.insert(id.id(), Vec::new());
+            }
+            Event::Selection { id } => state.set_offer(Selection::Clipboard, id.map(Offer::Ext)),
+            Event::PrimarySelection { id } => state.set_offer(Selection::Primary, id.map(Offer::Ext)),
+            _ => {}
+        }
+    }
+    
+    event_created_child!(State, ExtDataControlDeviceV1, [
+        ext_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ExtDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+    fn event(state: &mut Self, _: &ZwlrDataControlDeviceV1, event: zwlr_data_control_device_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        use zwlr_data_control_device_v1::Event;
+        match event {
+            Event::DataOffer { id } => {
+                state.mime_types.insert(id.id(), Vec::new());
+            }
+            Event::Selection { id } => state.set_offer(Selection::Clipboard, id.map(Offer::Wlr)),
+            Event::PrimarySelection { id } => state.set_offer(Selection::Primary, id.map(Offer::Wlr)),
+            _ => {}
+        }
+    }
+    
+    event_created_child!(State, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtDataControlOfferV1, ()> for State {
+    fn event(state: &mut Self, offer: &ExtDataControlOfferV1, event: ext_data_control_offer_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        let ext_data_control_offer_v1::Event::Offer { mime_type } = event else {
+            return;
+        };
+        state.mime_types.entry(offer.id()).or_default().push(mime_type);
+    }
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+    fn event(state: &mut Self, offer: &ZwlrDataControlOfferV1, event: zwlr_data_control_offer_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event else {
+            return;
+        };
+        state.mime_types.entry(offer.id()).or_default().push(mime_type);
+    }
+}
+
+impl Dispatch<ExtDataControlSourceV1, Arc<Contents>> for State {
+    fn event(_: &mut Self, source: &ExtDataControlSourceV1, event: ext_data_control_source_v1::Event, contents: &Arc<Contents>, _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            ext_data_control_source_v1::Event::Send { mime_type, fd } => contents.send(&mime_type, fd),
+            ext_data_control_source_v1::Event::Cancelled => {
+                source.destroy();
+                contents.cancelled();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, Arc<Contents>> for State {
+    fn event(_: &mut Self, source: &ZwlrDataControlSourceV1, event: zwlr_data_control_source_v1::Event, contents: &Arc<Contents>, _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            zwlr_data_control_source_v1::Event::Send { mime_type, fd } => contents.send(&mime_type, fd),
+            zwlr_data_control_source_v1::Event::Cancelled => {
+                source.destroy();
+                contents.cancelled();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_read_pipe_gives_up_on_stalled_writer() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        writer.write_all(b"partial").unwrap();
+        assert!(read_pipe(reader, Duration::from_millis(50)).is_err());
+        
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        writer.write_all(b"complete").unwrap();
+        drop(writer);
+        assert_eq!(read_pipe(reader, Duration::from_millis(50)).unwrap(), b"complete");
+    }
+}